@@ -0,0 +1,350 @@
+use chrono::{DateTime, Local};
+use std::collections::HashMap;
+use std::time::Duration;
+
+use crate::config::Config;
+use crate::date_range::DateRange;
+use crate::enrichment::{split_shares, CommentMode, HamsterEnrichedData, TaskLink};
+use crate::hamster::{HamsterData, HamsterFact};
+use crate::utils::{unique_lines, DurationFormatting};
+
+/// How facts are turned into tasks
+#[derive(Default)]
+pub struct AggregationOptions {
+    /// only take facts of this category into account
+    pub category: Option<String>,
+    /// split time of facts with several task links between all of them
+    pub split_links: bool,
+    pub comment_mode: CommentMode,
+}
+
+/// Time of a task along with what was done on it
+pub struct TaskData {
+    pub title: Option<String>,
+    pub duration: Duration,
+    pub comments: Vec<String>,
+    /// notes on facts whose time was split between several tasks
+    pub splits: Vec<String>,
+    /// time is booked to the project itself, task id being the project id
+    pub project: bool,
+    /// set by billing rules, otherwise left to Everhour
+    pub billable: Option<bool>,
+}
+
+impl TaskData {
+    /// Id of the task (or project) in Everhour
+    pub fn everhour_id(&self, task_id: &str) -> String {
+        if self.project {
+            // project ids are configured in Everhour form already
+            task_id.to_string()
+        } else {
+            format!("as:{task_id}")
+        }
+    }
+
+    /// Comment to send to the tracker - comments followed by split notes
+    pub fn comment(&self) -> String {
+        self.comments
+            .iter()
+            .chain(self.splits.iter())
+            .cloned()
+            .collect::<Vec<String>>()
+            .join("\n")
+    }
+}
+
+/// Finds task id and title for the fact by its first link, falling back to task
+/// mappings and then to project mappings - the latter are flagged by the last value.
+/// Tells why the fact can't be synced if none of these work.
+pub fn resolve_task(
+    record: &HamsterFact,
+    config: &Config,
+) -> Result<(Option<String>, Option<String>, bool), String> {
+    let mut task_id: Option<String>;
+    let mut title: Option<String>;
+    let mut href: Option<String> = None;
+
+    if let Some(task_link) = record.task() {
+        task_id = task_link.task_id;
+        title = Some(task_link.link_title);
+        href = Some(task_link.href);
+    } else {
+        task_id = None;
+        title = None;
+    }
+
+    if task_id.is_none() {
+        // recurring work without a link - fall back to configured mappings
+        if let Some(mapping) = config.find_mapping(&record.category, &record.activity) {
+            task_id = Some(mapping.task_id.clone());
+            title = title.or(Some(record.activity.clone()));
+        }
+    }
+
+    if task_id.is_none() {
+        if let Some(project) = config.find_project(record) {
+            return Ok((
+                Some(project.project_id.clone()),
+                title.or(Some(record.activity.clone())),
+                true,
+            ));
+        }
+    }
+
+    if task_id.is_none() {
+        return Err(match href {
+            Some(href) => format!(
+                "Error obtaining task id from fact {} ('{}' at {}) - \
+                link {href} doesn't look like a task link",
+                record.id, record.activity, record.start_time
+            ),
+            None => format!(
+                "Error obtaining task id from fact {} ('{}' at {}) - \
+                add a link to the description or a mapping via `ham-cli mappings add`",
+                record.id, record.activity, record.start_time
+            ),
+        });
+    }
+    Ok((task_id, title, false))
+}
+
+/// Tasks of facts started on days of the range (both ends included), by task id
+pub fn get_tasks_with_durations(
+    hamster_db: Option<String>,
+    range: DateRange,
+    config: &Config,
+    options: &AggregationOptions,
+) -> HashMap<Option<String>, TaskData> {
+    let hamster_data = HamsterData::open(hamster_db).unwrap();
+    aggregate_facts(hamster_data.get_facts(range), config, options, Local::now())
+}
+
+/// Turns facts into tasks, by task id. Running facts are counted up to `now`.
+pub fn aggregate_facts(
+    facts: Vec<HamsterFact>,
+    config: &Config,
+    options: &AggregationOptions,
+    now: DateTime<Local>,
+) -> HashMap<Option<String>, TaskData> {
+    let facts = match &options.category {
+        None => facts,
+        Some(category) => facts
+            .into_iter()
+            .filter(|fact| &fact.category == category)
+            .collect(),
+    };
+
+    let mut tasks: HashMap<Option<String>, TaskData> = HashMap::new();
+
+    for record in facts {
+        let end_time = record.end_time.unwrap_or(now);
+        // ending before it starts - checks report such facts, there's no time to book
+        let Ok(fact_duration) = (end_time - record.start_time).to_std() else {
+            continue;
+        };
+        let comments = unique_lines(record.comments_in_mode(options.comment_mode));
+        let billable = config.billable(&record);
+
+        // links in comments, like a reviewed PR, don't take a share of the time
+        let task_links: Vec<TaskLink> = record
+            .booking_links()
+            .into_iter()
+            .filter(|task_link| task_link.task_id.is_some())
+            .collect();
+
+        // task id, title, share of the fact time and whether it's a project
+        let mut bookings: Vec<(Option<String>, Option<String>, f64, bool)> = vec![];
+
+        if options.split_links && task_links.len() > 1 {
+            let weights: Vec<Option<u32>> = task_links
+                .iter()
+                .map(|task_link| task_link.weight)
+                .collect();
+            for (task_link, share) in task_links.into_iter().zip(split_shares(&weights)) {
+                bookings.push((task_link.task_id, Some(task_link.link_title), share, false));
+            }
+        } else {
+            // facts that can't be synced end up under no task id - it's up to
+            // the caller whether that's fine
+            let (task_id, title, project) = resolve_task(&record, config).unwrap_or((
+                None,
+                Some(record.activity.clone()),
+                false,
+            ));
+            bookings.push((task_id, title, 1.0, project));
+        }
+
+        for (task_id, title, share, project) in bookings {
+            if share == 0.0 {
+                continue;
+            }
+            let duration = fact_duration.mul_f64(share);
+            let mut comments = comments.clone();
+            let mut splits = vec![];
+            if share < 1.0 {
+                splits.push(format!(
+                    "split: {:.0}% of {}",
+                    share * 100.0,
+                    fact_duration.as_hhmm()
+                ));
+            }
+
+            tasks
+                .entry(task_id)
+                .and_modify(|task_data: &mut TaskData| {
+                    task_data.duration += duration;
+                    let mut unique_comments = vec![];
+                    unique_comments.append(&mut task_data.comments);
+                    unique_comments.append(&mut comments);
+                    task_data.comments = unique_lines(unique_comments);
+                    task_data.splits.append(&mut splits);
+                    // Everhour has a single flag per record, so billable time wins
+                    // (None < Some(false) < Some(true))
+                    task_data.billable = task_data.billable.max(billable);
+                })
+                .or_insert(TaskData {
+                    title,
+                    duration,
+                    comments,
+                    splits,
+                    project,
+                    billable,
+                });
+        }
+    }
+    tasks
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{DateTime, Local, NaiveDate};
+    use std::time::Duration;
+
+    use crate::config::Config;
+    use crate::hamster::HamsterFact;
+
+    use super::{aggregate_facts, AggregationOptions};
+
+    fn at(hour: u32, minute: u32) -> DateTime<Local> {
+        NaiveDate::from_ymd_opt(2024, 5, 13)
+            .unwrap()
+            .and_hms_opt(hour, minute, 0)
+            .unwrap()
+            .and_local_timezone(Local)
+            .unwrap()
+    }
+
+    fn fact(
+        start: DateTime<Local>,
+        end: Option<DateTime<Local>>,
+        description: &str,
+    ) -> HamsterFact {
+        HamsterFact {
+            id: 1,
+            start_time: start,
+            end_time: end,
+            description: String::from(description),
+            activity: String::from("coding"),
+            category: String::from("Work"),
+            tags: vec![],
+        }
+    }
+
+    #[test]
+    fn facts_summed_by_task() {
+        let facts = vec![
+            fact(
+                at(9, 0),
+                Some(at(10, 0)),
+                "[Parser](https://app.asana.com/0/0/111/f)\n- fixed it",
+            ),
+            fact(
+                at(10, 0),
+                Some(at(10, 30)),
+                "[Parser](https://app.asana.com/0/0/111/f)\n- tested it",
+            ),
+            fact(at(11, 0), None, "[Docs](https://app.asana.com/0/0/222/f)"),
+        ];
+
+        let tasks = aggregate_facts(
+            facts,
+            &Config::default(),
+            &AggregationOptions::default(),
+            at(11, 15),
+        );
+
+        let parser = &tasks[&Some(String::from("111"))];
+        assert_eq!(parser.duration, Duration::new(90 * 60, 0));
+        assert_eq!(parser.comment(), "fixed it\ntested it");
+        assert_eq!(parser.everhour_id("111"), "as:111");
+        assert_eq!(
+            tasks[&Some(String::from("222"))].duration,
+            Duration::new(15 * 60, 0)
+        );
+    }
+
+    #[test]
+    fn time_split_between_booking_links_only() {
+        let options = AggregationOptions {
+            split_links: true,
+            ..Default::default()
+        };
+        let facts = vec![fact(
+            at(9, 0),
+            Some(at(10, 0)),
+            "[Parser](https://app.asana.com/0/0/111/f) and [Docs](https://app.asana.com/0/0/222/f)\n\
+            - reviewed [PR](https://app.asana.com/0/0/333/f)",
+        )];
+
+        let tasks = aggregate_facts(facts, &Config::default(), &options, at(11, 0));
+
+        assert_eq!(tasks.len(), 2);
+        for task_id in ["111", "222"] {
+            let task = &tasks[&Some(String::from(task_id))];
+            assert_eq!(task.duration, Duration::new(30 * 60, 0));
+            assert_eq!(task.splits, ["split: 50% of 1:00"]);
+        }
+    }
+
+    #[test]
+    fn facts_ending_before_start_skipped() {
+        let facts = vec![
+            fact(
+                at(10, 0),
+                Some(at(9, 0)),
+                "[Parser](https://app.asana.com/0/0/111/f)",
+            ),
+            fact(
+                at(10, 0),
+                Some(at(10, 30)),
+                "[Parser](https://app.asana.com/0/0/111/f)",
+            ),
+        ];
+
+        let tasks = aggregate_facts(
+            facts,
+            &Config::default(),
+            &AggregationOptions::default(),
+            at(11, 0),
+        );
+
+        assert_eq!(
+            tasks[&Some(String::from("111"))].duration,
+            Duration::new(30 * 60, 0)
+        );
+    }
+
+    #[test]
+    fn other_categories_skipped() {
+        let options = AggregationOptions {
+            category: Some(String::from("Personal")),
+            ..Default::default()
+        };
+        let facts = vec![fact(
+            at(9, 0),
+            Some(at(10, 0)),
+            "[Parser](https://app.asana.com/0/0/111/f)",
+        )];
+        assert!(aggregate_facts(facts, &Config::default(), &options, at(11, 0)).is_empty());
+    }
+}
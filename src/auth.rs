@@ -0,0 +1,216 @@
+//! Everhour API token kept out of shell history and dotfiles: in a file only the
+//! user can read, or printed by a command like `pass show everhour`
+use std::fmt;
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use crate::config::{self, Config};
+
+/// Where the token in use came from
+#[derive(Debug, PartialEq)]
+pub enum TokenSource {
+    /// --api-token or EVERHOUR_API_TOKEN
+    Argument,
+    Command(String),
+    File(PathBuf),
+}
+
+impl fmt::Display for TokenSource {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TokenSource::Argument => write!(f, "--api-token/EVERHOUR_API_TOKEN"),
+            TokenSource::Command(command) => write!(f, "token_command `{command}`"),
+            TokenSource::File(path) => write!(f, "{}", path.display()),
+        }
+    }
+}
+
+/// Token file, next to the config file
+pub fn token_path(config_path: Option<String>) -> Result<PathBuf, String> {
+    let config_path = config::resolve_path(config_path)?;
+    Ok(config_path
+        .parent()
+        .unwrap_or(Path::new("."))
+        .join("everhour-token"))
+}
+
+/// Writes the token, readable by the user only
+pub fn save_token(path: &Path, token: &str) -> Result<(), String> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)
+            .map_err(|err| format!("couldn't create {}: {}", parent.display(), err))?;
+    }
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::{OpenOptionsExt, PermissionsExt};
+        options.mode(0o600);
+        // mode only applies to new files - an older file may be readable by others
+        if path.exists() {
+            fs::set_permissions(path, fs::Permissions::from_mode(0o600))
+                .map_err(|err| format!("couldn't restrict {}: {}", path.display(), err))?;
+        }
+    }
+    let mut file = options
+        .open(path)
+        .map_err(|err| format!("couldn't write {}: {}", path.display(), err))?;
+    writeln!(file, "{token}").map_err(|err| format!("couldn't write {}: {}", path.display(), err))
+}
+
+/// Token stored in the file, if there's one
+pub fn load_token(path: &Path) -> Result<Option<String>, String> {
+    if !path.exists() {
+        return Ok(None);
+    }
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        let mode = fs::metadata(path)
+            .map_err(|err| format!("couldn't read {}: {}", path.display(), err))?
+            .permissions()
+            .mode();
+        if mode & 0o077 != 0 {
+            eprintln!(
+                "Warning: {} can be read by other users - run `chmod 600` on it",
+                path.display()
+            );
+        }
+    }
+    let token = fs::read_to_string(path)
+        .map_err(|err| format!("couldn't read {}: {}", path.display(), err))?;
+    let token = token.trim();
+    Ok((!token.is_empty()).then(|| token.to_string()))
+}
+
+/// Removes the token file, returns whether there was one
+pub fn remove_token(path: &Path) -> Result<bool, String> {
+    if !path.exists() {
+        return Ok(false);
+    }
+    fs::remove_file(path).map_err(|err| format!("couldn't remove {}: {}", path.display(), err))?;
+    Ok(true)
+}
+
+/// First line the command prints, run by the shell
+pub fn run_token_command(command: &str) -> Result<String, String> {
+    let output = Command::new("sh")
+        .arg("-c")
+        .arg(command)
+        .output()
+        .map_err(|err| format!("couldn't run `{command}`: {err}"))?;
+    if !output.status.success() {
+        return Err(format!(
+            "`{command}` failed with {}: {}",
+            output.status,
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    match stdout.lines().next().map(str::trim) {
+        Some(token) if !token.is_empty() => Ok(token.to_string()),
+        _ => Err(format!("`{command}` printed no token")),
+    }
+}
+
+/// Token given on the command line, printed by the token command or stored by
+/// `auth login` - in that order
+pub fn find_token(
+    argument: Option<String>,
+    config: &Config,
+    path: &Path,
+) -> Result<Option<(String, TokenSource)>, String> {
+    if let Some(token) = argument {
+        return Ok(Some((token, TokenSource::Argument)));
+    }
+    if let Some(command) = &config.token_command {
+        let token = run_token_command(command)?;
+        return Ok(Some((token, TokenSource::Command(command.clone()))));
+    }
+    Ok(load_token(path)?.map(|token| (token, TokenSource::File(path.to_path_buf()))))
+}
+
+/// Token to call Everhour with, failing if there's none anywhere
+pub fn everhour_token(
+    argument: Option<String>,
+    config: &Config,
+    config_path: Option<String>,
+) -> Result<String, String> {
+    match find_token(argument, config, &token_path(config_path)?)? {
+        Some((token, _)) => Ok(token),
+        None => Err(String::from(
+            "No Everhour API token - run `ham-cli auth login`, \
+            set token_command in the config or pass --api-token",
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use crate::config::Config;
+
+    use super::{find_token, load_token, remove_token, run_token_command, save_token, TokenSource};
+
+    fn temp_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!(
+            "ham-cli-{}-{name}/everhour-token",
+            std::process::id()
+        ))
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn token_saved_for_user_only() {
+        use std::os::unix::fs::PermissionsExt;
+        let path = temp_path("token_saved_for_user_only");
+
+        save_token(&path, "secret").unwrap();
+
+        let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+        assert_eq!(load_token(&path).unwrap(), Some(String::from("secret")));
+        assert!(remove_token(&path).unwrap());
+        assert!(!remove_token(&path).unwrap());
+        assert_eq!(load_token(&path).unwrap(), None);
+    }
+
+    #[test]
+    fn argument_wins_over_command_and_file() {
+        let path = temp_path("argument_wins_over_command_and_file");
+        save_token(&path, "from file").unwrap();
+        let mut config = Config::default();
+
+        assert_eq!(
+            find_token(None, &config, &path).unwrap(),
+            Some((String::from("from file"), TokenSource::File(path.clone())))
+        );
+        config.token_command = Some(String::from("echo from command"));
+        assert_eq!(
+            find_token(None, &config, &path).unwrap(),
+            Some((
+                String::from("from command"),
+                TokenSource::Command(String::from("echo from command"))
+            ))
+        );
+        assert_eq!(
+            find_token(Some(String::from("given")), &config, &path).unwrap(),
+            Some((String::from("given"), TokenSource::Argument))
+        );
+        remove_token(&path).unwrap();
+    }
+
+    #[test]
+    fn failing_token_command_reported() {
+        assert!(run_token_command("exit 3")
+            .unwrap_err()
+            .contains("exit status: 3"));
+        assert_eq!(
+            run_token_command("true").unwrap_err(),
+            "`true` printed no token"
+        );
+    }
+}
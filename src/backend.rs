@@ -0,0 +1,281 @@
+//! Time trackers tasks are synced to, behind a common interface so the sync
+//! logic can run against Everhour, other trackers or an in-memory fake in tests
+use chrono::NaiveDate;
+use std::fmt::Display;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use crate::aggregation::TaskData;
+use crate::date_range::DateRange;
+use crate::everhour::{Client, TimeBooking};
+
+/// Time record as the tracker has it
+#[derive(Clone, Debug, PartialEq)]
+pub struct BookedTime {
+    pub id: i64,
+    pub date: NaiveDate,
+    /// not set for time booked to a project
+    pub task_id: Option<String>,
+    pub task_name: Option<String>,
+    pub time: Duration,
+    pub comment: Option<String>,
+}
+
+/// Time to book to a task on a day
+pub struct TimeEntry {
+    pub date: NaiveDate,
+    pub time: Duration,
+    pub title: String,
+    pub comment: String,
+    /// set by billing rules, otherwise left to the tracker
+    pub billable: Option<bool>,
+    /// time goes to the project itself, task id being the project id
+    pub project: bool,
+}
+
+impl TimeEntry {
+    pub fn new(date: NaiveDate, task: &TaskData) -> TimeEntry {
+        TimeEntry {
+            date,
+            time: task.duration,
+            title: task.title.clone().unwrap_or("-".to_string()),
+            comment: task.comment(),
+            billable: task.billable,
+            project: task.project,
+        }
+    }
+}
+
+// backends are awaited in place and never sent across threads,
+// so the futures don't need to be `Send`
+#[allow(async_fn_in_trait)]
+pub trait TimeTrackerBackend {
+    /// How the tracker identifies users
+    type User: Display;
+
+    /// Tracker name, for messages
+    fn name(&self) -> &str;
+
+    /// Id the tracker knows the task by, if it's mapped at all - Everhour form by default
+    fn task_id(&self, task_id: &str, task: &TaskData) -> Option<String> {
+        Some(task.everhour_id(task_id))
+    }
+
+    /// User whose time is synced
+    async fn current_user(&self) -> Result<Self::User, String>;
+
+    /// Records of the user on days of the range
+    async fn time_records(
+        &self,
+        user: &Self::User,
+        range: DateRange,
+    ) -> Result<Vec<BookedTime>, String>;
+
+    /// Creates a record for the task
+    async fn add_time(
+        &self,
+        user: &Self::User,
+        task_id: &str,
+        entry: &TimeEntry,
+    ) -> Result<(), String>;
+
+    /// Overwrites an existing record of the task
+    async fn update_time(
+        &self,
+        user: &Self::User,
+        task_id: &str,
+        record_id: i64,
+        entry: &TimeEntry,
+    ) -> Result<(), String>;
+
+    async fn delete_time(&self, task_id: &str, record_id: i64) -> Result<(), String>;
+}
+
+pub struct EverhourBackend {
+    client: Client,
+}
+
+impl EverhourBackend {
+    pub fn new(client: Client) -> EverhourBackend {
+        EverhourBackend { client }
+    }
+
+    pub fn client(&self) -> &Client {
+        &self.client
+    }
+}
+
+fn time_booking(user: i64, entry: &TimeEntry) -> TimeBooking {
+    TimeBooking {
+        date: entry.date,
+        user,
+        time: entry.time.as_secs() as i64,
+        comment: entry.comment.clone(),
+        billable: entry.billable,
+    }
+}
+
+impl TimeTrackerBackend for EverhourBackend {
+    type User = i64;
+
+    fn name(&self) -> &str {
+        "Everhour"
+    }
+
+    async fn current_user(&self) -> Result<i64, String> {
+        let user = self
+            .client
+            .current_user()
+            .await
+            .map_err(|err| format!("Error getting Everhour user: {err}"))?;
+        Ok(user.id)
+    }
+
+    async fn time_records(&self, user: &i64, range: DateRange) -> Result<Vec<BookedTime>, String> {
+        let time_records = self
+            .client
+            .time_records(*user, range.first(), range.last())
+            .await
+            .map_err(|err| format!("Error getting Everhour time records: {err}"))?;
+        Ok(time_records
+            .into_iter()
+            .map(|time_record| BookedTime {
+                id: time_record.id,
+                date: time_record.date,
+                task_id: time_record.task.as_ref().map(|task| task.id.clone()),
+                task_name: time_record.task.as_ref().map(|task| task.name.clone()),
+                time: Duration::new(time_record.time.max(0) as u64, 0),
+                comment: time_record.comment,
+            })
+            .collect())
+    }
+
+    async fn add_time(&self, user: &i64, task_id: &str, entry: &TimeEntry) -> Result<(), String> {
+        let booking = time_booking(*user, entry);
+        // projects and billable flags only go with time set directly,
+        // which works the same for new and existing records
+        let result = if entry.project {
+            self.client.put_project_time(task_id, &booking).await
+        } else if entry.billable.is_some() {
+            self.client.put_task_time(task_id, &booking).await
+        } else {
+            self.client.add_task_time(task_id, &booking).await
+        };
+        result.map_err(|err| format!("Error adding time to {task_id}: {err}"))
+    }
+
+    async fn update_time(
+        &self,
+        user: &i64,
+        task_id: &str,
+        _record_id: i64,
+        entry: &TimeEntry,
+    ) -> Result<(), String> {
+        let booking = time_booking(*user, entry);
+        let result = if entry.project {
+            self.client.put_project_time(task_id, &booking).await
+        } else {
+            self.client.put_task_time(task_id, &booking).await
+        };
+        result.map_err(|err| format!("Error updating time of {task_id}: {err}"))
+    }
+
+    async fn delete_time(&self, _task_id: &str, record_id: i64) -> Result<(), String> {
+        self.client.delete_time(record_id).await
+    }
+}
+
+/// Tracker keeping records of a single user in memory, for tests
+pub struct InMemoryBackend {
+    user: i64,
+    records: Mutex<Vec<BookedTime>>,
+}
+
+impl InMemoryBackend {
+    pub fn new(user: i64, records: Vec<BookedTime>) -> InMemoryBackend {
+        InMemoryBackend {
+            user,
+            records: Mutex::new(records),
+        }
+    }
+
+    /// Records as they are now, ordered by id
+    pub fn records(&self) -> Vec<BookedTime> {
+        let mut records = self.records.lock().unwrap().clone();
+        records.sort_by_key(|record| record.id);
+        records
+    }
+
+    fn check_user(&self, user: i64) -> Result<(), String> {
+        if user == self.user {
+            Ok(())
+        } else {
+            Err(format!("Unknown user {user}"))
+        }
+    }
+}
+
+impl TimeTrackerBackend for InMemoryBackend {
+    type User = i64;
+
+    fn name(&self) -> &str {
+        "in-memory tracker"
+    }
+
+    async fn current_user(&self) -> Result<i64, String> {
+        Ok(self.user)
+    }
+
+    async fn time_records(&self, user: &i64, range: DateRange) -> Result<Vec<BookedTime>, String> {
+        self.check_user(*user)?;
+        Ok(self
+            .records()
+            .into_iter()
+            .filter(|record| range.contains(record.date))
+            .collect())
+    }
+
+    async fn add_time(&self, user: &i64, task_id: &str, entry: &TimeEntry) -> Result<(), String> {
+        self.check_user(*user)?;
+        let mut records = self.records.lock().unwrap();
+        let id = records.iter().map(|record| record.id).max().unwrap_or(0) + 1;
+        records.push(BookedTime {
+            id,
+            date: entry.date,
+            task_id: Some(task_id.to_string()),
+            task_name: Some(entry.title.clone()),
+            time: entry.time,
+            comment: Some(entry.comment.clone()),
+        });
+        Ok(())
+    }
+
+    async fn update_time(
+        &self,
+        user: &i64,
+        task_id: &str,
+        record_id: i64,
+        entry: &TimeEntry,
+    ) -> Result<(), String> {
+        self.check_user(*user)?;
+        let mut records = self.records.lock().unwrap();
+        let record = records
+            .iter_mut()
+            .find(|record| record.id == record_id && record.task_id.as_deref() == Some(task_id))
+            .ok_or(format!("No record {record_id} of {task_id}"))?;
+        record.time = entry.time;
+        record.comment = Some(entry.comment.clone());
+        Ok(())
+    }
+
+    async fn delete_time(&self, _task_id: &str, record_id: i64) -> Result<(), String> {
+        let mut records = self.records.lock().unwrap();
+        let count = records.len();
+        records.retain(|record| record.id != record_id);
+        if records.len() < count {
+            Ok(())
+        } else {
+            Err(format!("No record {record_id}"))
+        }
+    }
+}
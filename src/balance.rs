@@ -0,0 +1,122 @@
+use chrono::{Local, NaiveDate, TimeDelta};
+use std::collections::HashMap;
+use std::time::Duration;
+
+use crate::config::WorkSchedule;
+use crate::date_range::DateRange;
+use crate::hamster::HamsterData;
+
+pub struct DayBalance {
+    pub day: NaiveDate,
+    pub tracked: Duration,
+    pub target: Duration,
+    /// overtime accumulated up to and including this day
+    pub running: TimeDelta,
+}
+
+impl DayBalance {
+    /// surplus (positive) or deficit (negative) of the day
+    pub fn difference(&self) -> TimeDelta {
+        TimeDelta::from_std(self.tracked).unwrap() - TimeDelta::from_std(self.target).unwrap()
+    }
+}
+
+/// Sums durations of facts by their start day, only of the category if given.
+/// Running facts count up to now, facts ending before they start are left out.
+pub fn tracked_by_day(
+    hamster_db: Option<String>,
+    range: DateRange,
+    category: Option<&str>,
+) -> Result<HashMap<NaiveDate, Duration>, String> {
+    let hamster_data = HamsterData::open(hamster_db)?;
+    let mut tracked: HashMap<NaiveDate, Duration> = HashMap::new();
+
+    for record in hamster_data.get_category_facts(range, category) {
+        let end_time = record.end_time.unwrap_or_else(Local::now);
+        let Ok(duration) = (end_time - record.start_time).to_std() else {
+            continue;
+        };
+        *tracked.entry(record.start_time.date_naive()).or_default() += duration;
+    }
+    Ok(tracked)
+}
+
+/// Compares tracked time against the schedule for every day of the range
+pub fn daily_balance(
+    range: DateRange,
+    tracked: &HashMap<NaiveDate, Duration>,
+    schedule: &WorkSchedule,
+) -> Vec<DayBalance> {
+    let mut running = TimeDelta::zero();
+    range
+        .days()
+        .map(|day| {
+            let mut day_balance = DayBalance {
+                day,
+                tracked: tracked.get(&day).cloned().unwrap_or_default(),
+                target: schedule.target(day),
+                running,
+            };
+            running += day_balance.difference();
+            day_balance.running = running;
+            day_balance
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{NaiveDate, TimeDelta};
+    use std::collections::HashMap;
+    use std::time::Duration;
+
+    use crate::config::WorkSchedule;
+    use crate::date_range::DateRange;
+
+    use super::daily_balance;
+
+    #[test]
+    fn running_balance_accumulates() {
+        let friday = NaiveDate::from_ymd_opt(2024, 5, 17).unwrap();
+        let saturday = NaiveDate::from_ymd_opt(2024, 5, 18).unwrap();
+        let monday = NaiveDate::from_ymd_opt(2024, 5, 20).unwrap();
+        let tracked = HashMap::from([
+            (friday, Duration::new(9 * 3600, 0)),
+            (saturday, Duration::new(3600, 0)),
+            (monday, Duration::new(6 * 3600, 0)),
+        ]);
+
+        let balance = daily_balance(
+            DateRange::new(friday, monday).unwrap(),
+            &tracked,
+            &WorkSchedule::default(),
+        );
+
+        assert_eq!(
+            balance
+                .iter()
+                .map(|day_balance| (day_balance.difference(), day_balance.running))
+                .collect::<Vec<_>>(),
+            [
+                (TimeDelta::hours(1), TimeDelta::hours(1)),
+                (TimeDelta::hours(1), TimeDelta::hours(2)),
+                (TimeDelta::zero(), TimeDelta::hours(2)),
+                (TimeDelta::hours(-2), TimeDelta::zero()),
+            ]
+        );
+    }
+
+    #[test]
+    fn holidays_have_no_target() {
+        let christmas = NaiveDate::from_ymd_opt(2024, 12, 25).unwrap();
+        let schedule = WorkSchedule {
+            holidays: vec![christmas],
+            ..WorkSchedule::default()
+        };
+
+        let balance = daily_balance(DateRange::day(christmas), &HashMap::new(), &schedule);
+
+        assert_eq!(balance[0].target, Duration::new(0, 0));
+        assert_eq!(balance[0].running, TimeDelta::zero());
+    }
+}
@@ -0,0 +1,289 @@
+use chrono::{DateTime, Local, NaiveTime, TimeDelta};
+use std::fmt;
+use std::time::Duration;
+
+use crate::date_range::DateRange;
+use crate::hamster::{HamsterData, HamsterFact};
+use crate::utils::DurationFormatting;
+
+pub struct CheckOptions {
+    /// facts longer than this are reported
+    pub max_duration: Duration,
+    /// gaps between facts shorter than this are fine
+    pub min_gap: Duration,
+    pub work_start: NaiveTime,
+    pub work_end: NaiveTime,
+}
+
+pub enum FactIssue {
+    /// both facts cover the same time, so it would be billed twice
+    Overlap {
+        fact_id: i64,
+        other_fact_id: i64,
+        overlap: Duration,
+    },
+    /// untracked time within working hours
+    Gap {
+        after_fact_id: i64,
+        before_fact_id: i64,
+        gap: Duration,
+    },
+    /// fact ends before it starts, or right when it starts
+    NonPositive {
+        fact_id: i64,
+    },
+    TooLong {
+        fact_id: i64,
+        duration: Duration,
+    },
+}
+
+impl FactIssue {
+    /// gaps are only suspicious, everything else distorts tracked time
+    pub fn is_error(&self) -> bool {
+        !matches!(self, FactIssue::Gap { .. })
+    }
+}
+
+impl fmt::Display for FactIssue {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            FactIssue::Overlap {
+                fact_id,
+                other_fact_id,
+                overlap,
+            } => write!(
+                f,
+                "facts {fact_id} and {other_fact_id} overlap by {}",
+                overlap.as_hhmm()
+            ),
+            FactIssue::Gap {
+                after_fact_id,
+                before_fact_id,
+                gap,
+            } => write!(
+                f,
+                "{} gap between facts {after_fact_id} and {before_fact_id}",
+                gap.as_hhmm()
+            ),
+            FactIssue::NonPositive { fact_id } => {
+                write!(f, "fact {fact_id} has zero or negative length")
+            }
+            FactIssue::TooLong { fact_id, duration } => {
+                write!(f, "fact {fact_id} is {} long", duration.as_hhmm())
+            }
+        }
+    }
+}
+
+/// Issues of facts started on days of the range, only of the category if given
+pub fn find_fact_issues(
+    hamster_db: Option<String>,
+    range: DateRange,
+    category: Option<&str>,
+    options: &CheckOptions,
+) -> Result<Vec<FactIssue>, String> {
+    let hamster_data = HamsterData::open(hamster_db)?;
+    let facts = hamster_data.get_category_facts(range, category);
+    Ok(check_facts(&facts, options, Local::now()))
+}
+
+/// Finds overlapping, suspiciously long or empty facts and gaps between them.
+/// Facts that are still running are considered to end at `now`.
+pub fn check_facts(
+    facts: &[HamsterFact],
+    options: &CheckOptions,
+    now: DateTime<Local>,
+) -> Vec<FactIssue> {
+    let mut issues = vec![];
+    let mut facts: Vec<(&HamsterFact, DateTime<Local>)> = facts
+        .iter()
+        .map(|fact| (fact, fact.end_time.unwrap_or(now)))
+        .collect();
+    facts.sort_by_key(|(fact, _)| (fact.start_time, fact.id));
+
+    for (fact, end_time) in facts.iter() {
+        let length = *end_time - fact.start_time;
+        if length <= TimeDelta::zero() {
+            issues.push(FactIssue::NonPositive { fact_id: fact.id });
+        } else if length.to_std().unwrap() > options.max_duration {
+            issues.push(FactIssue::TooLong {
+                fact_id: fact.id,
+                duration: length.to_std().unwrap(),
+            });
+        }
+    }
+
+    // facts of bad length are reported already and would only confuse the rest
+    facts.retain(|(fact, end_time)| *end_time > fact.start_time);
+
+    for (index, (fact, end_time)) in facts.iter().enumerate() {
+        for (other_fact, other_end_time) in facts[index + 1..].iter() {
+            if other_fact.start_time >= *end_time {
+                break;
+            }
+            let overlap = (*end_time).min(*other_end_time) - other_fact.start_time;
+            if overlap > TimeDelta::zero() {
+                issues.push(FactIssue::Overlap {
+                    fact_id: fact.id,
+                    other_fact_id: other_fact.id,
+                    overlap: overlap.to_std().unwrap(),
+                });
+            }
+        }
+    }
+
+    // latest end so far, so a long fact "covers" the short ones within it
+    let mut covered_until: Option<(i64, DateTime<Local>)> = None;
+    for (fact, end_time) in facts.iter() {
+        if let Some((previous_fact_id, previous_end_time)) = covered_until {
+            let same_day = previous_end_time.date_naive() == fact.start_time.date_naive();
+            if same_day && fact.start_time > previous_end_time {
+                let gap = working_time(previous_end_time, fact.start_time, options);
+                if gap >= options.min_gap && !gap.is_zero() {
+                    issues.push(FactIssue::Gap {
+                        after_fact_id: previous_fact_id,
+                        before_fact_id: fact.id,
+                        gap,
+                    });
+                }
+            }
+        }
+        match covered_until {
+            Some((_, previous_end_time)) if previous_end_time >= *end_time => {}
+            _ => covered_until = Some((fact.id, *end_time)),
+        }
+    }
+
+    issues
+}
+
+/// Part of the interval (within one day) that falls into working hours
+fn working_time(from: DateTime<Local>, to: DateTime<Local>, options: &CheckOptions) -> Duration {
+    let from = from.time().max(options.work_start);
+    let to = to.time().min(options.work_end);
+    if to > from {
+        (to - from).to_std().unwrap()
+    } else {
+        Duration::new(0, 0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{DateTime, Local, NaiveDate, NaiveTime};
+    use std::time::Duration;
+
+    use crate::hamster::HamsterFact;
+
+    use super::{check_facts, CheckOptions, FactIssue};
+
+    fn at(hour: u32, minute: u32) -> DateTime<Local> {
+        NaiveDate::from_ymd_opt(2024, 5, 13)
+            .unwrap()
+            .and_hms_opt(hour, minute, 0)
+            .unwrap()
+            .and_local_timezone(Local)
+            .unwrap()
+    }
+
+    fn fact(
+        id: i64,
+        start_time: DateTime<Local>,
+        end_time: Option<DateTime<Local>>,
+    ) -> HamsterFact {
+        HamsterFact {
+            id,
+            start_time,
+            end_time,
+            description: String::new(),
+            activity: String::from("coding"),
+            category: String::from("Work"),
+            tags: vec![],
+        }
+    }
+
+    fn options() -> CheckOptions {
+        CheckOptions {
+            max_duration: Duration::new(4 * 3600, 0),
+            min_gap: Duration::new(15 * 60, 0),
+            work_start: NaiveTime::from_hms_opt(9, 0, 0).unwrap(),
+            work_end: NaiveTime::from_hms_opt(18, 0, 0).unwrap(),
+        }
+    }
+
+    fn describe(issues: Vec<FactIssue>) -> Vec<String> {
+        issues.iter().map(|issue| issue.to_string()).collect()
+    }
+
+    #[test]
+    fn clean_day_has_no_issues() {
+        let facts = [
+            fact(1, at(9, 0), Some(at(12, 0))),
+            fact(2, at(12, 10), Some(at(13, 0))),
+            fact(3, at(13, 0), Some(at(17, 0))),
+        ];
+        assert!(check_facts(&facts, &options(), at(20, 0)).is_empty());
+    }
+
+    #[test]
+    fn overlaps_detected() {
+        let facts = [
+            fact(1, at(9, 0), Some(at(12, 0))),
+            fact(2, at(11, 30), Some(at(12, 30))),
+            fact(3, at(10, 0), Some(at(10, 20))),
+        ];
+        assert_eq!(
+            describe(check_facts(&facts, &options(), at(20, 0))),
+            [
+                "facts 1 and 3 overlap by 0:20",
+                "facts 1 and 2 overlap by 0:30"
+            ]
+        );
+    }
+
+    #[test]
+    fn running_fact_overlaps_until_now() {
+        let facts = [fact(1, at(9, 0), None), fact(2, at(10, 0), Some(at(11, 0)))];
+        assert_eq!(
+            describe(check_facts(&facts, &options(), at(10, 30))),
+            ["facts 1 and 2 overlap by 0:30"]
+        );
+    }
+
+    #[test]
+    fn gaps_within_working_hours_detected() {
+        let facts = [
+            fact(1, at(7, 0), Some(at(8, 0))),
+            fact(2, at(9, 20), Some(at(12, 0))),
+            fact(3, at(13, 0), Some(at(17, 0))),
+            fact(4, at(19, 0), Some(at(20, 0))),
+        ];
+        assert_eq!(
+            describe(check_facts(&facts, &options(), at(21, 0))),
+            [
+                "0:20 gap between facts 1 and 2",
+                "1:00 gap between facts 2 and 3",
+                "1:00 gap between facts 3 and 4"
+            ]
+        );
+    }
+
+    #[test]
+    fn bad_lengths_detected() {
+        let facts = [
+            fact(1, at(9, 0), Some(at(9, 0))),
+            fact(2, at(10, 0), Some(at(9, 30))),
+            fact(3, at(10, 0), Some(at(15, 0))),
+        ];
+        let issues = check_facts(&facts, &options(), at(20, 0));
+        assert_eq!(
+            describe(issues),
+            [
+                "fact 1 has zero or negative length",
+                "fact 2 has zero or negative length",
+                "fact 3 is 5:00 long"
+            ]
+        );
+    }
+}
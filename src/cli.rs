@@ -1,5 +1,8 @@
-use chrono::NaiveDate;
-use clap::{Parser, Subcommand};
+use chrono::{NaiveDate, NaiveDateTime, NaiveTime, Weekday};
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use ham_cli::date_range::DateRange;
+use ham_cli::utils::{Period, Since};
+use std::path::PathBuf;
 
 #[derive(Parser)]
 #[command(version, about, long_about = None)]
@@ -8,34 +11,524 @@ pub struct Cli {
     #[arg(long, env = "HAMCLI_DB")]
     pub hamster_db: Option<String>,
 
+    /// Path to ham-cli config file - by default $HOME/.config/ham-cli/config.toml
+    #[arg(long, env = "HAMCLI_CONFIG")]
+    pub config: Option<String>,
+
+    #[command(flatten)]
+    pub everhour: EverhourArgs,
+
     #[command(subcommand)]
     pub command: Commands,
 }
 
+/// Where Everhour requests go
+#[derive(Args)]
+pub struct EverhourArgs {
+    /// Everhour API base URL, e.g. a local stub server
+    #[arg(long, global = true, env = "EVERHOUR_API_URL", default_value = ham_cli::everhour::API_URL)]
+    pub everhour_url: String,
+
+    /// Save Everhour requests and responses to a file
+    #[arg(long, global = true, value_name = "FILE")]
+    pub everhour_record: Option<String>,
+
+    /// Answer Everhour requests from a file saved by --everhour-record, without calling the API
+    #[arg(
+        long,
+        global = true,
+        env = "EVERHOUR_REPLAY",
+        value_name = "FILE",
+        conflicts_with = "everhour_record"
+    )]
+    pub everhour_replay: Option<String>,
+}
+
 #[derive(Subcommand)]
 pub enum Commands {
     /// Outputs information
     Info {},
-    /// get facts (last week by default)
-    GetFacts {},
+    /// get facts (this week by default)
+    GetFacts {
+        #[command(flatten)]
+        period: PeriodArgs,
+    },
+    /// Tasks with durations and comments (today by default)
     Tasks {
-        #[arg(long)]
+        /// First day to show - today by default
+        #[arg(long, conflicts_with = "period")]
         from: Option<NaiveDate>,
-        #[arg(long)]
+        /// Last day to show (inclusive) - same as `--from` by default
+        #[arg(long, conflicts_with = "period")]
         to: Option<NaiveDate>,
-        category: Option<String>,
+        #[command(flatten)]
+        period: PeriodArgs,
+        #[command(flatten)]
+        aggregation: AggregationArgs,
     },
     /// Synchronize task records to Everhour
     #[command(name = "sync-eh")]
     SyncTasksToEverhour {
-        category: Option<String>,
+        #[command(flatten)]
+        aggregation: AggregationArgs,
+        /// Token stored by `auth login` or printed by token_command by default
         #[arg(long, env = "EVERHOUR_API_TOKEN")]
-        api_token: String,
+        api_token: Option<String>,
+        /// First day to sync - today by default
+        #[arg(long, conflicts_with = "period")]
+        from: Option<NaiveDate>,
+        /// Last day to sync (inclusive) - same as `--from` by default
+        #[arg(long, conflicts_with = "period")]
+        to: Option<NaiveDate>,
+        #[command(flatten)]
+        period: PeriodArgs,
+        /// Sync days after the last successfully synced one up to yesterday
+        #[arg(long, default_value_t = false, conflicts_with_all = ["period", "from", "to"])]
+        since_last: bool,
+        /// Warn if days older than that many days aren't synced yet
+        #[arg(long, value_name = "DAYS", default_value_t = 7)]
+        warn_unsynced_after: u64,
+        /// Delete extra records of a task on a day instead of only reporting them
+        #[arg(long, default_value_t = false)]
+        delete_duplicates: bool,
+        #[arg(long, default_value_t = false)]
+        dry_run: bool,
+        /// Sync even if facts overlap or have bad length
+        #[arg(long, default_value_t = false)]
+        skip_checks: bool,
+        #[command(flatten)]
+        checks: CheckArgs,
+    },
+    /// Synchronize task records to Everhour or another tracker set up in the config
+    Sync {
+        #[arg(long, value_enum)]
+        target: SyncTarget,
+        #[command(flatten)]
+        aggregation: AggregationArgs,
+        /// For Everhour target - token stored by `auth login` or printed by token_command by default
+        #[arg(long, env = "EVERHOUR_API_TOKEN")]
+        api_token: Option<String>,
+        /// First day to sync - today by default
         #[arg(long)]
         from: Option<NaiveDate>,
+        /// Last day to sync (inclusive) - same as `--from` by default
         #[arg(long)]
         to: Option<NaiveDate>,
+        /// Delete extra records of a task on a day instead of only reporting them
+        #[arg(long, default_value_t = false)]
+        delete_duplicates: bool,
+        #[arg(long, default_value_t = false)]
+        dry_run: bool,
+        /// Sync even if facts overlap or have bad length
+        #[arg(long, default_value_t = false)]
+        skip_checks: bool,
+        #[command(flatten)]
+        checks: CheckArgs,
+    },
+    /// Keep syncing days to Everhour as facts get closed, until stopped
+    Watch {
+        #[command(flatten)]
+        aggregation: AggregationArgs,
+        /// Token stored by `auth login` or printed by token_command by default
+        #[arg(long, env = "EVERHOUR_API_TOKEN")]
+        api_token: Option<String>,
+        /// How often to look at the database
+        #[arg(long, value_name = "SECONDS", default_value_t = 10)]
+        interval: u64,
+        /// Sync once no fact has been closed for that long
+        #[arg(long, value_name = "SECONDS", default_value_t = 60)]
+        debounce: u64,
+        /// Append what's synced, or failed to, to this file
+        #[arg(long)]
+        log_file: Option<PathBuf>,
+        /// Delete extra records of a task on a day instead of only reporting them
+        #[arg(long, default_value_t = false)]
+        delete_duplicates: bool,
         #[arg(long, default_value_t = false)]
         dry_run: bool,
+        /// Sync even if facts overlap or have bad length
+        #[arg(long, default_value_t = false)]
+        skip_checks: bool,
+        #[command(flatten)]
+        checks: CheckArgs,
+    },
+    /// Compare Everhour time records with Hamster, day by day and task by task
+    #[command(name = "diff-eh")]
+    DiffEverhour {
+        #[command(flatten)]
+        aggregation: AggregationArgs,
+        /// Token stored by `auth login` or printed by token_command by default
+        #[arg(long, env = "EVERHOUR_API_TOKEN")]
+        api_token: Option<String>,
+        /// First day to compare - today by default
+        #[arg(long)]
+        from: Option<NaiveDate>,
+        /// Last day to compare (inclusive) - same as `--from` by default
+        #[arg(long)]
+        to: Option<NaiveDate>,
+    },
+    /// Create Hamster facts from Everhour time records Hamster doesn't have yet
+    #[command(name = "import-eh")]
+    ImportEverhour {
+        /// Category of created facts - facts of other categories are not compared
+        category: String,
+        /// Token stored by `auth login` or printed by token_command by default
+        #[arg(long, env = "EVERHOUR_API_TOKEN")]
+        api_token: Option<String>,
+        /// First day to import - today by default
+        #[arg(long)]
+        from: Option<NaiveDate>,
+        /// Last day to import (inclusive) - same as `--from` by default
+        #[arg(long)]
+        to: Option<NaiveDate>,
+        /// Activity of created facts
+        #[arg(long, default_value = "everhour")]
+        activity: String,
+        /// Imported facts start at this time, HH:MM, or after the day's last fact
+        #[arg(long, value_parser = ham_cli::utils::parse_time, default_value = "09:00")]
+        start: NaiveTime,
+        #[arg(long, default_value_t = false)]
+        dry_run: bool,
+    },
+    /// Start tracking an activity, stopping the running one
+    Start {
+        /// "activity@category"
+        #[arg(value_parser = ham_cli::utils::parse_activity)]
+        activity: (String, String),
+        /// Fact description, e.g. "[Task title](task link)"
+        #[arg(long)]
+        desc: Option<String>,
+    },
+    /// Stop tracking the running activity
+    Stop {},
+    /// Add a finished fact
+    Add {
+        /// "activity@category"
+        #[arg(value_parser = ham_cli::utils::parse_activity)]
+        activity: (String, String),
+        /// "YYYY-MM-DD HH:MM", or "HH:MM" for today
+        #[arg(long, value_parser = ham_cli::utils::parse_datetime)]
+        start: NaiveDateTime,
+        /// "YYYY-MM-DD HH:MM", or "HH:MM" for today
+        #[arg(long, value_parser = ham_cli::utils::parse_datetime)]
+        end: NaiveDateTime,
+        /// Fact description, e.g. "[Task title](task link)"
+        #[arg(long)]
+        desc: Option<String>,
+    },
+    /// Change a fact - whatever is not given stays as it is
+    Edit {
+        fact_id: i64,
+        /// "activity@category"
+        #[arg(long, value_parser = ham_cli::utils::parse_activity)]
+        activity: Option<(String, String)>,
+        /// "YYYY-MM-DD HH:MM", or "HH:MM" for today
+        #[arg(long, value_parser = ham_cli::utils::parse_datetime)]
+        start: Option<NaiveDateTime>,
+        /// "YYYY-MM-DD HH:MM", or "HH:MM" for today
+        #[arg(long, value_parser = ham_cli::utils::parse_datetime)]
+        end: Option<NaiveDateTime>,
+        /// New description
+        #[arg(long)]
+        desc: Option<String>,
+        /// Edit the description in $EDITOR
+        #[arg(long, default_value_t = false)]
+        editor: bool,
+        /// Save even if the fact would overlap others or wouldn't sync
+        #[arg(long, default_value_t = false)]
+        force: bool,
+    },
+    /// Point links to a task at another url in all facts of the range
+    Relink {
+        // also used when re-syncing
+        #[command(flatten)]
+        aggregation: AggregationArgs,
+        #[arg(long)]
+        from: NaiveDate,
+        /// Last day to relink (inclusive)
+        #[arg(long)]
+        to: NaiveDate,
+        /// Id of the task whose links are rewritten
+        #[arg(long)]
+        old: String,
+        /// Link to the task to use instead
+        #[arg(long)]
+        new: String,
+        /// Only show what would change
+        #[arg(long, default_value_t = false)]
+        dry_run: bool,
+        /// Sync days with changed facts to Everhour afterwards
+        #[arg(long, default_value_t = false)]
+        sync: bool,
+        /// For `--sync` - token stored by `auth login` or printed by token_command by default
+        #[arg(long, env = "EVERHOUR_API_TOKEN")]
+        api_token: Option<String>,
+        /// For `--sync` - delete extra records of a task on a day instead of only reporting them
+        #[arg(long, default_value_t = false)]
+        delete_duplicates: bool,
+        /// For `--sync` - sync even if facts overlap or have bad length
+        #[arg(long, default_value_t = false)]
+        skip_checks: bool,
+        #[command(flatten)]
+        checks: CheckArgs,
+    },
+    /// Export facts to other time tracking tools or for archiving
+    Export {
+        category: Option<String>,
+        /// First day to export - today by default
+        #[arg(long)]
+        from: Option<NaiveDate>,
+        /// Last day to export (inclusive) - same as `--from` by default
+        #[arg(long)]
+        to: Option<NaiveDate>,
+        #[arg(long, value_enum, default_value_t = ExportFormat::Csv)]
+        format: ExportFormat,
+        /// User email for Toggl and Clockify
+        #[arg(long, default_value = "")]
+        email: String,
+        /// File to write to - standard output by default
+        #[arg(long, short)]
+        output: Option<String>,
+    },
+    /// Add facts from a file exported by another time tracker (or by ham-cli itself)
+    Import {
+        file: String,
+        #[arg(long, value_enum)]
+        format: ImportFormat,
+        /// Activity of entries the file has none for
+        #[arg(long, default_value = "imported")]
+        activity: String,
+        /// Category of entries the file has none for
+        #[arg(long, default_value = "Imported")]
+        category: String,
+        #[arg(long, default_value_t = false)]
+        dry_run: bool,
+    },
+    /// Report overlapping facts, gaps within working hours and facts of suspicious length
+    Check {
+        category: Option<String>,
+        /// First day to check - today by default
+        #[arg(long)]
+        from: Option<NaiveDate>,
+        /// Last day to check (inclusive) - same as `--from` by default
+        #[arg(long)]
+        to: Option<NaiveDate>,
+        #[command(flatten)]
+        checks: CheckArgs,
+    },
+    /// Timesheet: tasks by days with daily and overall totals
+    Report {
+        /// Report the whole week of `--from` date - starting on Monday, unless the config says otherwise
+        #[arg(long, default_value_t = false)]
+        week: bool,
+        /// First day to report - today by default
+        #[arg(long)]
+        from: Option<NaiveDate>,
+        /// Last day to report (inclusive) - same as `--from` by default
+        #[arg(long)]
+        to: Option<NaiveDate>,
+        #[arg(long, value_enum, default_value_t = ReportFormat::Table)]
+        format: ReportFormat,
+        #[command(flatten)]
+        aggregation: AggregationArgs,
+    },
+    /// Tracked time against the work schedule, with running overtime balance
+    Balance {
+        /// Category that counts as work - taken from the schedule config by default
+        category: Option<String>,
+        /// Month to check, as YYYY-MM - current month by default
+        #[arg(long, value_parser = ham_cli::utils::parse_month, conflicts_with_all = ["year", "from"])]
+        month: Option<NaiveDate>,
+        /// Year to check
+        #[arg(long, conflicts_with = "from")]
+        year: Option<i32>,
+        #[arg(long)]
+        from: Option<NaiveDate>,
+        /// Last day to check (inclusive) - today by default. Without `--from`,
+        /// its month is checked up to that day.
+        #[arg(long, conflicts_with_all = ["month", "year"])]
+        to: Option<NaiveDate>,
+    },
+    /// Find mistakes in fact descriptions - exits with non-zero code if any found
+    Lint {
+        /// Category of work facts - taken from the schedule config by default
+        category: Option<String>,
+        /// First day to check - today by default
+        #[arg(long)]
+        from: Option<NaiveDate>,
+        /// Last day to check (inclusive) - same as `--from` by default
+        #[arg(long)]
+        to: Option<NaiveDate>,
+    },
+    /// Manage default tasks for facts without a task link
+    Mappings {
+        #[command(subcommand)]
+        command: MappingsCommands,
+    },
+    /// Manage stored Everhour API token
+    Auth {
+        #[command(subcommand)]
+        command: AuthCommands,
+    },
+}
+
+/// Days relative to today, instead of `--from` and `--to`
+#[derive(Args)]
+#[group(id = "period", multiple = false)]
+pub struct PeriodArgs {
+    /// From the first day of this week (see first_day_of_week in the config) to today
+    #[arg(long)]
+    pub this_week: bool,
+    #[arg(long)]
+    pub last_week: bool,
+    /// From the first day of this month to today
+    #[arg(long)]
+    pub this_month: bool,
+    #[arg(long)]
+    pub last_month: bool,
+    /// From days, weeks or months ago to today, e.g. 3d, 2w or 1m
+    #[arg(long, value_parser = ham_cli::utils::parse_since)]
+    pub since: Option<Since>,
+}
+
+impl PeriodArgs {
+    /// Days of the period, if any is given
+    pub fn range(&self, today: NaiveDate, first_day: Weekday) -> Option<DateRange> {
+        let period = if self.this_week {
+            Period::ThisWeek
+        } else if self.last_week {
+            Period::LastWeek
+        } else if self.this_month {
+            Period::ThisMonth
+        } else if self.last_month {
+            Period::LastMonth
+        } else {
+            return self
+                .since
+                .map(|since| DateRange::new(since.start(today), today).unwrap());
+        };
+        Some(period.range(today, first_day))
+    }
+}
+
+// how facts are turned into tasks - shared by commands that aggregate facts
+#[derive(Args)]
+pub struct AggregationArgs {
+    pub category: Option<String>,
+    /// Split time of facts with several task links between all of them
+    #[arg(long, default_value_t = false)]
+    pub split_links: bool,
+    /// How comments are extracted from fact descriptions
+    #[arg(long, value_enum, default_value_t = CommentsFormat::Simple)]
+    pub comments: CommentsFormat,
+    /// Also take comments from paragraphs, not only from lists (rich comments only)
+    #[arg(long, default_value_t = false)]
+    pub include_paragraphs: bool,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+pub enum SyncTarget {
+    Everhour,
+    /// Jira worklogs, `[targets.jira]` in the config
+    Jira,
+    /// `[targets.harvest]` in the config
+    Harvest,
+    /// Toggl Track, `[targets.toggl]` in the config
+    Toggl,
+    /// JSON POSTed to `[targets.webhook]` url
+    Webhook,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+pub enum ExportFormat {
+    /// Fact per line, with tags and task ids
+    Csv,
+    /// JSON object per line
+    Jsonl,
+    /// iCalendar, event per fact
+    Ical,
+    /// CSV for Toggl Track import
+    Toggl,
+    /// CSV for Clockify import
+    Clockify,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+pub enum ImportFormat {
+    /// CSV written by `export --format csv`
+    Csv,
+    /// iCalendar events
+    Ical,
+    /// Toggl Track CSV export
+    Toggl,
+    /// Clockify CSV export
+    Clockify,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+pub enum CommentsFormat {
+    /// Plain text of list items
+    Simple,
+    /// Inline formatting as plain text, nesting as indentation,
+    /// `<!-- private -->` items and ~~struck~~ text left out
+    Rich,
+}
+
+#[derive(Args)]
+pub struct CheckArgs {
+    /// Report facts longer than this many hours
+    #[arg(long, default_value_t = 10.0, value_parser = ham_cli::utils::parse_hours)]
+    pub max_hours: f64,
+    /// Report gaps between facts within working hours longer than this many minutes
+    #[arg(long, default_value_t = 15)]
+    pub min_gap: u64,
+    /// Start of working hours, HH:MM
+    #[arg(long, value_parser = ham_cli::utils::parse_time, default_value = "09:00")]
+    pub work_start: NaiveTime,
+    /// End of working hours, HH:MM
+    #[arg(long, value_parser = ham_cli::utils::parse_time, default_value = "18:00")]
+    pub work_end: NaiveTime,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+pub enum ReportFormat {
+    Table,
+    Csv,
+    Markdown,
+}
+
+#[derive(Subcommand)]
+pub enum MappingsCommands {
+    /// List configured mappings
+    List {},
+    /// Map Hamster category and/or activity to a task id
+    Add {
+        /// Asana task id, as extracted from task links
+        task_id: String,
+        #[arg(long)]
+        category: Option<String>,
+        #[arg(long)]
+        activity: Option<String>,
+    },
+    /// Remove mapping for exactly this category/activity pair
+    Remove {
+        #[arg(long)]
+        category: Option<String>,
+        #[arg(long)]
+        activity: Option<String>,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum AuthCommands {
+    /// Store Everhour API token, read from stdin, in a file only you can read
+    Login {
+        /// Store the token without checking it with Everhour
+        #[arg(long)]
+        no_verify: bool,
     },
+    /// Show where the token comes from and which Everhour user it belongs to
+    Status {},
+    /// Remove stored token
+    Logout {},
 }
@@ -0,0 +1,441 @@
+use chrono::{Datelike, NaiveDate, Weekday};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use crate::hamster::HamsterFact;
+use crate::targets::harvest::HarvestConfig;
+use crate::targets::jira::JiraConfig;
+use crate::targets::toggl::TogglConfig;
+use crate::targets::webhook::WebhookConfig;
+
+/// ham-cli settings, stored as TOML - by default in $HOME/.config/ham-cli/config.toml
+#[derive(Serialize, Deserialize, Default)]
+pub struct Config {
+    /// Everhour tasks to book facts without a task link to
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub mappings: Vec<TaskMapping>,
+    /// Contracted working time, used by `balance` command
+    #[serde(default, skip_serializing_if = "WorkSchedule::is_default")]
+    pub schedule: WorkSchedule,
+    /// Billable flag for synced time - first matching rule wins
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub billing: Vec<BillingRule>,
+    /// Everhour projects to book facts without a task to
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub projects: Vec<ProjectMapping>,
+    /// Trackers other than Everhour, for `sync --target`
+    #[serde(default, skip_serializing_if = "Targets::is_empty")]
+    pub targets: Targets,
+    /// Command printing Everhour API token, e.g. `pass show everhour` - used instead of `auth login` token
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub token_command: Option<String>,
+    /// Where `--this-week` and `--last-week` start, e.g. "Sun" - Monday by default
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub first_day_of_week: Option<Weekday>,
+}
+
+/// Settings and task mappings of each sync target
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct Targets {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub jira: Option<JiraConfig>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub harvest: Option<HarvestConfig>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub toggl: Option<TogglConfig>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub webhook: Option<WebhookConfig>,
+}
+
+impl Targets {
+    fn is_empty(&self) -> bool {
+        *self == Targets::default()
+    }
+}
+
+/// Target working hours
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct WorkSchedule {
+    /// Hamster category that counts as work
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub category: Option<String>,
+    /// Hours per weekday, Monday to Sunday
+    #[serde(default = "default_hours")]
+    pub hours: [f64; 7],
+    /// Days off, regardless of weekday, as "YYYY-MM-DD"
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub holidays: Vec<NaiveDate>,
+}
+
+fn default_hours() -> [f64; 7] {
+    [8.0, 8.0, 8.0, 8.0, 8.0, 0.0, 0.0]
+}
+
+impl Default for WorkSchedule {
+    fn default() -> Self {
+        WorkSchedule {
+            category: None,
+            hours: default_hours(),
+            holidays: vec![],
+        }
+    }
+}
+
+impl WorkSchedule {
+    fn is_default(&self) -> bool {
+        *self == WorkSchedule::default()
+    }
+
+    pub fn target(&self, day: NaiveDate) -> Duration {
+        if self.holidays.contains(&day) {
+            return Duration::new(0, 0);
+        }
+        let hours = self.hours[day.weekday().num_days_from_monday() as usize];
+        Duration::from_secs_f64(hours.max(0.0) * 3600.0)
+    }
+}
+
+/// Maps Hamster category and/or activity to a fixed task id
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct TaskMapping {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub category: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub activity: Option<String>,
+    pub task_id: String,
+}
+
+impl TaskMapping {
+    pub fn matches(&self, category: &str, activity: &str) -> bool {
+        if self.category.is_none() && self.activity.is_none() {
+            return false;
+        }
+        let category_matches = match &self.category {
+            None => true,
+            Some(mapped_category) => mapped_category == category,
+        };
+        let activity_matches = match &self.activity {
+            None => true,
+            Some(mapped_activity) => mapped_activity == activity,
+        };
+        category_matches && activity_matches
+    }
+
+    /// mappings that name both activity and category win over
+    /// activity-only ones, which in turn win over category-only ones
+    fn specificity(&self) -> u8 {
+        match (&self.category, &self.activity) {
+            (Some(_), Some(_)) => 3,
+            (None, Some(_)) => 2,
+            (Some(_), None) => 1,
+            (None, None) => 0,
+        }
+    }
+
+    pub fn describe(&self) -> String {
+        format!(
+            "{}@{}",
+            self.activity.as_deref().unwrap_or("*"),
+            self.category.as_deref().unwrap_or("*")
+        )
+    }
+}
+
+/// Facts a rule applies to - every field given must match, empty filter matches nothing
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Default)]
+pub struct FactFilter {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub category: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub activity: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tag: Option<String>,
+}
+
+impl FactFilter {
+    pub fn matches(&self, fact: &HamsterFact) -> bool {
+        if self.category.is_none() && self.activity.is_none() && self.tag.is_none() {
+            return false;
+        }
+        let category_matches = match &self.category {
+            None => true,
+            Some(category) => *category == fact.category,
+        };
+        let activity_matches = match &self.activity {
+            None => true,
+            Some(activity) => *activity == fact.activity,
+        };
+        let tag_matches = match &self.tag {
+            None => true,
+            Some(tag) => fact.tags.contains(tag),
+        };
+        category_matches && activity_matches && tag_matches
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct BillingRule {
+    #[serde(flatten)]
+    pub filter: FactFilter,
+    pub billable: bool,
+}
+
+/// Books matching facts to the project itself, e.g. "as:1200000000000001"
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct ProjectMapping {
+    #[serde(flatten)]
+    pub filter: FactFilter,
+    pub project_id: String,
+}
+
+pub fn resolve_path(config_path: Option<String>) -> Result<PathBuf, String> {
+    match config_path {
+        Some(config_path) => Ok(PathBuf::from(config_path)),
+        None => match std::env::var("HOME") {
+            Ok(home) => Ok(Path::new(home.as_str()).join(".config/ham-cli/config.toml")),
+            Err(_) => Err(String::from(
+                "Config path wasn't supplied, $HOME is not set - I give up",
+            )),
+        },
+    }
+}
+
+impl Config {
+    /// Loads config, falling back to defaults if the file doesn't exist yet
+    pub fn load(config_path: Option<String>) -> Result<Config, String> {
+        let path = resolve_path(config_path)?;
+        if !path.exists() {
+            return Ok(Config::default());
+        }
+        let raw = fs::read_to_string(&path)
+            .map_err(|err| format!("couldn't read config {}: {}", path.display(), err))?;
+        toml::from_str(&raw)
+            .map_err(|err| format!("couldn't parse config {}: {}", path.display(), err))
+    }
+
+    pub fn save(&self, config_path: Option<String>) -> Result<(), String> {
+        let path = resolve_path(config_path)?;
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)
+                .map_err(|err| format!("couldn't create {}: {}", parent.display(), err))?;
+        }
+        let raw = toml::to_string_pretty(self)
+            .map_err(|err| format!("couldn't serialize config: {}", err))?;
+        fs::write(&path, raw)
+            .map_err(|err| format!("couldn't write config {}: {}", path.display(), err))
+    }
+
+    /// Finds the most specific mapping for the given category and activity
+    pub fn find_mapping(&self, category: &str, activity: &str) -> Option<&TaskMapping> {
+        self.mappings
+            .iter()
+            .filter(|mapping| mapping.matches(category, activity))
+            .max_by_key(|mapping| mapping.specificity())
+    }
+
+    /// Billable flag for the fact, if any rule says so
+    pub fn billable(&self, fact: &HamsterFact) -> Option<bool> {
+        self.billing
+            .iter()
+            .find(|rule| rule.filter.matches(fact))
+            .map(|rule| rule.billable)
+    }
+
+    pub fn first_day_of_week(&self) -> Weekday {
+        self.first_day_of_week.unwrap_or(Weekday::Mon)
+    }
+
+    pub fn find_project(&self, fact: &HamsterFact) -> Option<&ProjectMapping> {
+        self.projects
+            .iter()
+            .find(|project| project.filter.matches(fact))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{Local, NaiveDate};
+    use std::time::Duration;
+
+    use crate::hamster::HamsterFact;
+
+    use super::{Config, TaskMapping, WorkSchedule};
+
+    fn mapping(category: Option<&str>, activity: Option<&str>, task_id: &str) -> TaskMapping {
+        TaskMapping {
+            category: category.map(String::from),
+            activity: activity.map(String::from),
+            task_id: String::from(task_id),
+        }
+    }
+
+    #[test]
+    fn most_specific_mapping_wins() {
+        let config = Config {
+            mappings: vec![
+                mapping(Some("Work"), None, "1"),
+                mapping(Some("Work"), Some("stand-up"), "2"),
+                mapping(None, Some("code review"), "3"),
+            ],
+            ..Config::default()
+        };
+
+        assert_eq!(
+            config.find_mapping("Work", "stand-up").unwrap().task_id,
+            "2"
+        );
+        assert_eq!(
+            config.find_mapping("Work", "code review").unwrap().task_id,
+            "3"
+        );
+        assert_eq!(config.find_mapping("Work", "support").unwrap().task_id, "1");
+        assert_eq!(
+            config.find_mapping("Home", "code review").unwrap().task_id,
+            "3"
+        );
+        assert!(config.find_mapping("Home", "stand-up").is_none());
+    }
+
+    #[test]
+    fn empty_mapping_matches_nothing() {
+        assert!(!mapping(None, None, "1").matches("Work", "stand-up"));
+    }
+
+    #[test]
+    fn config_roundtrips_through_toml() {
+        let config = Config {
+            mappings: vec![mapping(Some("Work"), Some("stand-up"), "123456")],
+            ..Config::default()
+        };
+        let raw = toml::to_string_pretty(&config).unwrap();
+        let parsed: Config = toml::from_str(&raw).unwrap();
+        assert_eq!(parsed.mappings, config.mappings);
+    }
+
+    #[test]
+    fn schedule_parsed_from_toml() {
+        let config: Config = toml::from_str(
+            "[schedule]\n\
+            category = \"Work\"\n\
+            hours = [8, 8, 8, 8, 6.5, 0, 0]\n\
+            holidays = [\"2024-12-25\"]\n",
+        )
+        .unwrap();
+        let schedule = config.schedule;
+
+        assert_eq!(schedule.category, Some(String::from("Work")));
+        // Friday
+        assert_eq!(
+            schedule.target(NaiveDate::from_ymd_opt(2024, 12, 27).unwrap()),
+            Duration::new(6 * 3600 + 1800, 0)
+        );
+        // Saturday
+        assert_eq!(
+            schedule.target(NaiveDate::from_ymd_opt(2024, 12, 28).unwrap()),
+            Duration::new(0, 0)
+        );
+        // Wednesday, but a holiday
+        assert_eq!(
+            schedule.target(NaiveDate::from_ymd_opt(2024, 12, 25).unwrap()),
+            Duration::new(0, 0)
+        );
+    }
+
+    #[test]
+    fn default_schedule_is_five_eight_hour_days() {
+        let config: Config = toml::from_str("").unwrap();
+        assert_eq!(config.schedule, WorkSchedule::default());
+        assert_eq!(
+            config
+                .schedule
+                .target(NaiveDate::from_ymd_opt(2024, 5, 13).unwrap()),
+            Duration::new(8 * 3600, 0)
+        );
+    }
+
+    fn fact(category: &str, activity: &str, tags: &[&str]) -> HamsterFact {
+        HamsterFact {
+            id: 1,
+            start_time: NaiveDate::from_ymd_opt(2024, 5, 13)
+                .unwrap()
+                .and_hms_opt(9, 0, 0)
+                .unwrap()
+                .and_local_timezone(Local)
+                .unwrap(),
+            end_time: None,
+            description: String::new(),
+            activity: String::from(activity),
+            category: String::from(category),
+            tags: tags.iter().map(|tag| String::from(*tag)).collect(),
+        }
+    }
+
+    #[test]
+    fn first_billing_rule_wins() {
+        let config: Config = toml::from_str(
+            "[[billing]]\n\
+            tag = \"internal\"\n\
+            billable = false\n\
+            [[billing]]\n\
+            category = \"Work\"\n\
+            billable = true\n",
+        )
+        .unwrap();
+
+        assert_eq!(config.billable(&fact("Work", "coding", &[])), Some(true));
+        assert_eq!(
+            config.billable(&fact("Work", "coding", &["internal"])),
+            Some(false)
+        );
+        assert_eq!(config.billable(&fact("Home", "coding", &[])), None);
+    }
+
+    #[test]
+    fn project_mapping_matches_all_given_fields() {
+        let config: Config = toml::from_str(
+            "[[projects]]\n\
+            category = \"Work\"\n\
+            tag = \"support\"\n\
+            project_id = \"as:1200\"\n",
+        )
+        .unwrap();
+
+        assert_eq!(
+            config
+                .find_project(&fact("Work", "calls", &["support", "urgent"]))
+                .unwrap()
+                .project_id,
+            "as:1200"
+        );
+        assert!(config.find_project(&fact("Work", "calls", &[])).is_none());
+        assert!(config
+            .find_project(&fact("Home", "calls", &["support"]))
+            .is_none());
+    }
+
+    #[test]
+    fn targets_parsed() {
+        let config: Config = toml::from_str(
+            "[targets.jira]\n\
+            url = \"https://example.atlassian.net\"\n\
+            email = \"me@example.com\"\n\
+            tasks = { \"1200\" = \"PROJ-1\" }\n\
+            [targets.harvest]\n\
+            account_id = \"42\"\n",
+        )
+        .unwrap();
+
+        let jira = config.targets.jira.as_ref().unwrap();
+        assert_eq!(jira.tasks["1200"], "PROJ-1");
+        assert!(jira.api_token.is_none());
+        let harvest = config.targets.harvest.as_ref().unwrap();
+        assert_eq!(harvest.url, "https://api.harvestapp.com");
+        assert!(harvest.tasks.is_empty());
+        assert!(config.targets.toggl.is_none());
+        assert!(!toml::to_string(&Config::default())
+            .unwrap()
+            .contains("targets"));
+    }
+}
@@ -0,0 +1,155 @@
+//! Days a command works on. Both ends are included everywhere: `--from 2024-05-13
+//! --to 2024-05-17` is Monday to Friday, and `--from` alone is that single day.
+//! Only database queries need the (exclusive) day after the range - that's `end`.
+use chrono::{Days, NaiveDate};
+use std::fmt;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct DateRange {
+    first: NaiveDate,
+    last: NaiveDate,
+}
+
+impl DateRange {
+    /// Days from `first` to `last`, both included - fails if `last` comes before `first`
+    pub fn new(first: NaiveDate, last: NaiveDate) -> Result<DateRange, String> {
+        if last < first {
+            return Err(format!(
+                "Date range ends ({last}) before it starts ({first})"
+            ));
+        }
+        Ok(DateRange { first, last })
+    }
+
+    pub fn day(day: NaiveDate) -> DateRange {
+        DateRange {
+            first: day,
+            last: day,
+        }
+    }
+
+    /// Range of `--from` and `--to` options: today if neither is given, a single day if only
+    /// `--from` is
+    pub fn from_args(
+        from: Option<NaiveDate>,
+        to: Option<NaiveDate>,
+        today: NaiveDate,
+    ) -> Result<DateRange, String> {
+        let first = from.unwrap_or(today);
+        DateRange::new(first, to.unwrap_or(first))
+    }
+
+    pub fn first(&self) -> NaiveDate {
+        self.first
+    }
+
+    pub fn last(&self) -> NaiveDate {
+        self.last
+    }
+
+    /// Day after the last one, for queries with exclusive end
+    pub fn end(&self) -> NaiveDate {
+        self.last.checked_add_days(Days::new(1)).unwrap()
+    }
+
+    pub fn contains(&self, day: NaiveDate) -> bool {
+        self.first <= day && day <= self.last
+    }
+
+    /// Every day of the range, in order
+    pub fn days(&self) -> impl Iterator<Item = NaiveDate> {
+        let last = self.last;
+        self.first.iter_days().take_while(move |day| *day <= last)
+    }
+
+    /// Same range, but not past `day`
+    pub fn until(&self, day: NaiveDate) -> Option<DateRange> {
+        DateRange::new(self.first, self.last.min(day)).ok()
+    }
+}
+
+impl fmt::Display for DateRange {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.first == self.last {
+            write!(f, "{}", self.first)
+        } else {
+            write!(f, "{} - {}", self.first, self.last)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::NaiveDate;
+
+    use super::DateRange;
+
+    fn day(day: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(2024, 5, day).unwrap()
+    }
+
+    #[test]
+    fn both_ends_included() {
+        let range = DateRange::new(day(13), day(17)).unwrap();
+
+        assert_eq!(
+            range.days().collect::<Vec<_>>(),
+            [day(13), day(14), day(15), day(16), day(17)]
+        );
+        assert!(range.contains(day(13)));
+        assert!(range.contains(day(17)));
+        assert!(!range.contains(day(12)));
+        assert!(!range.contains(day(18)));
+        assert_eq!(range.end(), day(18));
+    }
+
+    #[test]
+    fn single_day() {
+        let range = DateRange::day(day(13));
+
+        assert_eq!(range.days().collect::<Vec<_>>(), [day(13)]);
+        assert_eq!(range.end(), day(14));
+        assert_eq!(range.to_string(), "2024-05-13");
+    }
+
+    #[test]
+    fn defaults_from_args() {
+        let today = day(20);
+
+        assert_eq!(
+            DateRange::from_args(None, None, today),
+            Ok(DateRange::day(today))
+        );
+        assert_eq!(
+            DateRange::from_args(Some(day(13)), None, today),
+            Ok(DateRange::day(day(13)))
+        );
+        assert_eq!(
+            DateRange::from_args(Some(day(13)), Some(day(17)), today)
+                .unwrap()
+                .to_string(),
+            "2024-05-13 - 2024-05-17"
+        );
+    }
+
+    #[test]
+    fn backwards_range_rejected() {
+        assert_eq!(
+            DateRange::new(day(17), day(13)),
+            Err(String::from(
+                "Date range ends (2024-05-13) before it starts (2024-05-17)"
+            ))
+        );
+        // only `--to` given, before today
+        assert!(DateRange::from_args(None, Some(day(13)), day(20)).is_err());
+    }
+
+    #[test]
+    fn range_cut_at_day() {
+        let range = DateRange::new(day(13), day(17)).unwrap();
+
+        assert_eq!(range.until(day(15)), DateRange::new(day(13), day(15)).ok());
+        assert_eq!(range.until(day(20)), Some(range));
+        assert_eq!(range.until(day(12)), None);
+    }
+}
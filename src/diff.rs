@@ -0,0 +1,215 @@
+use chrono::{NaiveDate, TimeDelta};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::time::Duration;
+
+use crate::aggregation::{get_tasks_with_durations, AggregationOptions};
+use crate::backend::{EverhourBackend, TimeTrackerBackend};
+use crate::config::Config;
+use crate::date_range::DateRange;
+
+/// Durations closer than this are considered equal - running facts keep
+/// growing, and nobody cares about seconds on a timesheet anyway
+const TOLERANCE: Duration = Duration::new(60, 0);
+
+/// Time booked to a task on a day, on either side
+pub struct Booking {
+    pub day: NaiveDate,
+    /// Everhour id, e.g. "as:1200000000000001"
+    pub task_id: String,
+    pub title: String,
+    pub duration: Duration,
+}
+
+pub struct DiffRow {
+    pub day: NaiveDate,
+    pub task_id: String,
+    pub title: String,
+    pub hamster: Option<Duration>,
+    pub everhour: Option<Duration>,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum DiffStatus {
+    Match,
+    /// tracked in Hamster, not synced to Everhour
+    Missing,
+    /// booked in Everhour, nothing tracked in Hamster
+    Extra,
+    Mismatch,
+}
+
+impl fmt::Display for DiffStatus {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let status = match self {
+            DiffStatus::Match => "ok",
+            DiffStatus::Missing => "missing",
+            DiffStatus::Extra => "extra",
+            DiffStatus::Mismatch => "mismatch",
+        };
+        write!(f, "{status}")
+    }
+}
+
+impl DiffRow {
+    pub fn status(&self) -> DiffStatus {
+        match (self.hamster, self.everhour) {
+            (Some(_), None) => DiffStatus::Missing,
+            (None, _) => DiffStatus::Extra,
+            (Some(hamster), Some(everhour)) => {
+                if hamster.abs_diff(everhour) < TOLERANCE {
+                    DiffStatus::Match
+                } else {
+                    DiffStatus::Mismatch
+                }
+            }
+        }
+    }
+
+    /// Everhour minus Hamster - negative when not everything is synced
+    pub fn difference(&self) -> TimeDelta {
+        TimeDelta::from_std(self.everhour.unwrap_or_default()).unwrap()
+            - TimeDelta::from_std(self.hamster.unwrap_or_default()).unwrap()
+    }
+}
+
+/// Lays both sides next to each other by day and task, ordered the same way
+pub fn compare(hamster: Vec<Booking>, everhour: Vec<Booking>) -> Vec<DiffRow> {
+    let mut rows: BTreeMap<(NaiveDate, String), DiffRow> = BTreeMap::new();
+    for (booking, from_hamster) in hamster
+        .into_iter()
+        .map(|booking| (booking, true))
+        .chain(everhour.into_iter().map(|booking| (booking, false)))
+    {
+        let row = rows
+            .entry((booking.day, booking.task_id.clone()))
+            .or_insert(DiffRow {
+                day: booking.day,
+                task_id: booking.task_id,
+                title: booking.title,
+                hamster: None,
+                everhour: None,
+            });
+        let side = if from_hamster {
+            &mut row.hamster
+        } else {
+            &mut row.everhour
+        };
+        *side = Some(side.unwrap_or_default() + booking.duration);
+    }
+    rows.into_values().collect()
+}
+
+/// Hamster aggregates of every day of the range, "-" for tasks without an id
+pub fn hamster_bookings(
+    hamster_db: Option<String>,
+    range: DateRange,
+    config: &Config,
+    options: &AggregationOptions,
+) -> Vec<Booking> {
+    let mut bookings = vec![];
+    for day in range.days() {
+        let tasks =
+            get_tasks_with_durations(hamster_db.clone(), DateRange::day(day), config, options);
+        for (task_id, task_data) in tasks.into_iter() {
+            bookings.push(Booking {
+                day,
+                task_id: task_id
+                    .map(|task_id| task_data.everhour_id(&task_id))
+                    .unwrap_or("-".to_string()),
+                title: task_data.title.unwrap_or("-".to_string()),
+                duration: task_data.duration,
+            });
+        }
+    }
+    bookings
+}
+
+/// Everhour records of the range next to Hamster aggregates
+pub async fn diff_everhour(
+    hamster_db: Option<String>,
+    backend: &EverhourBackend,
+    range: DateRange,
+    config: &Config,
+    options: &AggregationOptions,
+) -> Result<Vec<DiffRow>, String> {
+    let me = backend.current_user().await?;
+    let everhour_bookings: Vec<Booking> = backend
+        .time_records(&me, range)
+        .await?
+        .into_iter()
+        .map(|time_record| Booking {
+            day: time_record.date,
+            task_id: time_record.task_id.unwrap_or("-".to_string()),
+            title: time_record.task_name.unwrap_or("-".to_string()),
+            duration: time_record.time,
+        })
+        .collect();
+    let hamster_bookings = hamster_bookings(hamster_db, range, config, options);
+    Ok(compare(hamster_bookings, everhour_bookings))
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{NaiveDate, TimeDelta};
+    use std::time::Duration;
+
+    use super::{compare, Booking, DiffStatus};
+
+    fn booking(day: u32, task_id: &str, minutes: u64) -> Booking {
+        Booking {
+            day: NaiveDate::from_ymd_opt(2024, 5, day).unwrap(),
+            task_id: String::from(task_id),
+            title: format!("task {task_id}"),
+            duration: Duration::new(minutes * 60, 0),
+        }
+    }
+
+    #[test]
+    fn statuses_detected() {
+        let rows = compare(
+            vec![
+                booking(13, "as:1", 60),
+                booking(13, "as:2", 30),
+                booking(14, "as:1", 90),
+            ],
+            vec![
+                booking(13, "as:1", 60),
+                booking(14, "as:1", 60),
+                booking(14, "as:3", 15),
+            ],
+        );
+
+        assert_eq!(
+            rows.iter()
+                .map(|row| (row.day.to_string(), row.task_id.as_str(), row.status()))
+                .collect::<Vec<_>>(),
+            [
+                (String::from("2024-05-13"), "as:1", DiffStatus::Match),
+                (String::from("2024-05-13"), "as:2", DiffStatus::Missing),
+                (String::from("2024-05-14"), "as:1", DiffStatus::Mismatch),
+                (String::from("2024-05-14"), "as:3", DiffStatus::Extra),
+            ]
+        );
+        assert_eq!(rows[2].difference(), TimeDelta::minutes(-30));
+        assert_eq!(rows[3].difference(), TimeDelta::minutes(15));
+    }
+
+    #[test]
+    fn duplicate_records_summed() {
+        let rows = compare(
+            vec![booking(13, "as:1", 60)],
+            vec![booking(13, "as:1", 20), booking(13, "as:1", 40)],
+        );
+        assert_eq!(rows.len(), 1);
+        assert_eq!(rows[0].status(), DiffStatus::Match);
+    }
+
+    #[test]
+    fn seconds_ignored() {
+        let mut running = booking(13, "as:1", 60);
+        running.duration += Duration::new(59, 0);
+        let rows = compare(vec![running], vec![booking(13, "as:1", 60)]);
+        assert_eq!(rows[0].status(), DiffStatus::Match);
+    }
+}
@@ -0,0 +1,131 @@
+//! Changing existing facts, checked so they still make sense and still sync
+use chrono::{DateTime, Days, Local, NaiveDateTime};
+
+use crate::aggregation::resolve_task;
+use crate::config::Config;
+use crate::date_range::DateRange;
+use crate::hamster::{HamsterData, HamsterFact};
+
+/// What `edit` changes - `None` keeps the value as it is
+#[derive(Default)]
+pub struct FactChanges {
+    /// activity and category
+    pub activity: Option<(String, String)>,
+    pub start: Option<NaiveDateTime>,
+    pub end: Option<NaiveDateTime>,
+    pub description: Option<String>,
+}
+
+/// Time given on the command line, in local time
+pub fn to_local(time: NaiveDateTime) -> Result<DateTime<Local>, String> {
+    time.and_local_timezone(Local).single().ok_or(format!(
+        "{time} is ambiguous or doesn't exist in local time"
+    ))
+}
+
+/// Adds a closed fact, given it ends after it starts
+pub fn add_fact(
+    hamster_data: &HamsterData,
+    (activity, category): (&str, &str),
+    start: NaiveDateTime,
+    end: NaiveDateTime,
+    description: &str,
+) -> Result<i64, String> {
+    if end <= start {
+        return Err(format!("fact would end ({end}) before it starts ({start})"));
+    }
+    hamster_data.add_fact(
+        activity,
+        category,
+        to_local(start)?,
+        Some(to_local(end)?),
+        description,
+        &[],
+    )
+}
+
+/// The fact with changes applied, not saved yet
+pub fn changed_fact(
+    hamster_data: &HamsterData,
+    fact_id: i64,
+    changes: FactChanges,
+) -> Result<HamsterFact, String> {
+    let mut fact = hamster_data
+        .get_fact(fact_id)
+        .ok_or(format!("there's no fact {fact_id}"))?;
+    if let Some((activity, category)) = changes.activity {
+        fact.activity = activity;
+        fact.category = category;
+    }
+    if let Some(start) = changes.start {
+        fact.start_time = to_local(start)?;
+    }
+    if let Some(end) = changes.end {
+        fact.end_time = Some(to_local(end)?);
+    }
+    if let Some(description) = changes.description {
+        fact.description = description;
+    }
+    Ok(fact)
+}
+
+/// Saves the edited fact. It can't end before it starts; overlapping other facts
+/// or having nothing to book it to is only saved with `force`, the problems
+/// given back then.
+pub fn save_fact(
+    hamster_data: &HamsterData,
+    fact: &HamsterFact,
+    config: &Config,
+    force: bool,
+) -> Result<Vec<String>, String> {
+    if let Some(end_time) = fact.end_time {
+        if end_time <= fact.start_time {
+            return Err(format!(
+                "fact would end ({end_time}) before it starts ({})",
+                fact.start_time
+            ));
+        }
+    }
+
+    let mut problems: Vec<String> = overlapping_facts(hamster_data, fact, Local::now())
+        .iter()
+        .map(|other_fact_id| format!("fact would overlap with fact {other_fact_id}"))
+        .collect();
+    if let Err(err) = resolve_task(fact, config) {
+        problems.push(format!("{err}\nThe fact wouldn't sync"));
+    }
+    if !problems.is_empty() && !force {
+        return Err(problems.join("\n") + "\nFix it or use --force to save anyway");
+    }
+
+    hamster_data.update_fact(fact)?;
+    Ok(problems)
+}
+
+/// Ids of other facts covering some of the fact's time, running ones ending now
+fn overlapping_facts(
+    hamster_data: &HamsterData,
+    fact: &HamsterFact,
+    now: DateTime<Local>,
+) -> Vec<i64> {
+    let end_time = fact.end_time.unwrap_or(now);
+    // a fact of the day before may run into this one
+    let first_day = fact
+        .start_time
+        .date_naive()
+        .checked_sub_days(Days::new(1))
+        .unwrap();
+    let Ok(range) = DateRange::new(first_day, end_time.date_naive()) else {
+        return vec![];
+    };
+    hamster_data
+        .get_facts(range)
+        .iter()
+        .filter(|other| {
+            other.id != fact.id
+                && other.start_time < end_time
+                && fact.start_time < other.end_time.unwrap_or(now)
+        })
+        .map(|other| other.id)
+        .collect()
+}
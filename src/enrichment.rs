@@ -1,64 +1,165 @@
 use crate::hamster::HamsterFact;
-use crate::utils::{LinkText, MarkdownProcessing};
-use markdown::mdast::Node;
-use markdown::ParseOptions;
+use crate::utils::LinkText;
+use markdown::mdast::{Link, List, Node};
+use markdown::{Constructs, ParseOptions};
 use regex::Regex;
+use std::sync::LazyLock;
+
+static TASK_ID_REGEX: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"\/(?<task_id>\d+)\/f").unwrap());
+/// Share of the fact time written right after a link, e.g. `30%`
+static WEIGHT_REGEX: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"^\s*(?<weight>\d{1,3})\s*%").unwrap());
+static PRIVATE_REGEX: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"<!--\s*private\s*-->").unwrap());
+
+/// How comments are rendered from the fact description
+#[derive(Clone, Copy, Default)]
+pub enum CommentMode {
+    /// text of list items, formatting and nesting dropped
+    #[default]
+    Simple,
+    /// list items with inline formatting kept as plain text and nesting kept
+    /// as indentation
+    Rich { include_paragraphs: bool },
+}
 
 pub struct TaskLink {
     pub link_title: String,
     pub href: String,
     pub task_id: Option<String>,
+    /// share of the fact time in percent, written right after the link: `[A](...) 30%`
+    pub weight: Option<u32>,
 }
 
 pub trait HamsterEnrichedData {
     fn task(&self) -> Option<TaskLink>;
+    /// All links of the description, in order of appearance
+    fn tasks(&self) -> Vec<TaskLink>;
+    /// Links time is booked to: those outside comment lists, or the first
+    /// link if all of them are in lists
+    fn booking_links(&self) -> Vec<TaskLink>;
     /// Extracts comments
     fn comments(&self) -> Vec<String>;
+    /// Extracts comments, rendered according to the mode
+    fn comments_in_mode(&self, mode: CommentMode) -> Vec<String>;
+    /// Description with links to the task pointed to the new url instead,
+    /// `None` if there are no such links
+    fn relinked(&self, old_task_id: &str, new_url: &str) -> Option<String>;
+}
+
+/// Task id out of a task link, e.g. https://app.asana.com/0/0/1200000000000001/f
+pub fn task_id_from_url(url: &str) -> Option<String> {
+    TASK_ID_REGEX
+        .captures(url)
+        .map(|captures| captures["task_id"].to_string())
 }
 
 impl HamsterEnrichedData for HamsterFact {
     fn task(&self) -> Option<TaskLink> {
+        self.tasks().into_iter().next()
+    }
+
+    fn tasks(&self) -> Vec<TaskLink> {
         let markdown_root =
             markdown::to_mdast(&self.description, &ParseOptions::default()).unwrap();
-        let links = markdown_root.links();
 
-        if links.is_empty() {
-            None
-        } else {
-            let link = links[0];
-            let matched_id = Regex::new(r"\/(?<task_id>\d+)\/f")
-                .unwrap()
-                .captures(link.url.as_str());
-            let task_id = match matched_id {
-                Some(captures) => Some(captures["task_id"].to_string()),
-                None => None,
-            };
-
-            Some(TaskLink {
-                link_title: link.text(),
-                href: link.url.clone(),
-                task_id,
-            })
-        }
+        links_with_weights(&markdown_root)
+            .into_iter()
+            .map(task_link)
+            .collect()
     }
 
-    /// extracts comments, but with some catches
-    fn comments(&self) -> Vec<String> {
+    fn booking_links(&self) -> Vec<TaskLink> {
         let markdown_root =
             markdown::to_mdast(&self.description, &ParseOptions::default()).unwrap();
-        let comments_from_description: Vec<String> = markdown_root
+
+        let task_links: Vec<TaskLink> = markdown_root
             .children()
             .unwrap()
+            .iter()
+            .filter(|node| !matches!(node, Node::List(_)))
+            .flat_map(links_with_weights)
+            .map(task_link)
+            .collect();
+        if task_links.is_empty() {
+            self.task().into_iter().collect()
+        } else {
+            task_links
+        }
+    }
+
+    fn relinked(&self, old_task_id: &str, new_url: &str) -> Option<String> {
+        let mut old_hrefs: Vec<String> = self
+            .tasks()
             .into_iter()
-            .filter_map(|node| match node {
-                Node::List(_) => Some(node.texts()),
-                _ => None,
-            })
-            .flatten()
-            .map(|text| text.value.clone())
+            .filter(|task_link| task_link.task_id.as_deref() == Some(old_task_id))
+            .map(|task_link| task_link.href)
             .collect();
+        if old_hrefs.is_empty() {
+            return None;
+        }
+        old_hrefs.dedup();
+        let mut description = self.description.clone();
+        for old_href in old_hrefs {
+            // the whole link target, `](old)` or `](old "title")` - not a longer href
+            // starting with the old one
+            let target_regex =
+                Regex::new(&format!(r"\]\({}([\s)])", regex::escape(&old_href))).unwrap();
+            description = target_regex
+                .replace_all(&description, |captures: &regex::Captures| {
+                    format!("]({new_url}{}", &captures[1])
+                })
+                .replace(&format!("<{old_href}>"), &format!("<{new_url}>"));
+        }
+        Some(description)
+    }
+
+    /// extracts comments, but with some catches
+    fn comments(&self) -> Vec<String> {
+        let markdown_root =
+            markdown::to_mdast(&self.description, &comment_parse_options()).unwrap();
+        let mut comments_from_description = vec![];
+        for node in markdown_root.children().unwrap() {
+            if let Node::List(_) = node {
+                list_texts(node, &mut comments_from_description);
+            }
+        }
 
-        if comments_from_description.len() == 0 {
+        if comments_from_description.is_empty() {
+            vec![self.activity.clone()]
+        } else {
+            comments_from_description
+        }
+    }
+
+    fn comments_in_mode(&self, mode: CommentMode) -> Vec<String> {
+        let include_paragraphs = match mode {
+            CommentMode::Simple => return self.comments(),
+            CommentMode::Rich { include_paragraphs } => include_paragraphs,
+        };
+        let markdown_root =
+            markdown::to_mdast(&self.description, &comment_parse_options()).unwrap();
+
+        let mut comments_from_description = vec![];
+        for node in markdown_root.children().unwrap() {
+            match node {
+                Node::List(list) => render_list(list, 0, &mut comments_from_description),
+                Node::Paragraph(_) if include_paragraphs => {
+                    // a line of task links is not a comment
+                    if is_private(node) || without_links(node).trim().is_empty() {
+                        continue;
+                    }
+                    let line = squash_whitespace(&inline_text(node));
+                    if !line.is_empty() {
+                        comments_from_description.push(line);
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        if comments_from_description.is_empty() {
             vec![self.activity.clone()]
         } else {
             comments_from_description
@@ -66,13 +167,169 @@ impl HamsterEnrichedData for HamsterFact {
     }
 }
 
+/// Comments never include items marked with `<!-- private -->` or ~~struck~~ text,
+/// whatever the mode - strikethrough has to be parsed for that
+fn comment_parse_options() -> ParseOptions {
+    ParseOptions {
+        constructs: Constructs {
+            gfm_strikethrough: true,
+            ..Constructs::default()
+        },
+        ..ParseOptions::default()
+    }
+}
+
+/// Texts of list items, one by one, for the simple mode
+fn list_texts(node: &Node, texts: &mut Vec<String>) {
+    match node {
+        Node::Text(text) => texts.push(text.value.clone()),
+        Node::Delete(_) => {}
+        Node::ListItem(_) if is_private(node) => {}
+        _ => {
+            for child in node.children().into_iter().flatten() {
+                list_texts(child, texts);
+            }
+        }
+    }
+}
+
+/// Renders list items as lines, indented by nesting level
+fn render_list(list: &List, depth: usize, lines: &mut Vec<String>) {
+    for item in list.children.iter() {
+        if is_private(item) {
+            continue;
+        }
+        let Some(children) = item.children() else {
+            continue;
+        };
+        let item_text: Vec<String> = children
+            .iter()
+            .filter(|child| !matches!(child, Node::List(_)))
+            .map(|child| squash_whitespace(&inline_text(child)))
+            .filter(|text| !text.is_empty())
+            .collect();
+        if !item_text.is_empty() {
+            lines.push(format!("{}{}", "  ".repeat(depth), item_text.join(" ")));
+        }
+        for child in children {
+            if let Node::List(nested_list) = child {
+                render_list(nested_list, depth + 1, lines);
+            }
+        }
+    }
+}
+
+/// Plain text of the node, with inline formatting dropped and struck text left out
+fn inline_text(node: &Node) -> String {
+    match node {
+        Node::Text(text) => text.value.clone(),
+        Node::InlineCode(code) => code.value.clone(),
+        Node::Break(_) => String::from(" "),
+        Node::Delete(_) | Node::Html(_) => String::new(),
+        _ => match node.children() {
+            Some(children) => children.iter().map(inline_text).collect(),
+            None => String::new(),
+        },
+    }
+}
+
+/// Struck or private parts leave extra spaces behind
+fn squash_whitespace(text: &str) -> String {
+    text.split_whitespace().collect::<Vec<&str>>().join(" ")
+}
+
+/// Same as `inline_text`, but without link texts
+fn without_links(node: &Node) -> String {
+    match node {
+        Node::Link(_) => String::new(),
+        Node::Text(_) | Node::InlineCode(_) | Node::Break(_) | Node::Delete(_) | Node::Html(_) => {
+            inline_text(node)
+        }
+        // weights written after links, like `30%`, are part of the link line
+        _ => match node.children() {
+            Some(children) => children
+                .iter()
+                .map(|child| WEIGHT_REGEX.replace(&without_links(child), "").to_string())
+                .collect(),
+            None => String::new(),
+        },
+    }
+}
+
+/// Whether the node is marked with `<!-- private -->` (not looking into nested lists)
+fn is_private(node: &Node) -> bool {
+    match node {
+        Node::Html(html) => PRIVATE_REGEX.is_match(&html.value),
+        Node::List(_) => false,
+        _ => match node.children() {
+            Some(children) => children.iter().any(is_private),
+            None => false,
+        },
+    }
+}
+
+fn task_link((link, weight): (&Link, Option<u32>)) -> TaskLink {
+    TaskLink {
+        link_title: link.text(),
+        href: link.url.clone(),
+        task_id: task_id_from_url(&link.url),
+        weight,
+    }
+}
+
+/// Collects links along with the percentage that immediately follows them
+fn links_with_weights(node: &Node) -> Vec<(&Link, Option<u32>)> {
+    let mut links = vec![];
+    if let Some(children) = node.children() {
+        for (index, child) in children.iter().enumerate() {
+            match child {
+                Node::Link(link) => {
+                    let weight = match children.get(index + 1) {
+                        Some(Node::Text(text)) => WEIGHT_REGEX
+                            .captures(&text.value)
+                            .map(|captures| captures["weight"].parse().unwrap()),
+                        _ => None,
+                    };
+                    links.push((link, weight));
+                }
+                _ => links.extend(links_with_weights(child)),
+            }
+        }
+    }
+    links
+}
+
+/// Divides the whole (1.0) between links: explicit percentages are taken as is,
+/// the remainder is split evenly between links without one. If percentages
+/// don't add up to 100, shares are scaled proportionally.
+pub fn split_shares(weights: &[Option<u32>]) -> Vec<f64> {
+    let explicit_total: u32 = weights.iter().flatten().sum();
+    let unweighted_count = weights.iter().filter(|weight| weight.is_none()).count();
+    let remainder = 100u32.saturating_sub(explicit_total) as f64;
+
+    let raw: Vec<f64> = weights
+        .iter()
+        .map(|weight| match weight {
+            Some(weight) => *weight as f64,
+            None => remainder / unweighted_count as f64,
+        })
+        .collect();
+    let total: f64 = raw.iter().sum();
+
+    if total == 0.0 {
+        vec![1.0 / weights.len() as f64; weights.len()]
+    } else {
+        raw.into_iter().map(|share| share / total).collect()
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use chrono::{Local, NaiveDate, NaiveDateTime, NaiveTime, TimeDelta};
 
     use crate::hamster::HamsterFact;
 
-    use super::HamsterEnrichedData;
+    use super::{split_shares, CommentMode, HamsterEnrichedData};
 
     fn get_fact(
         activity: Option<String>,
@@ -88,11 +345,12 @@ mod tests {
         .unwrap();
         HamsterFact {
             id: 1,
-            start_time: start_time,
+            start_time,
             end_time: Some(start_time + TimeDelta::new(3600, 0).unwrap()),
             description: description.unwrap_or(String::from("default description")),
             activity: activity.unwrap_or(String::from("running and jumping")),
             category: category.unwrap_or(String::from("Sports")),
+            tags: vec![],
         }
     }
 
@@ -100,6 +358,38 @@ mod tests {
         get_fact(None, Some(description), None)
     }
 
+    #[test]
+    fn relinked_replaces_only_old_task_links() {
+        let fact = get_fact_with_descr(String::from(
+            "[Old](https://example.com/0/111/f) and [Other](https://example.com/0/222/f)\n\
+            - see also [old again](https://example.com/0/111/f \"title\")",
+        ));
+
+        assert_eq!(
+            fact.relinked("111", "https://example.com/0/333/f").unwrap(),
+            "[Old](https://example.com/0/333/f) and [Other](https://example.com/0/222/f)\n\
+            - see also [old again](https://example.com/0/333/f \"title\")"
+        );
+        assert!(fact
+            .relinked("444", "https://example.com/0/333/f")
+            .is_none());
+    }
+
+    #[test]
+    fn relinked_replaces_whole_link_targets() {
+        // links starting with the old href are relinked as a whole, not just the prefix
+        let fact = get_fact_with_descr(String::from(
+            "[Old](https://example.com/0/111/f)\n\
+            - see [subtask](https://example.com/0/111/f/subtask/5)",
+        ));
+
+        assert_eq!(
+            fact.relinked("111", "https://example.com/0/333/f").unwrap(),
+            "[Old](https://example.com/0/333/f)\n\
+            - see [subtask](https://example.com/0/333/f)"
+        );
+    }
+
     #[test]
     fn ensure_task_extracted_correctly() {
         let fact = get_fact_with_descr(String::from(
@@ -116,6 +406,18 @@ mod tests {
         assert_eq!(extracted_task.task_id, Some(String::from("123456")));
     }
 
+    #[test]
+    fn formatted_task_title_extracted_correctly() {
+        let fact = get_fact_with_descr(String::from(
+            "[**Fix** the `parser` bug](https://example.com/task/123456/f)",
+        ));
+
+        let extracted_task = fact.task().unwrap();
+
+        assert_eq!(extracted_task.link_title, "Fix the parser bug");
+        assert_eq!(extracted_task.task_id, Some(String::from("123456")));
+    }
+
     #[test]
     fn simple_comments_extracted_correctly() {
         let fact = get_fact_with_descr(String::from(
@@ -167,4 +469,113 @@ mod tests {
         let comments = fact.comments();
         assert_eq!(comments, ["some activity"]);
     }
+
+    #[test]
+    fn all_links_extracted_with_weights() {
+        let fact = get_fact_with_descr(String::from(
+            "pairing on [A](https://example.com/task/111/f) 30% \
+            and [B](https://example.com/task/222/f)\n\
+            - reviewed [C](https://example.com/task/333/f)",
+        ));
+
+        let tasks = fact.tasks();
+
+        assert_eq!(
+            tasks
+                .iter()
+                .map(|task| (task.task_id.clone().unwrap(), task.weight))
+                .collect::<Vec<_>>(),
+            [
+                (String::from("111"), Some(30)),
+                (String::from("222"), None),
+                (String::from("333"), None)
+            ]
+        );
+        assert_eq!(fact.task().unwrap().link_title, "A");
+    }
+
+    #[test]
+    fn shares_split_correctly() {
+        assert_eq!(split_shares(&[None, None]), [0.5, 0.5]);
+        assert_eq!(split_shares(&[Some(30), None]), [0.3, 0.7]);
+        assert_eq!(split_shares(&[Some(20), None, None]), [0.2, 0.4, 0.4]);
+        assert_eq!(split_shares(&[Some(1), Some(3)]), [0.25, 0.75]);
+        assert_eq!(split_shares(&[Some(100), None]), [1.0, 0.0]);
+        assert_eq!(split_shares(&[Some(0), Some(0)]), [0.5, 0.5]);
+    }
+
+    #[test]
+    fn rich_comments_keep_formatting_and_nesting() {
+        let fact = get_fact_with_descr(String::from(
+            "[Some task](https://example.com/task/123456/f)\n\
+            - fixed `parse_date` for **all** locales\n  \
+              - see [the PR](https://example.com/pr/1)\n\
+            - talked to *the client*",
+        ));
+        let comments = fact.comments_in_mode(CommentMode::Rich {
+            include_paragraphs: false,
+        });
+        assert_eq!(
+            comments,
+            [
+                "fixed parse_date for all locales",
+                "  see the PR",
+                "talked to the client"
+            ]
+        );
+    }
+
+    #[test]
+    fn rich_comments_include_paragraphs() {
+        let fact = get_fact_with_descr(String::from(
+            "[Some task](https://example.com/task/123456/f) 50%\n\n\
+            Investigated the *flaky* test\n\n\
+            - rewrote fixtures",
+        ));
+        assert_eq!(
+            fact.comments_in_mode(CommentMode::Rich {
+                include_paragraphs: true
+            }),
+            ["Investigated the flaky test", "rewrote fixtures"]
+        );
+        assert_eq!(
+            fact.comments_in_mode(CommentMode::Rich {
+                include_paragraphs: false
+            }),
+            ["rewrote fixtures"]
+        );
+    }
+
+    #[test]
+    fn simple_comments_skip_private_and_struck() {
+        let fact = get_fact_with_descr(String::from(
+            "[Some task](https://example.com/task/123456/f)\n\
+            - salary talk <!-- private -->\n  \
+              - details\n\
+            - ~~abandoned approach~~\n\
+            - fixed two bugs",
+        ));
+        assert_eq!(fact.comments(), ["fixed two bugs"]);
+        assert_eq!(
+            fact.comments_in_mode(CommentMode::default()),
+            ["fixed two bugs"]
+        );
+    }
+
+    #[test]
+    fn rich_comments_skip_private_and_struck() {
+        let fact = get_fact_with_descr(String::from(
+            "[Some task](https://example.com/task/123456/f)\n\
+            - salary talk <!-- private -->\n  \
+              - details\n\
+            - ~~abandoned approach~~\n\
+            - fixed ~~three~~ two bugs",
+        ));
+        assert_eq!(
+            fact.comments_in_mode(CommentMode::Rich {
+                include_paragraphs: false
+            }),
+            ["fixed two bugs"]
+        );
+    }
 }
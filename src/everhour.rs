@@ -0,0 +1,260 @@
+//! Everhour API client. Talks to the production API by default, but can be pointed
+//! at a stub server or a proxy, record what it gets, and replay recorded responses.
+use chrono::NaiveDate;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::sync::Mutex;
+
+pub const API_URL: &str = "https://api.everhour.com";
+
+#[derive(Deserialize)]
+pub struct User {
+    pub id: i64,
+    #[serde(default)]
+    pub name: String,
+}
+
+#[derive(Deserialize)]
+pub struct Task {
+    pub id: String,
+    #[serde(default)]
+    pub name: String,
+}
+
+#[derive(Deserialize)]
+pub struct TimeRecord {
+    pub id: i64,
+    pub date: NaiveDate,
+    /// seconds
+    pub time: i64,
+    #[serde(default)]
+    pub comment: Option<String>,
+    /// not set for time booked to a project
+    #[serde(default)]
+    pub task: Option<Task>,
+}
+
+/// Time of a user for a day, with the billable flag if it's set by billing rules
+#[derive(Serialize)]
+pub struct TimeBooking {
+    pub date: NaiveDate,
+    pub user: i64,
+    /// seconds
+    pub time: i64,
+    pub comment: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub billable: Option<bool>,
+}
+
+/// Request and the response to it, as saved by recording and used by replaying
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Exchange {
+    pub method: String,
+    /// with the query
+    pub path: String,
+    pub status: u16,
+    #[serde(default)]
+    pub body: serde_json::Value,
+}
+
+enum Mode {
+    Live,
+    /// exchanges are written to the file as they happen
+    Recording(String),
+    /// responses come from recorded exchanges, each used once
+    Replaying,
+}
+
+pub struct Client {
+    api_token: String,
+    base_url: String,
+    http: reqwest::Client,
+    mode: Mode,
+    exchanges: Mutex<Vec<Exchange>>,
+}
+
+impl Client {
+    pub fn new(api_token: String, base_url: String) -> Client {
+        Client {
+            api_token,
+            base_url: base_url.trim_end_matches('/').to_string(),
+            http: reqwest::Client::new(),
+            mode: Mode::Live,
+            exchanges: Mutex::new(vec![]),
+        }
+    }
+
+    /// Saves every request and response to the file, for replaying later
+    pub fn recording(self, path: String) -> Client {
+        Client {
+            mode: Mode::Recording(path),
+            ..self
+        }
+    }
+
+    /// Answers requests from a file written by a recording client, without calling the API
+    pub fn replaying(path: &str) -> Result<Client, String> {
+        let raw = fs::read_to_string(path)
+            .map_err(|err| format!("couldn't read recorded responses {path}: {err}"))?;
+        let exchanges: Vec<Exchange> = serde_json::from_str(&raw)
+            .map_err(|err| format!("couldn't parse recorded responses {path}: {err}"))?;
+        Ok(Client {
+            api_token: String::new(),
+            base_url: String::new(),
+            http: reqwest::Client::new(),
+            mode: Mode::Replaying,
+            exchanges: Mutex::new(exchanges),
+        })
+    }
+
+    /// Recorded exchanges no request asked for yet, when replaying
+    pub fn unused_exchanges(&self) -> Vec<Exchange> {
+        match self.mode {
+            Mode::Replaying => self.exchanges.lock().unwrap().clone(),
+            _ => vec![],
+        }
+    }
+
+    pub async fn current_user(&self) -> Result<User, String> {
+        self.call_json(reqwest::Method::GET, "/users/me", None)
+            .await
+    }
+
+    /// Records of the user from `from` to `to` (inclusive)
+    pub async fn time_records(
+        &self,
+        user: i64,
+        from: NaiveDate,
+        to: NaiveDate,
+    ) -> Result<Vec<TimeRecord>, String> {
+        self.call_json(
+            reqwest::Method::GET,
+            &format!("/users/{user}/time?from={from}&to={to}"),
+            None,
+        )
+        .await
+    }
+
+    /// Adds time to the task for the day, on top of whatever was booked before
+    pub async fn add_task_time(&self, task_id: &str, booking: &TimeBooking) -> Result<(), String> {
+        self.call(
+            reqwest::Method::POST,
+            &format!("/tasks/{task_id}/time"),
+            Some(booking),
+        )
+        .await?;
+        Ok(())
+    }
+
+    /// Sets time on the task for the day, replacing whatever was booked before
+    pub async fn put_task_time(&self, task_id: &str, booking: &TimeBooking) -> Result<(), String> {
+        self.call(
+            reqwest::Method::PUT,
+            &format!("/tasks/{task_id}/time"),
+            Some(booking),
+        )
+        .await?;
+        Ok(())
+    }
+
+    /// Sets time on the project itself for the day, replacing whatever was booked before
+    pub async fn put_project_time(
+        &self,
+        project_id: &str,
+        booking: &TimeBooking,
+    ) -> Result<(), String> {
+        self.call(
+            reqwest::Method::PUT,
+            &format!("/projects/{project_id}/time"),
+            Some(booking),
+        )
+        .await?;
+        Ok(())
+    }
+
+    /// Removes a single time record
+    pub async fn delete_time(&self, record_id: i64) -> Result<(), String> {
+        self.call(reqwest::Method::DELETE, &format!("/time/{record_id}"), None)
+            .await?;
+        Ok(())
+    }
+
+    async fn call_json<T: serde::de::DeserializeOwned>(
+        &self,
+        method: reqwest::Method,
+        path: &str,
+        booking: Option<&TimeBooking>,
+    ) -> Result<T, String> {
+        let body = self.call(method, path, booking).await?;
+        serde_json::from_value(body)
+            .map_err(|err| format!("{path} gave unexpected response: {err}"))
+    }
+
+    /// Response body of the request, as JSON (null if empty)
+    async fn call(
+        &self,
+        method: reqwest::Method,
+        path: &str,
+        booking: Option<&TimeBooking>,
+    ) -> Result<serde_json::Value, String> {
+        let exchange = match &self.mode {
+            Mode::Replaying => self.replay(method.as_str(), path)?,
+            Mode::Live | Mode::Recording(_) => self.send(method, path, booking).await?,
+        };
+        if let Mode::Recording(record_path) = &self.mode {
+            let mut exchanges = self.exchanges.lock().unwrap();
+            exchanges.push(exchange.clone());
+            let raw = serde_json::to_string_pretty(&*exchanges).unwrap();
+            fs::write(record_path, raw)
+                .map_err(|err| format!("couldn't write {record_path}: {err}"))?;
+        }
+        if (200..300).contains(&exchange.status) {
+            Ok(exchange.body)
+        } else {
+            Err(format!(
+                "{path} responded with {}: {}",
+                exchange.status, exchange.body
+            ))
+        }
+    }
+
+    async fn send(
+        &self,
+        method: reqwest::Method,
+        path: &str,
+        booking: Option<&TimeBooking>,
+    ) -> Result<Exchange, String> {
+        let mut request = self
+            .http
+            .request(method.clone(), format!("{}{path}", self.base_url))
+            .header("X-Api-Key", &self.api_token);
+        if let Some(booking) = booking {
+            request = request.json(booking);
+        }
+        let response = request
+            .send()
+            .await
+            .map_err(|err| format!("request to {path} failed: {err}"))?;
+        let status = response.status().as_u16();
+        let text = response.text().await.unwrap_or_default();
+        Ok(Exchange {
+            method: method.to_string(),
+            path: path.to_string(),
+            status,
+            // error pages aren't necessarily JSON
+            body: serde_json::from_str(&text).unwrap_or(match text.is_empty() {
+                true => serde_json::Value::Null,
+                false => serde_json::Value::String(text),
+            }),
+        })
+    }
+
+    fn replay(&self, method: &str, path: &str) -> Result<Exchange, String> {
+        let mut exchanges = self.exchanges.lock().unwrap();
+        let position = exchanges
+            .iter()
+            .position(|exchange| exchange.method == method && exchange.path == path)
+            .ok_or(format!("No recorded response to {method} {path}"))?;
+        Ok(exchanges.remove(position))
+    }
+}
@@ -0,0 +1,358 @@
+use chrono::{DateTime, Local, Utc};
+use serde::Serialize;
+use std::time::Duration;
+
+use crate::config::Config;
+use crate::enrichment::HamsterEnrichedData;
+use crate::hamster::HamsterFact;
+use crate::utils::csv_line;
+
+/// Fact as it's written to CSV and JSON lines
+#[derive(Serialize)]
+struct ExportedFact {
+    id: i64,
+    start_time: DateTime<Local>,
+    /// not set for the running fact
+    end_time: Option<DateTime<Local>>,
+    /// seconds, up to now for the running fact
+    duration: u64,
+    activity: String,
+    category: String,
+    tags: Vec<String>,
+    task_ids: Vec<String>,
+    description: String,
+}
+
+impl ExportedFact {
+    fn new(fact: &HamsterFact, now: DateTime<Local>) -> ExportedFact {
+        ExportedFact {
+            id: fact.id,
+            start_time: fact.start_time,
+            end_time: fact.end_time,
+            duration: duration(fact, now).as_secs(),
+            activity: fact.activity.clone(),
+            category: fact.category.clone(),
+            tags: fact.tags.clone(),
+            task_ids: fact
+                .tasks()
+                .into_iter()
+                .filter_map(|task_link| task_link.task_id)
+                .collect(),
+            description: fact.description.clone(),
+        }
+    }
+}
+
+fn duration(fact: &HamsterFact, now: DateTime<Local>) -> Duration {
+    (fact.end_time.unwrap_or(now) - fact.start_time)
+        .to_std()
+        .unwrap_or_default()
+}
+
+/// What other tools call a description - the task title, or the activity if there's no link
+fn title(fact: &HamsterFact) -> String {
+    fact.task()
+        .map(|task_link| task_link.link_title)
+        .unwrap_or(fact.activity.clone())
+}
+
+pub fn to_csv(facts: &[HamsterFact], now: DateTime<Local>) -> String {
+    let header = [
+        "id",
+        "start_time",
+        "end_time",
+        "duration",
+        "activity",
+        "category",
+        "tags",
+        "task_ids",
+        "description",
+    ]
+    .map(String::from);
+    std::iter::once(csv_line(&header) + "\n")
+        .chain(facts.iter().map(|fact| {
+            let exported = ExportedFact::new(fact, now);
+            let line = [
+                exported.id.to_string(),
+                exported.start_time.to_rfc3339(),
+                exported
+                    .end_time
+                    .map(|end_time| end_time.to_rfc3339())
+                    .unwrap_or_default(),
+                exported.duration.to_string(),
+                exported.activity,
+                exported.category,
+                exported.tags.join(", "),
+                exported.task_ids.join(", "),
+                exported.description,
+            ];
+            csv_line(&line) + "\n"
+        }))
+        .collect()
+}
+
+pub fn to_jsonl(facts: &[HamsterFact], now: DateTime<Local>) -> String {
+    facts
+        .iter()
+        .map(|fact| serde_json::to_string(&ExportedFact::new(fact, now)).unwrap() + "\n")
+        .collect()
+}
+
+/// iCalendar with a VEVENT per fact, the running fact ending now. Summary is
+/// "activity@category" as Hamster writes it, so import reads it back.
+pub fn to_ical(facts: &[HamsterFact], now: DateTime<Local>) -> String {
+    let ical_time = |time: DateTime<Local>| {
+        time.with_timezone(&Utc)
+            .format("%Y%m%dT%H%M%SZ")
+            .to_string()
+    };
+    let mut lines = vec![
+        String::from("BEGIN:VCALENDAR"),
+        String::from("VERSION:2.0"),
+        String::from("PRODID:-//ham-cli//EN"),
+    ];
+    for fact in facts {
+        lines.push(String::from("BEGIN:VEVENT"));
+        lines.push(format!("UID:hamster-fact-{}@ham-cli", fact.id));
+        lines.push(format!("DTSTAMP:{}", ical_time(now)));
+        lines.push(format!("DTSTART:{}", ical_time(fact.start_time)));
+        lines.push(format!("DTEND:{}", ical_time(fact.end_time.unwrap_or(now))));
+        lines.push(format!(
+            "SUMMARY:{}",
+            ical_text(&format!("{}@{}", fact.activity, fact.category))
+        ));
+        if !fact.description.is_empty() {
+            lines.push(format!("DESCRIPTION:{}", ical_text(&fact.description)));
+        }
+        if !fact.tags.is_empty() {
+            let tags: Vec<String> = fact.tags.iter().map(|tag| ical_text(tag)).collect();
+            lines.push(format!("CATEGORIES:{}", tags.join(",")));
+        }
+        if let Some(task_link) = fact.task() {
+            lines.push(format!("URL:{}", task_link.href));
+        }
+        lines.push(String::from("END:VEVENT"));
+    }
+    lines.push(String::from("END:VCALENDAR"));
+    lines
+        .iter()
+        .map(|line| fold_ical_line(line) + "\r\n")
+        .collect()
+}
+
+/// Escapes iCalendar TEXT value
+fn ical_text(text: &str) -> String {
+    text.replace('\\', "\\\\")
+        .replace(';', "\\;")
+        .replace(',', "\\,")
+        .replace('\n', "\\n")
+}
+
+/// Lines longer than 75 octets are continued on the next line, starting with a space
+fn fold_ical_line(line: &str) -> String {
+    let mut folded = String::new();
+    let mut line_length = 0;
+    for character in line.chars() {
+        if line_length + character.len_utf8() > 75 {
+            folded.push_str("\r\n ");
+            line_length = 1;
+        }
+        folded.push(character);
+        line_length += character.len_utf8();
+    }
+    folded
+}
+
+/// CSV for Toggl Track import - category becomes the project
+pub fn to_toggl_csv(
+    facts: &[HamsterFact],
+    config: &Config,
+    email: &str,
+    now: DateTime<Local>,
+) -> String {
+    let header = [
+        "Email",
+        "Project",
+        "Description",
+        "Billable",
+        "Start date",
+        "Start time",
+        "Duration",
+        "Tags",
+    ]
+    .map(String::from);
+    std::iter::once(csv_line(&header) + "\n")
+        .chain(facts.iter().map(|fact| {
+            let seconds = duration(fact, now).as_secs();
+            let line = [
+                email.to_string(),
+                fact.category.clone(),
+                title(fact),
+                billable(fact, config),
+                fact.start_time.format("%Y-%m-%d").to_string(),
+                fact.start_time.format("%H:%M:%S").to_string(),
+                format!(
+                    "{:02}:{:02}:{:02}",
+                    seconds / 3600,
+                    seconds % 3600 / 60,
+                    seconds % 60
+                ),
+                fact.tags.join(", "),
+            ];
+            csv_line(&line) + "\n"
+        }))
+        .collect()
+}
+
+/// CSV for Clockify import - category becomes the project
+pub fn to_clockify_csv(
+    facts: &[HamsterFact],
+    config: &Config,
+    email: &str,
+    now: DateTime<Local>,
+) -> String {
+    let header = [
+        "Project",
+        "Description",
+        "Email",
+        "Tags",
+        "Billable",
+        "Start Date",
+        "Start Time",
+        "End Date",
+        "End Time",
+    ]
+    .map(String::from);
+    std::iter::once(csv_line(&header) + "\n")
+        .chain(facts.iter().map(|fact| {
+            let end_time = fact.end_time.unwrap_or(now);
+            let line = [
+                fact.category.clone(),
+                title(fact),
+                email.to_string(),
+                fact.tags.join(", "),
+                billable(fact, config),
+                fact.start_time.format("%Y-%m-%d").to_string(),
+                fact.start_time.format("%H:%M:%S").to_string(),
+                end_time.format("%Y-%m-%d").to_string(),
+                end_time.format("%H:%M:%S").to_string(),
+            ];
+            csv_line(&line) + "\n"
+        }))
+        .collect()
+}
+
+/// "Yes" or "No" by billing rules, empty if no rule matches
+fn billable(fact: &HamsterFact, config: &Config) -> String {
+    match config.billable(fact) {
+        Some(true) => String::from("Yes"),
+        Some(false) => String::from("No"),
+        None => String::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{DateTime, Local, NaiveDate};
+
+    use crate::config::Config;
+    use crate::hamster::HamsterFact;
+
+    use super::{fold_ical_line, to_csv, to_ical, to_jsonl, to_toggl_csv};
+
+    fn at(hour: u32, minute: u32) -> DateTime<Local> {
+        NaiveDate::from_ymd_opt(2024, 5, 13)
+            .unwrap()
+            .and_hms_opt(hour, minute, 0)
+            .unwrap()
+            .and_local_timezone(Local)
+            .unwrap()
+    }
+
+    fn facts() -> Vec<HamsterFact> {
+        vec![
+            HamsterFact {
+                id: 1,
+                start_time: at(9, 0),
+                end_time: Some(at(10, 30)),
+                description: String::from(
+                    "[Parser, again](https://example.com/0/111/f)\n- fixed it",
+                ),
+                activity: String::from("coding"),
+                category: String::from("Work"),
+                tags: vec![String::from("backend"), String::from("urgent")],
+            },
+            HamsterFact {
+                id: 2,
+                start_time: at(11, 0),
+                end_time: None,
+                description: String::new(),
+                activity: String::from("stand-up"),
+                category: String::from("Work"),
+                tags: vec![],
+            },
+        ]
+    }
+
+    #[test]
+    fn csv_exported() {
+        let csv = to_csv(&facts(), at(11, 15));
+        let lines: Vec<&str> = csv.lines().collect();
+        assert_eq!(
+            lines[0],
+            "id,start_time,end_time,duration,activity,category,tags,task_ids,description"
+        );
+        assert!(lines[1].starts_with("1,"));
+        assert!(lines[1].contains(",5400,coding,Work,\"backend, urgent\",111,"));
+        assert!(lines[3].contains(",,900,stand-up,Work,,,"));
+    }
+
+    #[test]
+    fn jsonl_exported() {
+        let jsonl = to_jsonl(&facts(), at(11, 15));
+        let first: serde_json::Value = serde_json::from_str(jsonl.lines().next().unwrap()).unwrap();
+        assert_eq!(first["duration"], 5400);
+        assert_eq!(first["task_ids"], serde_json::json!(["111"]));
+        assert_eq!(first["tags"], serde_json::json!(["backend", "urgent"]));
+        let second: serde_json::Value =
+            serde_json::from_str(jsonl.lines().nth(1).unwrap()).unwrap();
+        assert!(second["end_time"].is_null());
+    }
+
+    #[test]
+    fn ical_exported() {
+        let ical = to_ical(&facts(), at(11, 15));
+        assert!(ical.starts_with("BEGIN:VCALENDAR\r\n"));
+        assert!(ical.ends_with("END:VCALENDAR\r\n"));
+        assert_eq!(ical.matches("BEGIN:VEVENT").count(), 2);
+        assert!(ical.contains("SUMMARY:coding@Work\r\n"));
+        assert!(ical.contains(
+            "DESCRIPTION:[Parser\\, again](https://example.com/0/111/f)\\n- fixed it\r\n"
+        ));
+        assert!(ical.contains("CATEGORIES:backend,urgent\r\n"));
+        assert!(ical.contains("URL:https://example.com/0/111/f\r\n"));
+    }
+
+    #[test]
+    fn long_ical_lines_folded() {
+        let line = "DESCRIPTION:".to_string() + &"x".repeat(100);
+        let folded = fold_ical_line(&line);
+        let parts: Vec<&str> = folded.split("\r\n").collect();
+        assert_eq!(parts.len(), 2);
+        assert_eq!(parts[0].len(), 75);
+        assert_eq!(parts[0].to_string() + &parts[1][1..], line);
+    }
+
+    #[test]
+    fn toggl_csv_exported() {
+        let config: Config =
+            toml::from_str("[[billing]]\ncategory = \"Work\"\nbillable = true\n").unwrap();
+        let csv = to_toggl_csv(&facts(), &config, "me@example.com", at(11, 15));
+        assert_eq!(
+            csv,
+            "Email,Project,Description,Billable,Start date,Start time,Duration,Tags\n\
+            me@example.com,Work,\"Parser, again\",Yes,2024-05-13,09:00:00,01:30:00,\"backend, urgent\"\n\
+            me@example.com,Work,stand-up,Yes,2024-05-13,11:00:00,00:15:00,\n"
+        );
+    }
+}
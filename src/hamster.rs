@@ -1,7 +1,9 @@
-use chrono::{DateTime, Local, NaiveDate, NaiveDateTime};
-use sqlite::State;
+use chrono::{DateTime, Local, NaiveDateTime};
+use sqlite::{State, Value};
 use std::path::Path;
 
+use crate::date_range::DateRange;
+
 pub struct HamsterFact {
     pub id: i64,
     pub start_time: DateTime<Local>,
@@ -9,102 +11,406 @@ pub struct HamsterFact {
     pub description: String,
     pub activity: String,
     pub category: String,
+    pub tags: Vec<String>,
 }
 
+/// Hamster database schema version ham-cli knows how to write to
+const KNOWN_SCHEMA_VERSION: i64 = 9;
+
+/// Facts with their activity, category and tags - conditions are appended to it
+const FACTS_QUERY: &str = "
+    SELECT
+        facts.id as `fact_id`,
+        activities.name as `activity_name`,
+        categories.name as `category_name`,
+        start_time,
+        end_time,
+        description,
+        (
+            SELECT GROUP_CONCAT(tags.name, char(10))
+            FROM fact_tags
+            JOIN tags ON tags.id=fact_tags.tag_id
+            WHERE fact_tags.fact_id=facts.id
+        ) as `tag_names`
+    FROM facts
+    LEFT JOIN activities
+        ON activities.id=facts.activity_id
+    LEFT JOIN categories
+        ON categories.id=activities.category_id
+";
+
 pub struct HamsterData {
     connection: sqlite::Connection,
 }
 
 impl HamsterData {
     pub fn open(db_path: Option<String>) -> Result<HamsterData, String> {
-        let db_path: String = match db_path {
-            Some(db_path) => db_path,
-            None => match std::env::var("HOME") {
-                Ok(home) => String::from(
-                    Path::new(home.as_str())
-                        .join(".local/share/hamster/hamster.db")
-                        .to_str()
-                        .unwrap(),
-                ),
-                Err(_) => {
-                    return Err(String::from(
-                        "Hamster database path wasn't supplied, $HOME is not set - I give up",
-                    ))
-                }
-            },
-        };
-        match sqlite::open(db_path) {
+        match sqlite::open(self::db_path(db_path)?) {
             Ok(connection) => Ok(HamsterData { connection }),
             Err(hamster_error) => Err(format!("couldn't open hamster db: {}", hamster_error)),
         }
     }
 
-    pub fn get_facts(&self, from: NaiveDate, to: NaiveDate) -> Vec<HamsterFact> {
+    /// Facts started on days of the range
+    pub fn get_facts(&self, range: DateRange) -> Vec<HamsterFact> {
         let mut statement = self
             .connection
-            .prepare(
-                "
-                SELECT
-                    facts.id as `fact_id`,
-                    activities.name as `activity_name`,
-                    categories.name as `category_name`,
-                    start_time,
-                    end_time,
-                    description
-                FROM facts
-                LEFT JOIN activities
-                    ON activities.id=facts.activity_id
-                LEFT JOIN categories
-                    ON categories.id=activities.category_id
+            .prepare(format!(
+                "{FACTS_QUERY}
                 WHERE
                     start_time >= :from
                     AND start_time < :to
                 ORDER BY facts.id;
-                ",
-            )
+                "
+            ))
             .unwrap();
 
         statement
             .bind(
                 &[
-                    (":from", from.to_string().as_str()),
-                    (":to", to.to_string().as_str()),
+                    (":from", range.first().to_string().as_str()),
+                    (":to", range.end().to_string().as_str()),
                 ][..],
             )
             .unwrap();
 
-        let local_tz = Local::now().timezone();
         let mut data: Vec<HamsterFact> = vec![];
-
         while let Ok(State::Row) = statement.next() {
-            data.push(HamsterFact {
-                id: statement.read::<i64, _>("fact_id").unwrap(),
-                description: statement.read::<String, _>("description").unwrap(),
-                category: statement.read::<String, _>("category_name").unwrap(),
-                activity: statement.read::<String, _>("activity_name").unwrap(),
-
-                start_time: {
-                    NaiveDateTime::parse_from_str(
-                        statement.read::<String, _>("start_time").unwrap().as_str(),
-                        "%Y-%m-%d %H:%M:%S",
-                    )
-                    .unwrap()
-                    .and_local_timezone(local_tz)
-                    .unwrap()
-                },
-
-                end_time: {
-                    let end_time_raw = statement.read::<Option<String>, _>("end_time");
-
-                    end_time_raw.unwrap().map(|end_time_str| {
-                        NaiveDateTime::parse_from_str(end_time_str.as_str(), "%Y-%m-%d %H:%M:%S")
-                            .unwrap()
-                            .and_local_timezone(local_tz)
-                            .unwrap()
-                    })
-                },
-            });
+            data.push(read_fact(&statement));
         }
         data
     }
+
+    /// Facts started on days of the range, only of the category if given
+    pub fn get_category_facts(&self, range: DateRange, category: Option<&str>) -> Vec<HamsterFact> {
+        self.get_facts(range)
+            .into_iter()
+            .filter(|fact| category.is_none() || category == Some(fact.category.as_str()))
+            .collect()
+    }
+
+    /// Closed facts with id above `fact_id` or ending after `end_time` - that is,
+    /// added or stopped since then. Fails rather than giving part of them, e.g. when
+    /// Hamster has the database locked.
+    pub fn get_closed_facts_after(
+        &self,
+        fact_id: i64,
+        end_time: DateTime<Local>,
+    ) -> Result<Vec<HamsterFact>, String> {
+        let mut statement = self
+            .connection
+            .prepare(format!(
+                "{FACTS_QUERY}
+                WHERE
+                    end_time IS NOT NULL
+                    AND (facts.id > :id OR end_time > :end_time)
+                ORDER BY facts.id;
+                "
+            ))
+            .map_err(|err| format!("couldn't prepare query: {err}"))?;
+        statement
+            .bind(
+                &[
+                    (":id", Value::Integer(fact_id)),
+                    (":end_time", Value::String(format_time(end_time))),
+                ][..],
+            )
+            .map_err(|err| format!("couldn't bind query params: {err}"))?;
+
+        let mut data: Vec<HamsterFact> = vec![];
+        loop {
+            match statement.next() {
+                Ok(State::Row) => data.push(read_fact(&statement)),
+                Ok(State::Done) => return Ok(data),
+                Err(err) => return Err(format!("couldn't read closed facts: {err}")),
+            }
+        }
+    }
+
+    /// Highest id and latest end time of closed facts, if there are any
+    pub fn last_closed_fact(&self) -> Option<(i64, DateTime<Local>)> {
+        let mut statement = self
+            .connection
+            .prepare(
+                "SELECT MAX(id) as `fact_id`, MAX(end_time) as `end_time`
+                FROM facts WHERE end_time IS NOT NULL",
+            )
+            .unwrap();
+        let Ok(State::Row) = statement.next() else {
+            return None;
+        };
+        let fact_id = statement.read::<Option<i64>, _>("fact_id").unwrap()?;
+        let end_time = statement.read::<Option<String>, _>("end_time").unwrap()?;
+        Some((fact_id, parse_time(&end_time)))
+    }
+
+    pub fn get_fact(&self, fact_id: i64) -> Option<HamsterFact> {
+        let mut statement = self
+            .connection
+            .prepare(format!("{FACTS_QUERY} WHERE facts.id = :id;"))
+            .unwrap();
+        statement.bind((":id", fact_id)).unwrap();
+        match statement.next() {
+            Ok(State::Row) => Some(read_fact(&statement)),
+            _ => None,
+        }
+    }
+
+    /// Adds a fact with its tags, creating activity, category and tags that don't
+    /// exist yet - all of it or, if anything fails, nothing
+    pub fn add_fact(
+        &self,
+        activity: &str,
+        category: &str,
+        start_time: DateTime<Local>,
+        end_time: Option<DateTime<Local>>,
+        description: &str,
+        tags: &[String],
+    ) -> Result<i64, String> {
+        self.ensure_known_schema()?;
+        self.in_transaction(|| {
+            let category_id = self.find_or_create_category(category)?;
+            let activity_id = self.find_or_create_activity(activity, category_id)?;
+            let fact_id = self.insert(
+                "INSERT INTO facts (activity_id, start_time, end_time, description)
+                VALUES (:activity_id, :start_time, :end_time, :description)",
+                &[
+                    (":activity_id", Value::Integer(activity_id)),
+                    (":start_time", Value::String(format_time(start_time))),
+                    (
+                        ":end_time",
+                        match end_time {
+                            Some(end_time) => Value::String(format_time(end_time)),
+                            None => Value::Null,
+                        },
+                    ),
+                    (":description", Value::String(description.to_string())),
+                ],
+            )?;
+            self.add_tags(fact_id, tags)?;
+            Ok(fact_id)
+        })
+    }
+
+    /// Adds tags to the fact, creating ones Hamster doesn't know yet
+    fn add_tags(&self, fact_id: i64, tags: &[String]) -> Result<(), String> {
+        for tag in tags {
+            let found = self.query_id(
+                "SELECT id FROM tags WHERE name = :name",
+                &[(":name", Value::String(tag.to_string()))],
+            )?;
+            let tag_id = match found {
+                Some(tag_id) => tag_id,
+                None => self.insert(
+                    "INSERT INTO tags (name, autocomplete) VALUES (:name, 1)",
+                    &[(":name", Value::String(tag.to_string()))],
+                )?,
+            };
+            self.insert(
+                "INSERT INTO fact_tags (fact_id, tag_id) VALUES (:fact_id, :tag_id)",
+                &[
+                    (":fact_id", Value::Integer(fact_id)),
+                    (":tag_id", Value::Integer(tag_id)),
+                ],
+            )?;
+        }
+        Ok(())
+    }
+
+    /// Saves activity, category, times and description of the fact; tags are kept as they are
+    pub fn update_fact(&self, fact: &HamsterFact) -> Result<(), String> {
+        self.ensure_known_schema()?;
+        self.in_transaction(|| {
+            let category_id = self.find_or_create_category(&fact.category)?;
+            let activity_id = self.find_or_create_activity(&fact.activity, category_id)?;
+            self.query_id(
+                "UPDATE facts
+                SET activity_id = :activity_id, start_time = :start_time,
+                    end_time = :end_time, description = :description
+                WHERE id = :id",
+                &[
+                    (":activity_id", Value::Integer(activity_id)),
+                    (":start_time", Value::String(format_time(fact.start_time))),
+                    (
+                        ":end_time",
+                        match fact.end_time {
+                            Some(end_time) => Value::String(format_time(end_time)),
+                            None => Value::Null,
+                        },
+                    ),
+                    (":description", Value::String(fact.description.clone())),
+                    (":id", Value::Integer(fact.id)),
+                ],
+            )?;
+            Ok(())
+        })
+    }
+
+    /// Ends the running fact, if there is one, giving its id
+    pub fn stop_running(&self, end_time: DateTime<Local>) -> Result<Option<i64>, String> {
+        self.ensure_known_schema()?;
+        let running = self.query_id(
+            "SELECT id FROM facts WHERE end_time IS NULL ORDER BY start_time DESC",
+            &[],
+        )?;
+        let Some(fact_id) = running else {
+            return Ok(None);
+        };
+        self.query_id(
+            "UPDATE facts SET end_time = :end_time WHERE id = :id",
+            &[
+                (":end_time", Value::String(format_time(end_time))),
+                (":id", Value::Integer(fact_id)),
+            ],
+        )?;
+        Ok(Some(fact_id))
+    }
+
+    /// Runs the writes in a transaction, rolled back if any of them fails - a half
+    /// added fact would leave stray activities and categories in Hamster
+    fn in_transaction<T>(&self, write: impl FnOnce() -> Result<T, String>) -> Result<T, String> {
+        self.connection
+            .execute("BEGIN")
+            .map_err(|err| format!("couldn't start a transaction: {err}"))?;
+        let written = write().and_then(|value| {
+            self.connection
+                .execute("COMMIT")
+                .map_err(|err| format!("couldn't commit: {err}"))?;
+            Ok(value)
+        });
+        if written.is_err() {
+            let _ = self.connection.execute("ROLLBACK");
+        }
+        written
+    }
+
+    /// Writing to a schema we don't know could break the database for Hamster itself
+    fn ensure_known_schema(&self) -> Result<(), String> {
+        let version = self
+            .query_id("SELECT version FROM version", &[])
+            .map_err(|err| format!("couldn't get Hamster schema version: {err}"))?;
+        match version {
+            Some(KNOWN_SCHEMA_VERSION) => Ok(()),
+            Some(version) => Err(format!(
+                "Hamster schema version is {version}, only {KNOWN_SCHEMA_VERSION} is supported \
+                for writing - refusing to touch the database"
+            )),
+            None => Err(String::from(
+                "Hamster schema version is unknown - refusing to touch the database",
+            )),
+        }
+    }
+
+    fn find_or_create_category(&self, name: &str) -> Result<i64, String> {
+        let found = self.query_id(
+            "SELECT id FROM categories WHERE name = :name",
+            &[(":name", Value::String(name.to_string()))],
+        )?;
+        match found {
+            Some(category_id) => Ok(category_id),
+            None => self.insert(
+                "INSERT INTO categories (name, search_name) VALUES (:name, :search_name)",
+                &[
+                    (":name", Value::String(name.to_string())),
+                    (":search_name", Value::String(name.to_lowercase())),
+                ],
+            ),
+        }
+    }
+
+    fn find_or_create_activity(&self, name: &str, category_id: i64) -> Result<i64, String> {
+        let found = self.query_id(
+            "SELECT id FROM activities
+            WHERE name = :name AND category_id = :category_id AND NOT deleted",
+            &[
+                (":name", Value::String(name.to_string())),
+                (":category_id", Value::Integer(category_id)),
+            ],
+        )?;
+        match found {
+            Some(activity_id) => Ok(activity_id),
+            None => self.insert(
+                "INSERT INTO activities (name, deleted, category_id, search_name)
+                VALUES (:name, 0, :category_id, :search_name)",
+                &[
+                    (":name", Value::String(name.to_string())),
+                    (":category_id", Value::Integer(category_id)),
+                    (":search_name", Value::String(name.to_lowercase())),
+                ],
+            ),
+        }
+    }
+
+    /// Runs a query returning a single id column, gives the first row's one
+    fn query_id(&self, query: &str, params: &[(&str, Value)]) -> Result<Option<i64>, String> {
+        let mut statement = self
+            .connection
+            .prepare(query)
+            .map_err(|err| format!("couldn't prepare query: {err}"))?;
+        statement
+            .bind(params)
+            .map_err(|err| format!("couldn't bind query params: {err}"))?;
+        match statement.next() {
+            Ok(State::Row) => Ok(Some(statement.read::<i64, _>(0).unwrap())),
+            Ok(State::Done) => Ok(None),
+            Err(err) => Err(format!("query failed: {err}")),
+        }
+    }
+
+    /// Runs an insert, gives id of the new row
+    fn insert(&self, query: &str, params: &[(&str, Value)]) -> Result<i64, String> {
+        self.query_id(query, params)?;
+        self.query_id("SELECT last_insert_rowid()", &[])
+            .map(|row_id| row_id.unwrap())
+    }
+}
+
+/// Given path or the default one, in the home directory
+pub fn db_path(db_path: Option<String>) -> Result<String, String> {
+    match db_path {
+        Some(db_path) => Ok(db_path),
+        None => match std::env::var("HOME") {
+            Ok(home) => Ok(String::from(
+                Path::new(home.as_str())
+                    .join(".local/share/hamster/hamster.db")
+                    .to_str()
+                    .unwrap(),
+            )),
+            Err(_) => Err(String::from(
+                "Hamster database path wasn't supplied, $HOME is not set - I give up",
+            )),
+        },
+    }
+}
+
+/// Time in the format Hamster stores it
+fn format_time(time: DateTime<Local>) -> String {
+    time.format("%Y-%m-%d %H:%M:%S").to_string()
+}
+
+fn parse_time(raw: &str) -> DateTime<Local> {
+    NaiveDateTime::parse_from_str(raw, "%Y-%m-%d %H:%M:%S")
+        .unwrap()
+        .and_local_timezone(Local)
+        .unwrap()
+}
+
+fn read_fact(statement: &sqlite::Statement) -> HamsterFact {
+    HamsterFact {
+        id: statement.read::<i64, _>("fact_id").unwrap(),
+        description: statement.read::<String, _>("description").unwrap(),
+        category: statement.read::<String, _>("category_name").unwrap(),
+        activity: statement.read::<String, _>("activity_name").unwrap(),
+        tags: statement
+            .read::<Option<String>, _>("tag_names")
+            .unwrap()
+            .map(|tag_names| tag_names.lines().map(String::from).collect())
+            .unwrap_or_default(),
+
+        start_time: parse_time(&statement.read::<String, _>("start_time").unwrap()),
+        end_time: statement
+            .read::<Option<String>, _>("end_time")
+            .unwrap()
+            .map(|end_time| parse_time(&end_time)),
+    }
 }
@@ -0,0 +1,756 @@
+use chrono::{
+    DateTime, Days, Local, NaiveDate, NaiveDateTime, NaiveTime, TimeDelta, TimeZone, Utc,
+};
+use std::collections::HashMap;
+use std::time::Duration;
+
+use crate::aggregation::AggregationOptions;
+use crate::backend::{BookedTime, EverhourBackend, TimeTrackerBackend};
+use crate::config::Config;
+use crate::date_range::DateRange;
+use crate::diff;
+use crate::hamster::{HamsterData, HamsterFact};
+use crate::utils::{parse_activity, parse_csv, DurationFormatting, RunMode};
+
+/// Fact read from a file, to be added to Hamster
+#[derive(Debug, PartialEq)]
+pub struct ImportedFact {
+    pub start_time: DateTime<Local>,
+    pub end_time: DateTime<Local>,
+    pub activity: String,
+    pub category: String,
+    pub description: String,
+    pub tags: Vec<String>,
+}
+
+/// Activity and category for facts the file doesn't tell them for
+pub struct ImportDefaults {
+    pub activity: String,
+    pub category: String,
+}
+
+/// Reads CSV written by `ham-cli export --format csv`
+pub fn parse_ham_csv(text: &str, defaults: &ImportDefaults) -> Result<Vec<ImportedFact>, String> {
+    let (columns, rows) = csv_rows(text)?;
+    let mut facts = vec![];
+    for (row_number, row) in rows {
+        let field = |name: &str| field(&columns, &row, name);
+        let parse_time = |name: &str| {
+            DateTime::parse_from_rfc3339(field(name))
+                .map(|time| time.with_timezone(&Local))
+                .map_err(|_| format!("row {row_number}: bad {name} '{}'", field(name)))
+        };
+        if field("end_time").is_empty() {
+            return Err(format!(
+                "row {row_number}: fact has no end - it was running when exported"
+            ));
+        }
+        facts.push(ImportedFact {
+            start_time: parse_time("start_time")?,
+            end_time: parse_time("end_time")?,
+            activity: non_empty(field("activity"), &defaults.activity),
+            category: non_empty(field("category"), &defaults.category),
+            description: field("description").to_string(),
+            tags: field("tags")
+                .split(',')
+                .map(str::trim)
+                .filter(|tag| !tag.is_empty())
+                .map(String::from)
+                .collect(),
+        });
+    }
+    Ok(facts)
+}
+
+/// Reads CSV exported from Toggl Track or Clockify: project becomes the category,
+/// task (if any) the activity and description stays description
+pub fn parse_tool_csv(text: &str, defaults: &ImportDefaults) -> Result<Vec<ImportedFact>, String> {
+    let (columns, rows) = csv_rows(text)?;
+    let mut facts = vec![];
+    for (row_number, row) in rows {
+        let field = |name: &str| field(&columns, &row, name);
+        let start_time = tool_time(field("start date"), field("start time"))
+            .ok_or(format!("row {row_number}: bad start date or time"))?;
+        let end_time = if !field("end date").is_empty() && !field("end time").is_empty() {
+            tool_time(field("end date"), field("end time"))
+                .ok_or(format!("row {row_number}: bad end date or time"))?
+        } else {
+            let duration = tool_duration(field("duration"))
+                .ok_or(format!("row {row_number}: neither end time, nor duration"))?;
+            start_time + TimeDelta::from_std(duration).unwrap()
+        };
+        facts.push(ImportedFact {
+            start_time,
+            end_time,
+            activity: non_empty(field("task"), &defaults.activity),
+            category: non_empty(field("project"), &defaults.category),
+            description: field("description").to_string(),
+            tags: vec![],
+        });
+    }
+    Ok(facts)
+}
+
+/// Reads VEVENTs of an iCalendar file. Summary in Hamster's "activity@category"
+/// form gives both, otherwise it's the activity. Times with TZID are taken as local.
+/// Events without start and end times (all-day ones too) are skipped, given
+/// back as problems to report.
+pub fn parse_ical(text: &str, defaults: &ImportDefaults) -> (Vec<ImportedFact>, Vec<String>) {
+    // continuation lines start with a space or a tab
+    let unfolded = text
+        .replace("\r\n", "\n")
+        .replace("\n ", "")
+        .replace("\n\t", "");
+    let mut facts = vec![];
+    let mut skipped = vec![];
+    let mut event: Option<HashMap<String, String>> = None;
+    for line in unfolded.lines() {
+        match line {
+            "BEGIN:VEVENT" => event = Some(HashMap::new()),
+            "END:VEVENT" => {
+                let Some(properties) = event.take() else {
+                    continue;
+                };
+                let uid = properties.get("UID").cloned().unwrap_or_default();
+                let time = |name: &str| {
+                    properties
+                        .get(name)
+                        .and_then(|value| ical_time(value))
+                        .ok_or(format!(
+                            "event {uid}: missing or bad {name} '{}'",
+                            properties.get(name).cloned().unwrap_or_default()
+                        ))
+                };
+                let (start_time, end_time) = match (time("DTSTART"), time("DTEND")) {
+                    (Ok(start_time), Ok(end_time)) => (start_time, end_time),
+                    (Err(problem), _) | (_, Err(problem)) => {
+                        skipped.push(problem);
+                        continue;
+                    }
+                };
+                let summary = properties
+                    .get("SUMMARY")
+                    .map(|summary| ical_unescape(summary))
+                    .unwrap_or_default();
+                let (activity, category) = parse_activity(&summary).unwrap_or((
+                    non_empty(&summary, &defaults.activity),
+                    defaults.category.clone(),
+                ));
+                facts.push(ImportedFact {
+                    start_time,
+                    end_time,
+                    activity,
+                    category,
+                    description: properties
+                        .get("DESCRIPTION")
+                        .map(|description| ical_unescape(description))
+                        .unwrap_or_default(),
+                    tags: vec![],
+                });
+            }
+            _ => {
+                let Some(properties) = event.as_mut() else {
+                    continue;
+                };
+                let Some((name, value)) = line.split_once(':') else {
+                    continue;
+                };
+                // parameters like ";TZID=Europe/Berlin" are dropped
+                let name = name.split(';').next().unwrap_or_default();
+                properties.insert(name.to_uppercase(), value.to_string());
+            }
+        }
+    }
+    (facts, skipped)
+}
+
+/// Id of an existing fact the imported one overlaps with, running facts end now
+pub fn find_overlap(
+    fact: &ImportedFact,
+    existing_facts: &[HamsterFact],
+    now: DateTime<Local>,
+) -> Option<i64> {
+    existing_facts
+        .iter()
+        .find(|existing| {
+            existing.start_time < fact.end_time
+                && fact.start_time < existing.end_time.unwrap_or(now)
+        })
+        .map(|existing| existing.id)
+}
+
+/// Row number in the file, header being row 1, and fields of the row
+type NumberedRow = (usize, Vec<String>);
+
+/// Lowercased header names and numbered rows, blank lines skipped
+fn csv_rows(text: &str) -> Result<(Vec<String>, Vec<NumberedRow>), String> {
+    let mut records = parse_csv(text.trim_start_matches('\u{feff}')).into_iter();
+    let columns: Vec<String> = records
+        .next()
+        .ok_or("file is empty")?
+        .iter()
+        .map(|column| column.trim().to_lowercase())
+        .collect();
+    let rows = records
+        .enumerate()
+        .filter(|(_, row)| row.iter().any(|field| !field.trim().is_empty()))
+        .map(|(index, row)| (index + 2, row))
+        .collect();
+    Ok((columns, rows))
+}
+
+/// Value of the column, empty if there's no such column
+fn field<'a>(columns: &[String], row: &'a [String], name: &str) -> &'a str {
+    columns
+        .iter()
+        .position(|column| column == name)
+        .and_then(|index| row.get(index))
+        .map(|value| value.trim())
+        .unwrap_or_default()
+}
+
+fn non_empty(value: &str, default: &str) -> String {
+    if value.trim().is_empty() {
+        default.to_string()
+    } else {
+        value.trim().to_string()
+    }
+}
+
+/// Date and time as Toggl and Clockify write them, depending on user settings
+fn tool_time(date: &str, time: &str) -> Option<DateTime<Local>> {
+    let date = ["%Y-%m-%d", "%m/%d/%Y", "%d.%m.%Y", "%d/%m/%Y"]
+        .iter()
+        .find_map(|format| NaiveDate::parse_from_str(date, format).ok())?;
+    let time = ["%H:%M:%S", "%H:%M", "%I:%M:%S %p", "%I:%M %p"]
+        .iter()
+        .find_map(|format| NaiveTime::parse_from_str(time, format).ok())?;
+    date.and_time(time).and_local_timezone(Local).single()
+}
+
+/// "HH:MM:SS" or "HH:MM"
+fn tool_duration(duration: &str) -> Option<Duration> {
+    let parts: Vec<u64> = duration
+        .split(':')
+        .map(|part| part.parse().ok())
+        .collect::<Option<Vec<u64>>>()?;
+    match parts[..] {
+        [hours, minutes, seconds] => Some(Duration::new(hours * 3600 + minutes * 60 + seconds, 0)),
+        [hours, minutes] => Some(Duration::new(hours * 3600 + minutes * 60, 0)),
+        _ => None,
+    }
+}
+
+/// "20240513T090000Z" is UTC, "20240513T090000" is local
+fn ical_time(value: &str) -> Option<DateTime<Local>> {
+    match value.strip_suffix('Z') {
+        Some(utc) => NaiveDateTime::parse_from_str(utc, "%Y%m%dT%H%M%S")
+            .ok()
+            .map(|time| Utc.from_utc_datetime(&time).with_timezone(&Local)),
+        None => NaiveDateTime::parse_from_str(value, "%Y%m%dT%H%M%S")
+            .ok()?
+            .and_local_timezone(Local)
+            .single(),
+    }
+}
+
+fn ical_unescape(text: &str) -> String {
+    let mut unescaped = String::new();
+    let mut characters = text.chars();
+    while let Some(character) = characters.next() {
+        if character != '\\' {
+            unescaped.push(character);
+            continue;
+        }
+        match characters.next() {
+            Some('n') | Some('N') => unescaped.push('\n'),
+            Some(escaped) => unescaped.push(escaped),
+            None => {}
+        }
+    }
+    unescaped
+}
+
+/// Description in the form `HamsterEnrichedData::task` understands: task link
+/// followed by comments as a list
+pub fn fact_description(title: &str, asana_task_id: &str, comments: &[String]) -> String {
+    let mut description = format!(
+        "[{}](https://app.asana.com/0/0/{asana_task_id}/f)",
+        title.replace('[', "\\[").replace(']', "\\]")
+    );
+    for comment in comments {
+        description.push_str(&format!("\n- {comment}"));
+    }
+    description
+}
+
+/// Lays facts of given durations one after another, from `start` or from the end
+/// of the latest fact of the day, whichever is later. Facts that wouldn't end
+/// within the day are not placed.
+pub fn place_facts(
+    day: NaiveDate,
+    start: NaiveTime,
+    existing_facts: &[HamsterFact],
+    durations: &[Duration],
+    now: DateTime<Local>,
+) -> Vec<Option<(DateTime<Local>, DateTime<Local>)>> {
+    let day_end = day
+        .checked_add_days(Days::new(1))
+        .unwrap()
+        .and_time(NaiveTime::MIN)
+        .and_local_timezone(Local)
+        .unwrap();
+    let mut next_start = existing_facts
+        .iter()
+        .filter(|fact| fact.start_time.date_naive() == day)
+        .map(|fact| fact.end_time.unwrap_or(now))
+        .fold(
+            day.and_time(start).and_local_timezone(Local).unwrap(),
+            |latest, end_time| latest.max(end_time),
+        );
+
+    durations
+        .iter()
+        .map(|duration| {
+            let end_time = next_start + TimeDelta::from_std(*duration).unwrap();
+            if end_time > day_end {
+                return None;
+            }
+            let placed = (next_start, end_time);
+            next_start = end_time;
+            Some(placed)
+        })
+        .collect()
+}
+
+/// Adds the facts, skipping ones that end before they start or overlap existing
+/// facts or each other. Gives back how many were added and how many skipped.
+pub fn import_facts(
+    hamster_db: Option<String>,
+    mut facts: Vec<ImportedFact>,
+    run_mode: RunMode,
+) -> Result<(usize, usize), String> {
+    facts.sort_by_key(|fact| fact.start_time);
+    let (Some(first), Some(last)) = (facts.first(), facts.iter().map(|fact| fact.end_time).max())
+    else {
+        return Ok((0, 0));
+    };
+
+    let hamster_data = HamsterData::open(hamster_db)?;
+    // facts of the day before may run into the first imported entry
+    let existing_facts = hamster_data.get_facts(
+        DateRange::new(
+            first.start_time.date_naive().pred_opt().unwrap(),
+            last.date_naive().max(first.start_time.date_naive()),
+        )
+        .unwrap(),
+    );
+    let now = Local::now();
+    let mut added: Vec<&ImportedFact> = vec![];
+    let mut skipped = 0;
+    for fact in facts.iter() {
+        let data_msg = format!(
+            "{} - {} {}@{} {}",
+            fact.start_time.format("%Y-%m-%d %H:%M"),
+            fact.end_time.format("%Y-%m-%d %H:%M"),
+            fact.activity,
+            fact.category,
+            fact.description.lines().next().unwrap_or_default()
+        );
+        let problem = if fact.end_time <= fact.start_time {
+            Some(String::from("ends before it starts"))
+        } else if let Some(fact_id) = find_overlap(fact, &existing_facts, now) {
+            Some(format!("overlaps with fact {fact_id}"))
+        } else if added
+            .iter()
+            .any(|other| other.start_time < fact.end_time && fact.start_time < other.end_time)
+        {
+            Some(String::from("overlaps with another imported entry"))
+        } else {
+            None
+        };
+        if let Some(problem) = problem {
+            println!("skipping, {problem} - {data_msg}");
+            skipped += 1;
+            continue;
+        }
+
+        match run_mode {
+            RunMode::DryRun => println!("would add fact - {data_msg}"),
+            RunMode::Normal => {
+                let fact_id = hamster_data.add_fact(
+                    &fact.activity,
+                    &fact.category,
+                    fact.start_time,
+                    Some(fact.end_time),
+                    &fact.description,
+                    &fact.tags,
+                )?;
+                println!("added fact {fact_id} - {data_msg}");
+            }
+        }
+        added.push(fact);
+    }
+    Ok((added.len(), skipped))
+}
+
+/// Where facts created from Everhour records go
+pub struct ImportOptions {
+    pub category: String,
+    pub activity: String,
+    /// facts of a day are laid out from this time, unless there are later facts
+    pub start: NaiveTime,
+}
+
+/// Creates facts for Everhour records that have no counterpart in Hamster.
+/// Records that are in Hamster already, but with different time, are only reported.
+pub async fn import_from_everhour(
+    hamster_db: Option<String>,
+    backend: &EverhourBackend,
+    range: DateRange,
+    config: &Config,
+    import_options: &ImportOptions,
+    run_mode: RunMode,
+) -> Result<(), String> {
+    let me = backend.current_user().await?;
+    let time_records = backend.time_records(&me, range).await?;
+    let hamster_data = HamsterData::open(hamster_db.clone())?;
+    let options = AggregationOptions {
+        category: Some(import_options.category.clone()),
+        ..AggregationOptions::default()
+    };
+
+    for day in range.days() {
+        let day_records: Vec<&BookedTime> = time_records
+            .iter()
+            .filter(|time_record| time_record.date == day)
+            .collect();
+        if day_records.is_empty() {
+            continue;
+        }
+
+        let everhour_bookings: Vec<diff::Booking> = day_records
+            .iter()
+            .filter_map(|time_record| {
+                Some(diff::Booking {
+                    day,
+                    task_id: time_record.task_id.clone()?,
+                    title: time_record.task_name.clone().unwrap_or("-".to_string()),
+                    duration: time_record.time,
+                })
+            })
+            .collect();
+        let hamster_bookings: Vec<diff::Booking> =
+            diff::hamster_bookings(hamster_db.clone(), DateRange::day(day), config, &options)
+                .into_iter()
+                .filter(|booking| booking.task_id != "-")
+                .collect();
+
+        let mut to_import = vec![];
+        for row in diff::compare(hamster_bookings, everhour_bookings) {
+            let everhour = row.everhour.unwrap_or_default().as_hhmm();
+            match row.status() {
+                diff::DiffStatus::Extra => match row.task_id.strip_prefix("as:") {
+                    Some(asana_task_id) => {
+                        let comments: Vec<String> = day_records
+                            .iter()
+                            .filter(|time_record| {
+                                time_record.task_id.as_ref() == Some(&row.task_id)
+                            })
+                            .filter_map(|time_record| time_record.comment.clone())
+                            .flat_map(|comment| {
+                                comment.lines().map(String::from).collect::<Vec<_>>()
+                            })
+                            .filter(|line| !line.trim().is_empty())
+                            .collect();
+                        to_import
+                            .push((fact_description(&row.title, asana_task_id, &comments), row));
+                    }
+                    None => println!(
+                        "{day}: skipping {} ({}, {everhour}) - not an Asana task",
+                        row.task_id, row.title
+                    ),
+                },
+                diff::DiffStatus::Mismatch => println!(
+                    "{day}: conflict on {} ({}) - {} in Hamster, {everhour} in Everhour",
+                    row.task_id,
+                    row.title,
+                    row.hamster.unwrap_or_default().as_hhmm()
+                ),
+                diff::DiffStatus::Match | diff::DiffStatus::Missing => {}
+            }
+        }
+        let skipped = day_records
+            .iter()
+            .filter(|time_record| time_record.task_id.is_none())
+            .count();
+        if skipped > 0 {
+            println!("{day}: skipping {skipped} record(s) without a task");
+        }
+
+        let durations: Vec<Duration> = to_import
+            .iter()
+            .map(|(_, row)| row.everhour.unwrap())
+            .collect();
+        let placements = place_facts(
+            day,
+            import_options.start,
+            &hamster_data.get_facts(DateRange::day(day)),
+            &durations,
+            Local::now(),
+        );
+        for ((description, row), placement) in to_import.into_iter().zip(placements) {
+            let Some((start_time, end_time)) = placement else {
+                println!(
+                    "{day}: conflict on {} ({}) - {} doesn't fit into the day",
+                    row.task_id,
+                    row.title,
+                    row.everhour.unwrap().as_hhmm()
+                );
+                continue;
+            };
+            let data_msg = format!(
+                "{day}: {}-{} {} ({})",
+                start_time.format("%H:%M"),
+                end_time.format("%H:%M"),
+                row.task_id,
+                row.title
+            );
+            match run_mode {
+                RunMode::DryRun => println!("would add fact - {data_msg}"),
+                RunMode::Normal => {
+                    let fact_id = hamster_data.add_fact(
+                        &import_options.activity,
+                        &import_options.category,
+                        start_time,
+                        Some(end_time),
+                        &description,
+                        &[],
+                    )?;
+                    println!("added fact {fact_id} - {data_msg}");
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{DateTime, Local, NaiveDate, NaiveTime};
+    use std::time::Duration;
+
+    use crate::enrichment::HamsterEnrichedData;
+    use crate::hamster::HamsterFact;
+
+    use crate::export::{to_csv, to_ical};
+
+    use super::{
+        fact_description, find_overlap, parse_ham_csv, parse_ical, parse_tool_csv, place_facts,
+        ImportDefaults,
+    };
+
+    fn defaults() -> ImportDefaults {
+        ImportDefaults {
+            activity: String::from("imported"),
+            category: String::from("Imported"),
+        }
+    }
+
+    fn at(hour: u32, minute: u32) -> DateTime<Local> {
+        NaiveDate::from_ymd_opt(2024, 5, 13)
+            .unwrap()
+            .and_hms_opt(hour, minute, 0)
+            .unwrap()
+            .and_local_timezone(Local)
+            .unwrap()
+    }
+
+    fn fact(description: String, end_time: Option<DateTime<Local>>) -> HamsterFact {
+        HamsterFact {
+            id: 1,
+            start_time: at(9, 0),
+            end_time,
+            description,
+            activity: String::from("everhour"),
+            category: String::from("Work"),
+            tags: vec![],
+        }
+    }
+
+    #[test]
+    fn description_parsed_back() {
+        let fact = fact(
+            fact_description(
+                "Fix [urgent] bug",
+                "1200000000000001",
+                &[String::from("found it"), String::from("fixed it")],
+            ),
+            None,
+        );
+
+        let task_link = fact.task().unwrap();
+        assert_eq!(task_link.task_id, Some(String::from("1200000000000001")));
+        assert_eq!(task_link.link_title, "Fix [urgent] bug");
+        assert_eq!(fact.comments(), ["found it", "fixed it"]);
+    }
+
+    #[test]
+    fn facts_placed_after_existing_ones() {
+        let day = NaiveDate::from_ymd_opt(2024, 5, 13).unwrap();
+        let existing = [fact(String::new(), Some(at(11, 30)))];
+
+        let placed = place_facts(
+            day,
+            NaiveTime::from_hms_opt(9, 0, 0).unwrap(),
+            &existing,
+            &[Duration::new(3600, 0), Duration::new(24 * 3600, 0)],
+            at(20, 0),
+        );
+
+        assert_eq!(placed, [Some((at(11, 30), at(12, 30))), None]);
+    }
+
+    #[test]
+    fn facts_placed_from_start_on_empty_day() {
+        let day = NaiveDate::from_ymd_opt(2024, 5, 13).unwrap();
+
+        let placed = place_facts(
+            day,
+            NaiveTime::from_hms_opt(9, 0, 0).unwrap(),
+            &[],
+            &[Duration::new(1800, 0), Duration::new(900, 0)],
+            at(20, 0),
+        );
+
+        assert_eq!(
+            placed,
+            [Some((at(9, 0), at(9, 30))), Some((at(9, 30), at(9, 45)))]
+        );
+    }
+
+    #[test]
+    fn ham_csv_roundtrips() {
+        let mut exported = fact(
+            String::from("[A, B](https://example.com/0/1/f)\n- done"),
+            Some(at(10, 0)),
+        );
+        exported.activity = String::from("coding");
+        exported.tags = vec![String::from("backend"), String::from("urgent")];
+
+        let imported = parse_ham_csv(&to_csv(&[exported], at(12, 0)), &defaults()).unwrap();
+
+        assert_eq!(imported.len(), 1);
+        assert_eq!(imported[0].start_time, at(9, 0));
+        assert_eq!(imported[0].end_time, at(10, 0));
+        assert_eq!(imported[0].activity, "coding");
+        assert_eq!(imported[0].category, "Work");
+        assert_eq!(
+            imported[0].description,
+            "[A, B](https://example.com/0/1/f)\n- done"
+        );
+        assert_eq!(imported[0].tags, ["backend", "urgent"]);
+    }
+
+    #[test]
+    fn ical_roundtrips() {
+        let exported = fact(
+            "long description, ".repeat(10) + "\n- with; specials",
+            Some(at(10, 0)),
+        );
+
+        let (imported, skipped) = parse_ical(&to_ical(&[exported], at(12, 0)), &defaults());
+
+        assert!(skipped.is_empty());
+        assert_eq!(imported.len(), 1);
+        assert_eq!(imported[0].start_time, at(9, 0));
+        assert_eq!(imported[0].end_time, at(10, 0));
+        assert_eq!(imported[0].activity, "everhour");
+        assert_eq!(imported[0].category, "Work");
+        assert_eq!(
+            imported[0].description,
+            "long description, ".repeat(10) + "\n- with; specials"
+        );
+    }
+
+    #[test]
+    fn linked_fact_keeps_activity_through_ical() {
+        let mut exported = fact(
+            String::from("[Fix parser](https://app.asana.com/0/0/111/f)\n- done"),
+            Some(at(10, 0)),
+        );
+        exported.activity = String::from("coding");
+
+        let (imported, _) = parse_ical(&to_ical(&[exported], at(12, 0)), &defaults());
+
+        assert_eq!(imported[0].activity, "coding");
+        assert_eq!(imported[0].category, "Work");
+        assert_eq!(
+            imported[0].description,
+            "[Fix parser](https://app.asana.com/0/0/111/f)\n- done"
+        );
+    }
+
+    #[test]
+    fn all_day_ical_events_skipped() {
+        let calendar = "BEGIN:VCALENDAR\n\
+            BEGIN:VEVENT\nUID:holiday\nDTSTART;VALUE=DATE:20240513\nDTEND;VALUE=DATE:20240514\nSUMMARY:Holiday\nEND:VEVENT\n\
+            BEGIN:VEVENT\nUID:standup\nDTSTART:20240513T090000\nDTEND:20240513T091500\nSUMMARY:Stand-up\nEND:VEVENT\n\
+            END:VCALENDAR\n";
+
+        let (imported, skipped) = parse_ical(calendar, &defaults());
+
+        assert_eq!(imported.len(), 1);
+        assert_eq!(imported[0].activity, "Stand-up");
+        assert_eq!(imported[0].start_time, at(9, 0));
+        assert_eq!(
+            skipped,
+            ["event holiday: missing or bad DTSTART '20240513'"]
+        );
+    }
+
+    #[test]
+    fn toggl_and_clockify_csv_parsed() {
+        let toggl = "User,Email,Client,Project,Task,Description,Billable,Start date,Start time,End date,End time,Duration,Tags\n\
+            Me,me@example.com,,Backend,,Fix parser,No,2024-05-13,09:00:00,2024-05-13,10:30:00,01:30:00,\n";
+        let clockify = "\u{feff}Project,Client,Description,Task,User,Email,Tags,Billable,Start Date,Start Time,End Date,End Time,Duration (h)\n\
+            ,,Stand-up,Meetings,Me,me@example.com,,No,05/13/2024,11:00:00 AM,,,\n";
+        let clockify_with_duration = clockify
+            .replace("Duration (h)", "Duration")
+            .replace("AM,,,", "AM,,,00:15:00");
+
+        let toggl = parse_tool_csv(toggl, &defaults()).unwrap();
+        assert_eq!(toggl[0].start_time, at(9, 0));
+        assert_eq!(toggl[0].end_time, at(10, 30));
+        assert_eq!(toggl[0].activity, "imported");
+        assert_eq!(toggl[0].category, "Backend");
+        assert_eq!(toggl[0].description, "Fix parser");
+
+        assert!(parse_tool_csv(clockify, &defaults()).is_err());
+        let clockify = parse_tool_csv(&clockify_with_duration, &defaults()).unwrap();
+        assert_eq!(clockify[0].start_time, at(11, 0));
+        assert_eq!(clockify[0].end_time, at(11, 15));
+        assert_eq!(clockify[0].activity, "Meetings");
+        assert_eq!(clockify[0].category, "Imported");
+    }
+
+    #[test]
+    fn overlaps_found() {
+        let existing = [fact(String::new(), Some(at(10, 0)))];
+        let mut imported = parse_tool_csv(
+            "Start date,Start time,Duration\n2024-05-13,09:30,01:00\n2024-05-13,10:00,01:00\n",
+            &defaults(),
+        )
+        .unwrap()
+        .into_iter();
+
+        assert_eq!(
+            find_overlap(&imported.next().unwrap(), &existing, at(20, 0)),
+            Some(1)
+        );
+        assert_eq!(
+            find_overlap(&imported.next().unwrap(), &existing, at(20, 0)),
+            None
+        );
+    }
+}
@@ -0,0 +1,25 @@
+//! Reading Hamster time tracker data and turning it into tasks, for ham-cli
+//! and other tools that want the same view of the database.
+
+pub mod aggregation;
+pub mod auth;
+pub mod backend;
+pub mod balance;
+pub mod checks;
+pub mod config;
+pub mod date_range;
+pub mod diff;
+pub mod edit;
+pub mod enrichment;
+pub mod everhour;
+pub mod export;
+pub mod hamster;
+pub mod import;
+pub mod lint;
+pub mod relink;
+pub mod report;
+pub mod state;
+pub mod sync;
+pub mod targets;
+pub mod utils;
+pub mod watch;
@@ -0,0 +1,269 @@
+use markdown::mdast::Node;
+use markdown::ParseOptions;
+use std::collections::HashMap;
+use std::fmt;
+
+use crate::aggregation::resolve_task;
+use crate::config::Config;
+use crate::enrichment::HamsterEnrichedData;
+use crate::hamster::HamsterFact;
+use crate::utils::MarkdownProcessing;
+
+pub enum LintIssue {
+    /// nothing to book the fact to - neither a link, nor a task or project mapping
+    NoLink,
+    /// link that doesn't look like a task link
+    UnknownLink {
+        href: String,
+    },
+    /// same task is titled differently in another fact
+    TitleMismatch {
+        task_id: String,
+        title: String,
+        other_title: String,
+        other_fact_id: i64,
+    },
+    /// comment list is there, but has nothing in it
+    NoComments,
+    ParseError {
+        message: String,
+    },
+}
+
+impl fmt::Display for LintIssue {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            LintIssue::NoLink => write!(f, "no task link and no mapping"),
+            LintIssue::UnknownLink { href } => {
+                write!(f, "link {href} doesn't match any known task pattern")
+            }
+            LintIssue::TitleMismatch {
+                task_id,
+                title,
+                other_title,
+                other_fact_id,
+            } => write!(
+                f,
+                "task {task_id} is titled '{title}' here, but '{other_title}' in fact {other_fact_id}"
+            ),
+            LintIssue::NoComments => write!(f, "no comments"),
+            LintIssue::ParseError { message } => {
+                write!(f, "description couldn't be parsed: {message}")
+            }
+        }
+    }
+}
+
+/// Finds mistakes in fact descriptions that would make sync fail or go wrong
+pub fn lint_facts(facts: &[HamsterFact], config: &Config) -> Vec<(i64, LintIssue)> {
+    let mut issues = vec![];
+    // first title seen for every task id, along with the fact it was seen in
+    let mut titles: HashMap<String, (String, i64)> = HashMap::new();
+
+    for fact in facts {
+        let markdown_root = match markdown::to_mdast(&fact.description, &ParseOptions::default()) {
+            Ok(markdown_root) => markdown_root,
+            Err(message) => {
+                issues.push((
+                    fact.id,
+                    LintIssue::ParseError {
+                        message: message.to_string(),
+                    },
+                ));
+                continue;
+            }
+        };
+
+        // links in comments, like PRs or docs, aren't booked to
+        let task_links = fact.booking_links();
+        // mappings and project rules book facts without links, the way sync does
+        if task_links.is_empty() && resolve_task(fact, config).is_err() {
+            issues.push((fact.id, LintIssue::NoLink));
+        }
+
+        for task_link in task_links.iter() {
+            let Some(task_id) = &task_link.task_id else {
+                issues.push((
+                    fact.id,
+                    LintIssue::UnknownLink {
+                        href: task_link.href.clone(),
+                    },
+                ));
+                continue;
+            };
+            match titles.get(task_id) {
+                Some((other_title, other_fact_id)) => {
+                    if other_title.trim() != task_link.link_title.trim() {
+                        issues.push((
+                            fact.id,
+                            LintIssue::TitleMismatch {
+                                task_id: task_id.clone(),
+                                title: task_link.link_title.clone(),
+                                other_title: other_title.clone(),
+                                other_fact_id: *other_fact_id,
+                            },
+                        ));
+                    }
+                }
+                None => {
+                    titles.insert(task_id.clone(), (task_link.link_title.clone(), fact.id));
+                }
+            }
+        }
+
+        if has_empty_comment_list(&markdown_root) {
+            issues.push((fact.id, LintIssue::NoComments));
+        }
+    }
+    issues
+}
+
+/// Whether there are comment lists, but no text in any of them
+fn has_empty_comment_list(markdown_root: &Node) -> bool {
+    let lists: Vec<&Node> = markdown_root
+        .children()
+        .unwrap()
+        .iter()
+        .filter(|node| matches!(node, Node::List(_)))
+        .collect();
+    !lists.is_empty()
+        && lists
+            .iter()
+            .flat_map(|node| node.texts())
+            .all(|text| text.value.trim().is_empty())
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{Local, NaiveDate};
+
+    use crate::config::{Config, FactFilter, ProjectMapping, TaskMapping};
+    use crate::hamster::HamsterFact;
+
+    use super::lint_facts;
+
+    fn fact(id: i64, activity: &str, description: &str) -> HamsterFact {
+        let start_time = NaiveDate::from_ymd_opt(2024, 5, 13)
+            .unwrap()
+            .and_hms_opt(9, 0, 0)
+            .unwrap()
+            .and_local_timezone(Local)
+            .unwrap();
+        HamsterFact {
+            id,
+            start_time,
+            end_time: None,
+            description: String::from(description),
+            activity: String::from(activity),
+            category: String::from("Work"),
+            tags: vec![],
+        }
+    }
+
+    fn lint(facts: &[HamsterFact], config: &Config) -> Vec<String> {
+        lint_facts(facts, config)
+            .into_iter()
+            .map(|(fact_id, issue)| format!("{fact_id}: {issue}"))
+            .collect()
+    }
+
+    #[test]
+    fn good_facts_pass() {
+        let facts = [
+            fact(
+                1,
+                "coding",
+                "[Parser](https://example.com/0/111/f)\n- fixed it",
+            ),
+            fact(
+                2,
+                "coding",
+                "[Parser](https://example.com/0/111/f)\n- tested it",
+            ),
+        ];
+        assert!(lint(&facts, &Config::default()).is_empty());
+    }
+
+    #[test]
+    fn links_in_comments_and_missing_lists_pass() {
+        let facts = [
+            fact(
+                1,
+                "coding",
+                "[Parser](https://example.com/0/111/f)\n\
+                - opened [the PR](https://github.com/org/repo/pull/1)\n\
+                - see [docs](https://docs.example.com/parser)",
+            ),
+            fact(2, "coding", "[Parser](https://example.com/0/111/f)"),
+        ];
+        assert!(lint(&facts, &Config::default()).is_empty());
+    }
+
+    #[test]
+    fn missing_and_unknown_links_reported() {
+        let facts = [
+            fact(1, "coding", "forgot the link"),
+            fact(
+                2,
+                "coding",
+                "[Parser](https://example.com/0/111/x)\n- fixed it",
+            ),
+            fact(3, "stand-up", ""),
+        ];
+        let config = Config {
+            mappings: vec![TaskMapping {
+                category: None,
+                activity: Some(String::from("stand-up")),
+                task_id: String::from("999"),
+            }],
+            ..Config::default()
+        };
+        assert_eq!(
+            lint(&facts, &config),
+            [
+                "1: no task link and no mapping",
+                "2: link https://example.com/0/111/x doesn't match any known task pattern"
+            ]
+        );
+    }
+
+    #[test]
+    fn project_mapped_facts_pass() {
+        let facts = [fact(1, "support", "answered tickets")];
+        let config = Config {
+            projects: vec![ProjectMapping {
+                filter: FactFilter {
+                    category: None,
+                    activity: Some(String::from("support")),
+                    tag: None,
+                },
+                project_id: String::from("ev:123"),
+            }],
+            ..Config::default()
+        };
+        assert!(lint(&facts, &config).is_empty());
+        assert_eq!(
+            lint(&facts, &Config::default()),
+            ["1: no task link and no mapping"]
+        );
+    }
+
+    #[test]
+    fn title_mismatch_and_missing_comments_reported() {
+        let facts = [
+            fact(
+                1,
+                "coding",
+                "[Parser](https://example.com/0/111/f)\n- fixed it",
+            ),
+            fact(2, "coding", "[Parsre](https://example.com/0/111/f)\n\n-  "),
+        ];
+        assert_eq!(
+            lint(&facts, &Config::default()),
+            [
+                "2: task 111 is titled 'Parsre' here, but 'Parser' in fact 1",
+                "2: no comments"
+            ]
+        );
+    }
+}
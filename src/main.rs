@@ -1,52 +1,653 @@
-use std::collections::HashMap;
+use std::io::{IsTerminal, Write};
 
-use chrono::{DateTime, Days, Local, NaiveDate};
+use chrono::{DateTime, Datelike, Days, Local, NaiveDate, NaiveDateTime};
 use clap::Parser;
 use comfy_table::Table;
 use std::time::Duration;
-use utils::unique_lines;
 
-use crate::{enrichment::HamsterEnrichedData, utils::DurationFormatting};
+use ham_cli::{
+    aggregation::{get_tasks_with_durations, AggregationOptions},
+    auth,
+    backend::{EverhourBackend, TimeTrackerBackend},
+    balance, checks,
+    config::{self, Config},
+    date_range::DateRange,
+    diff,
+    edit::{self, FactChanges},
+    enrichment::CommentMode,
+    everhour, export, hamster, import, lint, relink, report,
+    state::{self, SyncState},
+    sync,
+    targets::{
+        harvest::HarvestBackend, jira::JiraBackend, toggl::TogglBackend, webhook::WebhookBackend,
+    },
+    utils::{self, DurationFormatting, RunMode},
+    watch,
+};
+
 mod cli;
-mod enrichment;
-mod hamster;
-mod utils;
-
-use everhour_simple_client::client::Client as EverhourClient;
-use everhour_simple_client::time_record::TimeRecord;
-
-#[derive(Default)]
-enum RunMode {
-    #[default]
-    Normal,
-    DryRun,
-}
 
 #[tokio::main]
 async fn main() {
     let cli_args = cli::Cli::parse();
+    let everhour = &cli_args.everhour;
+    let config_path = cli_args.config.clone();
     let mut run_mode = RunMode::default();
 
     match cli_args.command {
-        cli::Commands::GetFacts {} => print_last_week_facts(cli_args.hamster_db),
-        cli::Commands::Tasks { from, to, category } => {
-            print_tasks(cli_args.hamster_db, from, to, category)
+        cli::Commands::GetFacts { period } => {
+            let config = Config::load(cli_args.config).unwrap();
+            let today = chrono::Local::now().date_naive();
+            let first_day = config.first_day_of_week();
+            let range = period
+                .range(today, first_day)
+                .unwrap_or(utils::Period::ThisWeek.range(today, first_day));
+            print_facts(cli_args.hamster_db, range)
+        }
+        cli::Commands::Tasks {
+            from,
+            to,
+            period,
+            aggregation,
+        } => {
+            let config = Config::load(cli_args.config).unwrap();
+            let today = chrono::Local::now().date_naive();
+            let range = period
+                .range(today, config.first_day_of_week())
+                .unwrap_or_else(|| date_range(from, to));
+            print_tasks(
+                cli_args.hamster_db,
+                range,
+                &config,
+                &AggregationOptions::from(aggregation),
+            )
         }
         cli::Commands::SyncTasksToEverhour {
             api_token,
             from,
             to,
+            period,
+            since_last,
+            warn_unsynced_after,
+            aggregation,
+            delete_duplicates,
+            dry_run,
+            skip_checks,
+            checks,
+        } => {
+            let config = Config::load(cli_args.config).unwrap();
+            let today = chrono::Local::now().date_naive();
+            let state_path = state::state_path(config_path.clone()).unwrap_or_else(|err| {
+                eprintln!("{err}");
+                std::process::exit(1);
+            });
+            let mut sync_state = SyncState::load(&state_path).unwrap_or_else(|err| {
+                eprintln!("{err}");
+                std::process::exit(1);
+            });
+            if let Some(day) = sync_state.overdue_since(today, warn_unsynced_after) {
+                eprintln!(
+                    "Warning: days since {day} aren't synced to Everhour yet - \
+                    run `ham-cli sync-eh --since-last`"
+                );
+            }
+            let range = if since_last {
+                match sync_state.unsynced_range(today) {
+                    Some(range) => range,
+                    None => {
+                        println!("Nothing to sync - days up to yesterday are synced already");
+                        return;
+                    }
+                }
+            } else {
+                period
+                    .range(today, config.first_day_of_week())
+                    .unwrap_or_else(|| date_range(from, to))
+            };
+            if dry_run {
+                run_mode = RunMode::DryRun;
+            }
+            check_before_sync(
+                cli_args.hamster_db.clone(),
+                range,
+                aggregation.category.clone(),
+                &checks.into(),
+                run_mode,
+                skip_checks,
+            );
+            let options = AggregationOptions::from(aggregation);
+            let synced = sync::sync_days(
+                &everhour_backend(api_token, &config, config_path, everhour),
+                cli_args.hamster_db,
+                range,
+                &config,
+                &options,
+                delete_duplicates,
+                run_mode,
+            )
+            .await;
+            if let Err(err) = synced {
+                eprintln!("{err}");
+                std::process::exit(1);
+            }
+            if let RunMode::Normal = run_mode {
+                if sync_state.record_sync(range, today, options.category.as_deref()) {
+                    if let Err(err) = sync_state.save(&state_path) {
+                        eprintln!("Synced, but couldn't remember it: {err}");
+                        std::process::exit(1);
+                    }
+                } else if let (true, Some(category)) = (since_last, &options.category) {
+                    eprintln!(
+                        "Only {category} was synced - these days aren't counted as synced \
+                        for --since-last"
+                    );
+                }
+            }
+        }
+        cli::Commands::Sync {
+            target,
+            aggregation,
+            api_token,
+            from,
+            to,
+            delete_duplicates,
+            dry_run,
+            skip_checks,
+            checks,
+        } => {
+            let range = date_range(from, to);
+            if dry_run {
+                run_mode = RunMode::DryRun;
+            }
+            check_before_sync(
+                cli_args.hamster_db.clone(),
+                range,
+                aggregation.category.clone(),
+                &checks.into(),
+                run_mode,
+                skip_checks,
+            );
+            let config = Config::load(cli_args.config).unwrap();
+            let options = AggregationOptions::from(aggregation);
+            let hamster_db = cli_args.hamster_db;
+            let targets = &config.targets;
+            let result = match target {
+                cli::SyncTarget::Everhour => {
+                    let backend = Ok(everhour_backend(api_token, &config, config_path, everhour));
+                    sync_to(
+                        backend,
+                        hamster_db,
+                        range,
+                        &config,
+                        &options,
+                        delete_duplicates,
+                        run_mode,
+                    )
+                    .await
+                }
+                cli::SyncTarget::Jira => {
+                    let backend = target_section(&targets.jira, "jira").and_then(JiraBackend::new);
+                    sync_to(
+                        backend,
+                        hamster_db,
+                        range,
+                        &config,
+                        &options,
+                        delete_duplicates,
+                        run_mode,
+                    )
+                    .await
+                }
+                cli::SyncTarget::Harvest => {
+                    let backend =
+                        target_section(&targets.harvest, "harvest").and_then(HarvestBackend::new);
+                    sync_to(
+                        backend,
+                        hamster_db,
+                        range,
+                        &config,
+                        &options,
+                        delete_duplicates,
+                        run_mode,
+                    )
+                    .await
+                }
+                cli::SyncTarget::Toggl => {
+                    let backend =
+                        target_section(&targets.toggl, "toggl").and_then(TogglBackend::new);
+                    sync_to(
+                        backend,
+                        hamster_db,
+                        range,
+                        &config,
+                        &options,
+                        delete_duplicates,
+                        run_mode,
+                    )
+                    .await
+                }
+                cli::SyncTarget::Webhook => {
+                    let backend =
+                        target_section(&targets.webhook, "webhook").map(WebhookBackend::new);
+                    sync_to(
+                        backend,
+                        hamster_db,
+                        range,
+                        &config,
+                        &options,
+                        delete_duplicates,
+                        run_mode,
+                    )
+                    .await
+                }
+            };
+            if let Err(err) = result {
+                eprintln!("{err}");
+                std::process::exit(1);
+            }
+        }
+        cli::Commands::Watch {
+            aggregation,
+            api_token,
+            interval,
+            debounce,
+            log_file,
+            delete_duplicates,
+            dry_run,
+            skip_checks,
+            checks,
+        } => {
+            let config = Config::load(cli_args.config).unwrap();
+            let state_path = state::state_path(config_path.clone()).unwrap_or_else(|err| {
+                eprintln!("{err}");
+                std::process::exit(1);
+            });
+            let mut log = watch::WatchLog::open(log_file.as_deref()).unwrap_or_else(|err| {
+                eprintln!("{err}");
+                std::process::exit(1);
+            });
+            if dry_run {
+                run_mode = RunMode::DryRun;
+            }
+            let options = watch::WatchOptions {
+                aggregation: AggregationOptions::from(aggregation),
+                checks: checks.into(),
+                skip_checks,
+                delete_duplicates,
+                run_mode,
+                interval: Duration::from_secs(interval),
+                debounce: Duration::from_secs(debounce),
+            };
+            let backend = everhour_backend(api_token, &config, config_path, everhour);
+            if let Err(err) = watch::watch_facts(
+                cli_args.hamster_db,
+                &backend,
+                &config,
+                &options,
+                &state_path,
+                &mut log,
+            )
+            .await
+            {
+                log.write(&err);
+                std::process::exit(1);
+            }
+        }
+        cli::Commands::DiffEverhour {
+            aggregation,
+            api_token,
+            from,
+            to,
+        } => {
+            let range = date_range(from, to);
+            let config = Config::load(cli_args.config).unwrap();
+            let rows = diff::diff_everhour(
+                cli_args.hamster_db,
+                &everhour_backend(api_token, &config, config_path, everhour),
+                range,
+                &config,
+                &AggregationOptions::from(aggregation),
+            )
+            .await
+            .unwrap_or_else(|err| {
+                eprintln!("{err}");
+                std::process::exit(1);
+            });
+            if !print_diff(&rows) {
+                std::process::exit(1);
+            }
+        }
+        cli::Commands::ImportEverhour {
             category,
+            api_token,
+            from,
+            to,
+            activity,
+            start,
             dry_run,
+        } => {
+            let range = date_range(from, to);
+            if dry_run {
+                run_mode = RunMode::DryRun;
+            }
+            let config = Config::load(cli_args.config).unwrap();
+            let imported = import::import_from_everhour(
+                cli_args.hamster_db,
+                &everhour_backend(api_token, &config, config_path, everhour),
+                range,
+                &config,
+                &import::ImportOptions {
+                    category,
+                    activity,
+                    start,
+                },
+                run_mode,
+            )
+            .await;
+            if let Err(err) = imported {
+                eprintln!("{err}");
+                std::process::exit(1);
+            }
+        }
+        cli::Commands::Report {
+            week,
+            from,
+            to,
+            format,
+            aggregation,
+        } => {
+            let config = Config::load(cli_args.config).unwrap();
+            let mut range = date_range(from, to);
+            if week {
+                let week_start = utils::week_start_on(range.first(), config.first_day_of_week());
+                range = DateRange::new(
+                    week_start,
+                    week_start.checked_add_days(Days::new(6)).unwrap(),
+                )
+                .unwrap();
+            }
+            print_report(
+                cli_args.hamster_db,
+                range,
+                &config,
+                &AggregationOptions::from(aggregation),
+                format,
+            )
+        }
+        cli::Commands::Balance {
+            category,
+            month,
+            year,
+            from,
+            to,
         } => {
             let today = chrono::Local::now().date_naive();
-            let from: NaiveDate = from.unwrap_or(today);
-            let to: NaiveDate = to.unwrap_or(from.clone());
+            let (from, to) = match (from, month, year) {
+                (Some(from), _, _) => (from, to.unwrap_or(today)),
+                (None, Some(month), _) => (month, utils::month_end(month)),
+                (None, None, Some(year)) => (
+                    NaiveDate::from_ymd_opt(year, 1, 1).unwrap(),
+                    NaiveDate::from_ymd_opt(year, 12, 31).unwrap(),
+                ),
+                (None, None, None) => {
+                    let to = to.unwrap_or(today);
+                    (utils::month_start(to), to)
+                }
+            };
+            // no point in counting deficit for days that haven't come yet
+            let Some(range) = date_range(Some(from), Some(to)).until(today) else {
+                eprintln!("Nothing to balance - {from} hasn't come yet");
+                std::process::exit(1);
+            };
+            let config = Config::load(cli_args.config).unwrap();
+            if let Err(err) = print_balance(cli_args.hamster_db, range, category, &config) {
+                eprintln!("{err}");
+                std::process::exit(1);
+            }
+        }
+        cli::Commands::Check {
+            category,
+            from,
+            to,
+            checks,
+        } => {
+            let range = date_range(from, to);
+            let issues = checks::find_fact_issues(
+                cli_args.hamster_db,
+                range,
+                category.as_deref(),
+                &checks.into(),
+            )
+            .unwrap_or_else(|err| {
+                eprintln!("{err}");
+                std::process::exit(1);
+            });
+            print_fact_issues(&issues);
+            if issues.iter().any(|issue| issue.is_error()) {
+                std::process::exit(1);
+            }
+        }
+        cli::Commands::Lint { category, from, to } => {
+            let range = date_range(from, to);
+            let config = Config::load(cli_args.config).unwrap();
+            let category = category.or(config.schedule.category.clone());
+            if !lint_facts(cli_args.hamster_db, range, category, &config) {
+                std::process::exit(1);
+            }
+        }
+        cli::Commands::Start { activity, desc } => {
+            if let Err(err) = start_fact(cli_args.hamster_db, activity, desc) {
+                eprintln!("{err}");
+                std::process::exit(1);
+            }
+        }
+        cli::Commands::Stop {} => {
+            if let Err(err) = stop_fact(cli_args.hamster_db) {
+                eprintln!("{err}");
+                std::process::exit(1);
+            }
+        }
+        cli::Commands::Add {
+            activity,
+            start,
+            end,
+            desc,
+        } => {
+            if let Err(err) = add_fact(cli_args.hamster_db, activity, start, end, desc) {
+                eprintln!("{err}");
+                std::process::exit(1);
+            }
+        }
+        cli::Commands::Edit {
+            fact_id,
+            activity,
+            start,
+            end,
+            desc,
+            editor,
+            force,
+        } => {
+            let config = Config::load(cli_args.config).unwrap();
+            let changes = FactChanges {
+                activity,
+                start,
+                end,
+                description: desc,
+            };
+            if let Err(err) = edit_fact(
+                cli_args.hamster_db,
+                fact_id,
+                changes,
+                editor,
+                &config,
+                force,
+            ) {
+                eprintln!("{err}");
+                std::process::exit(1);
+            }
+        }
+        cli::Commands::Relink {
+            aggregation,
+            from,
+            to,
+            old,
+            new,
+            dry_run,
+            sync,
+            api_token,
+            delete_duplicates,
+            skip_checks,
+            checks,
+        } => {
             if dry_run {
                 run_mode = RunMode::DryRun;
             }
-            sync_tasks_to_everhour(cli_args.hamster_db, api_token, from, to, category, run_mode)
-                .await
+            let config = Config::load(cli_args.config).unwrap();
+            // token problems should come up before anything is relinked
+            let backend = sync.then(|| everhour_backend(api_token, &config, config_path, everhour));
+            let options = AggregationOptions::from(aggregation);
+            let range = date_range(Some(from), Some(to));
+            let relinked = relink::find_relinked_facts(
+                cli_args.hamster_db.clone(),
+                range,
+                options.category.as_deref(),
+                &old,
+                &new,
+            )
+            .unwrap_or_else(|err| {
+                eprintln!("{err}");
+                std::process::exit(1);
+            });
+            print_relinked_facts(&relinked, &old);
+            let relinked_days = relink::relinked_days(&relinked);
+            // same pre-flight checks as sync-eh, before anything is written
+            if backend.is_some() {
+                let checks = checks.into();
+                for day in relinked_days.iter() {
+                    check_before_sync(
+                        cli_args.hamster_db.clone(),
+                        DateRange::day(*day),
+                        options.category.clone(),
+                        &checks,
+                        run_mode,
+                        skip_checks,
+                    );
+                }
+            }
+            // a dry run syncs from a copy of the database with the links rewritten,
+            // so it shows what the real run would do
+            let preview_db = match (&backend, run_mode) {
+                (Some(_), RunMode::DryRun) => {
+                    match relink::relinked_copy(cli_args.hamster_db.clone(), &relinked) {
+                        Ok(path) => Some(path),
+                        Err(err) => {
+                            eprintln!("{err}");
+                            std::process::exit(1);
+                        }
+                    }
+                }
+                _ => None,
+            };
+            match (run_mode, relinked.len()) {
+                (_, 0) => {}
+                (RunMode::DryRun, count) => println!("{count} fact(s) would be relinked"),
+                (RunMode::Normal, count) => {
+                    if let Err(err) =
+                        relink::save_relinked_facts(cli_args.hamster_db.clone(), &relinked)
+                    {
+                        eprintln!("{err}");
+                        std::process::exit(1);
+                    }
+                    println!(
+                        "{count} fact(s) relinked - time already booked to task {old} \
+                        stays in Everhour until removed there"
+                    )
+                }
+            }
+            let mut synced = Ok(());
+            if let Some(backend) = backend {
+                let synced_db = preview_db.clone().or(cli_args.hamster_db.clone());
+                for day in relinked_days {
+                    synced = sync::sync_days(
+                        &backend,
+                        synced_db.clone(),
+                        DateRange::day(day),
+                        &config,
+                        &options,
+                        delete_duplicates,
+                        run_mode,
+                    )
+                    .await;
+                    if synced.is_err() {
+                        break;
+                    }
+                }
+            }
+            if let Some(path) = preview_db {
+                let _ = std::fs::remove_file(path);
+            }
+            if let Err(err) = synced {
+                eprintln!("{err}");
+                std::process::exit(1);
+            }
+        }
+        cli::Commands::Export {
+            category,
+            from,
+            to,
+            format,
+            email,
+            output,
+        } => {
+            let range = date_range(from, to);
+            let config = Config::load(cli_args.config).unwrap();
+            let exported = export_facts(
+                cli_args.hamster_db,
+                range,
+                category,
+                &config,
+                format,
+                &email,
+            )
+            .unwrap();
+            match output {
+                Some(path) => {
+                    if let Err(err) = std::fs::write(&path, exported) {
+                        eprintln!("couldn't write {path}: {err}");
+                        std::process::exit(1);
+                    }
+                }
+                None => print!("{exported}"),
+            }
+        }
+        cli::Commands::Import {
+            file,
+            format,
+            activity,
+            category,
+            dry_run,
+        } => {
+            if dry_run {
+                run_mode = RunMode::DryRun;
+            }
+            let defaults = import::ImportDefaults { activity, category };
+            if let Err(err) = import_facts(cli_args.hamster_db, &file, format, &defaults, run_mode)
+            {
+                eprintln!("{err}");
+                std::process::exit(1);
+            }
+        }
+        cli::Commands::Mappings { command } => {
+            if let Err(err) = manage_mappings(cli_args.config, command) {
+                eprintln!("{err}");
+                std::process::exit(1);
+            }
+        }
+        cli::Commands::Auth { command } => {
+            if let Err(err) = manage_auth(cli_args.config, everhour, command).await {
+                eprintln!("{err}");
+                std::process::exit(1);
+            }
         }
         _ => {
             println!("This command is not implemented yet")
@@ -54,11 +655,9 @@ async fn main() {
     }
 }
 
-fn print_last_week_facts(hamster_db: Option<String>) {
+fn print_facts(hamster_db: Option<String>, range: DateRange) {
     let hamster_data = hamster::HamsterData::open(hamster_db).unwrap();
-    let week_start = utils::week_start(Local::now().date_naive());
-    let week_end = week_start.checked_add_days(Days::new(7)).unwrap();
-    let facts = hamster_data.get_facts(week_start, week_end);
+    let facts = hamster_data.get_facts(range);
     let mut table = Table::new();
     table.set_header(["start time", "end_time", "duration", "name"]);
     for record in facts {
@@ -85,221 +684,596 @@ fn print_last_week_facts(hamster_db: Option<String>) {
     println!("{table}");
 }
 
-struct TaskData {
-    title: Option<String>,
-    duration: Duration,
-    comments: Vec<String>,
+impl From<cli::AggregationArgs> for AggregationOptions {
+    fn from(args: cli::AggregationArgs) -> Self {
+        AggregationOptions {
+            category: args.category,
+            split_links: args.split_links,
+            comment_mode: match args.comments {
+                cli::CommentsFormat::Simple => CommentMode::Simple,
+                cli::CommentsFormat::Rich => CommentMode::Rich {
+                    include_paragraphs: args.include_paragraphs,
+                },
+            },
+        }
+    }
 }
 
-fn get_tasks_with_durations(
+fn print_tasks(
     hamster_db: Option<String>,
-    from: NaiveDate,
-    to: NaiveDate,
-    category: Option<String>,
-) -> HashMap<Option<String>, TaskData> {
-    let hamster_data = hamster::HamsterData::open(hamster_db).unwrap();
+    range: DateRange,
+    config: &Config,
+    options: &AggregationOptions,
+) {
+    let tasks = get_tasks_with_durations(hamster_db, range, config, options);
+    let mut total_duration = Duration::new(0, 0);
 
-    let facts = hamster_data.get_facts(from, to);
+    let mut table = Table::new();
+    let mut header = vec!["Task ID", "name", "duration", "comments"];
+    if options.split_links {
+        header.push("split");
+    }
+    table.set_header(header);
+    for (task_id, task_data) in tasks.into_iter() {
+        total_duration += task_data.duration;
+        let mut row = vec![
+            task_id.unwrap_or("-".to_string()),
+            task_data.title.unwrap_or("-".to_string()),
+            task_data.duration.as_hhmm(),
+            task_data.comments.join("\n"),
+        ];
+        if options.split_links {
+            row.push(task_data.splits.join("\n"));
+        }
+        table.add_row(row);
+    }
+    table.add_row(["", "", total_duration.as_hhmm().as_str()]);
+    println!("{table}");
+}
 
-    let facts = match category {
-        None => facts,
-        Some(category) => facts
-            .into_iter()
-            .filter(|fact| fact.category == category)
-            .collect(),
-    };
+fn print_report(
+    hamster_db: Option<String>,
+    range: DateRange,
+    config: &Config,
+    options: &AggregationOptions,
+    format: cli::ReportFormat,
+) {
+    let mut timesheet = report::Timesheet::new(range.days().collect());
 
-    let mut tasks: HashMap<Option<String>, TaskData> = HashMap::new();
+    for day in range.days() {
+        let tasks =
+            get_tasks_with_durations(hamster_db.clone(), DateRange::day(day), config, options);
+        for (task_id, task_data) in tasks.into_iter() {
+            timesheet.add(
+                day,
+                task_id.as_deref().unwrap_or("-"),
+                task_data.title.as_deref().unwrap_or("-"),
+                task_data.duration,
+            );
+        }
+    }
 
-    for record in facts {
-        let end_time = record.end_time.unwrap_or_else(|| Local::now());
-        let duration = (end_time - record.start_time).to_std().unwrap();
-        let mut comments = unique_lines(record.comments());
+    match format {
+        cli::ReportFormat::Table => println!("{}", timesheet.to_table()),
+        cli::ReportFormat::Csv => print!("{}", timesheet.to_csv()),
+        cli::ReportFormat::Markdown => print!("{}", timesheet.to_markdown()),
+    }
+}
 
-        let task_id: Option<String>;
-        let title: Option<String>;
+fn print_balance(
+    hamster_db: Option<String>,
+    range: DateRange,
+    category: Option<String>,
+    config: &Config,
+) -> Result<(), String> {
+    let category = category.or(config.schedule.category.clone());
+    let tracked = balance::tracked_by_day(hamster_db, range, category.as_deref())?;
+    let balance = balance::daily_balance(range, &tracked, &config.schedule);
 
-        if let Some(task_link) = record.task() {
-            task_id = task_link.task_id;
-            title = Some(task_link.link_title);
-        } else {
-            task_id = None;
-            title = None;
+    let mut table = Table::new();
+    table.set_header(["day", "tracked", "target", "difference", "balance"]);
+    let mut total_tracked = Duration::new(0, 0);
+    let mut total_target = Duration::new(0, 0);
+    for day_balance in balance.iter() {
+        total_tracked += day_balance.tracked;
+        total_target += day_balance.target;
+        if day_balance.tracked.is_zero() && day_balance.target.is_zero() {
+            continue;
         }
+        table.add_row([
+            format!("{} {}", day_balance.day, day_balance.day.weekday()),
+            day_balance.tracked.as_hhmm(),
+            day_balance.target.as_hhmm(),
+            utils::signed_hhmm(day_balance.difference()),
+            utils::signed_hhmm(day_balance.running),
+        ]);
+    }
+    let overtime = balance
+        .last()
+        .map(|day_balance| day_balance.running)
+        .unwrap_or_default();
+    table.add_row([
+        String::from("total"),
+        total_tracked.as_hhmm(),
+        total_target.as_hhmm(),
+        String::new(),
+        utils::signed_hhmm(overtime),
+    ]);
+    println!("{table}");
+    Ok(())
+}
 
-        if task_id == None {
-            panic!(
-                "Error obtaining task id from fact {} ('{}' at {})",
-                record.id, record.activity, record.start_time
-            )
+impl From<cli::CheckArgs> for checks::CheckOptions {
+    fn from(args: cli::CheckArgs) -> Self {
+        checks::CheckOptions {
+            max_duration: Duration::from_secs_f64(args.max_hours * 3600.0),
+            min_gap: Duration::from_secs(args.min_gap * 60),
+            work_start: args.work_start,
+            work_end: args.work_end,
         }
+    }
+}
 
-        tasks
-            .entry(task_id)
-            .and_modify(|task_data: &mut TaskData| {
-                task_data.duration += duration;
-                let mut unique_comments = vec![];
-                unique_comments.append(&mut task_data.comments);
-                unique_comments.append(&mut comments);
-                task_data.comments = unique_lines(unique_comments);
-            })
-            .or_insert(TaskData {
-                title,
-                duration,
-                comments,
-            });
+fn print_fact_issues(issues: &[checks::FactIssue]) {
+    for issue in issues {
+        let level = if issue.is_error() { "error" } else { "warning" };
+        println!("{level}: {issue}");
     }
-    tasks
 }
 
-fn print_tasks(
+/// Prints issues found in fact descriptions, returns true if there are none
+fn lint_facts(
     hamster_db: Option<String>,
-    from: Option<NaiveDate>,
-    to: Option<NaiveDate>,
+    range: DateRange,
     category: Option<String>,
-) {
-    let now = Local::now();
+    config: &Config,
+) -> bool {
+    let hamster_data = hamster::HamsterData::open(hamster_db).unwrap();
+    let facts = hamster_data.get_category_facts(range, category.as_deref());
+    let issues = lint::lint_facts(&facts, config);
+
+    for (fact_id, issue) in issues.iter() {
+        let fact = facts.iter().find(|fact| fact.id == *fact_id).unwrap();
+        println!(
+            "fact {fact_id} ('{}' at {}): {issue}",
+            fact.activity, fact.start_time
+        );
+    }
+    issues.is_empty()
+}
+
+/// Range of `--from` and `--to` options, exits if it ends before it starts
+fn date_range(from: Option<NaiveDate>, to: Option<NaiveDate>) -> DateRange {
+    let today = Local::now().date_naive();
+    match DateRange::from_args(from, to, today) {
+        Ok(range) => range,
+        Err(err) => {
+            eprintln!("{err}");
+            std::process::exit(1);
+        }
+    }
+}
+
+/// Everhour client talking to the API, a stub server or a recording, as set on the command line
+fn everhour_client(api_token: String, args: &cli::EverhourArgs) -> everhour::Client {
+    match &args.everhour_replay {
+        Some(path) => everhour::Client::replaying(path).unwrap(),
+        None => {
+            let client = everhour::Client::new(api_token, args.everhour_url.clone());
+            match &args.everhour_record {
+                Some(path) => client.recording(path.clone()),
+                None => client,
+            }
+        }
+    }
+}
 
-    let from = match from {
-        Some(from) => from,
-        None => now.date_naive(),
+/// Exits if there's no token - it's not needed for replaying though
+fn everhour_backend(
+    api_token: Option<String>,
+    config: &Config,
+    config_path: Option<String>,
+    args: &cli::EverhourArgs,
+) -> EverhourBackend {
+    let api_token = match args.everhour_replay {
+        Some(_) => String::new(),
+        None => match auth::everhour_token(api_token, config, config_path) {
+            Ok(api_token) => api_token,
+            Err(err) => {
+                eprintln!("{err}");
+                std::process::exit(1);
+            }
+        },
     };
-    let to = match to {
-        Some(to) => to,
-        None => now.checked_add_days(Days::new(1)).unwrap().date_naive(),
+    EverhourBackend::new(everhour_client(api_token, args))
+}
+
+/// Reports fact issues, stopping unless it's a dry run or checks are skipped
+fn check_before_sync(
+    hamster_db: Option<String>,
+    range: DateRange,
+    category: Option<String>,
+    options: &checks::CheckOptions,
+    run_mode: RunMode,
+    skip_checks: bool,
+) {
+    let issues = checks::find_fact_issues(hamster_db, range, category.as_deref(), options)
+        .unwrap_or_else(|err| {
+            eprintln!("{err}");
+            std::process::exit(1);
+        });
+    print_fact_issues(&issues);
+    if issues.iter().any(|issue| issue.is_error())
+        && matches!(run_mode, RunMode::Normal)
+        && !skip_checks
+    {
+        eprintln!("Fix the facts above or use --skip-checks to sync anyway");
+        std::process::exit(1);
+    }
+}
+
+/// Config section of a sync target, if it's there
+fn target_section<'a, T>(section: &'a Option<T>, name: &str) -> Result<&'a T, String> {
+    section
+        .as_ref()
+        .ok_or(format!("No [targets.{name}] section in the config"))
+}
+
+/// Syncs to the tracker, if it could be set up
+async fn sync_to<B: TimeTrackerBackend>(
+    backend: Result<B, String>,
+    hamster_db: Option<String>,
+    range: DateRange,
+    config: &Config,
+    options: &AggregationOptions,
+    delete_duplicates: bool,
+    run_mode: RunMode,
+) -> Result<(), String> {
+    sync::sync_days(
+        &backend?,
+        hamster_db,
+        range,
+        config,
+        options,
+        delete_duplicates,
+        run_mode,
+    )
+    .await
+}
+
+/// Prints Everhour records next to Hamster aggregates, returns whether they agree
+fn print_diff(rows: &[diff::DiffRow]) -> bool {
+    let cell = |duration: Option<Duration>| match duration {
+        Some(duration) => duration.as_hhmm(),
+        None => "-".to_string(),
     };
-    let tasks = get_tasks_with_durations(hamster_db, from, to, category);
-    let mut total_duration = Duration::new(0, 0);
+    let mut table = Table::new();
+    table.set_header([
+        "day",
+        "Task ID",
+        "name",
+        "hamster",
+        "everhour",
+        "difference",
+        "status",
+    ]);
+    let mut in_sync = true;
+    for row in rows.iter() {
+        let status = row.status();
+        in_sync &= status == diff::DiffStatus::Match;
+        table.add_row([
+            row.day.to_string(),
+            row.task_id.clone(),
+            row.title.clone(),
+            cell(row.hamster),
+            cell(row.everhour),
+            utils::signed_hhmm(row.difference()),
+            status.to_string(),
+        ]);
+    }
+    println!("{table}");
+    in_sync
+}
+
+fn start_fact(
+    hamster_db: Option<String>,
+    (activity, category): (String, String),
+    description: Option<String>,
+) -> Result<(), String> {
+    let hamster_data = hamster::HamsterData::open(hamster_db)?;
+    let now = Local::now();
+    if let Some(fact_id) = hamster_data.stop_running(now)? {
+        println!("stopped fact {fact_id}");
+    }
+    let fact_id = hamster_data.add_fact(
+        &activity,
+        &category,
+        now,
+        None,
+        description.as_deref().unwrap_or_default(),
+        &[],
+    )?;
+    println!("started fact {fact_id} - {activity}@{category}");
+    Ok(())
+}
+
+fn stop_fact(hamster_db: Option<String>) -> Result<(), String> {
+    let hamster_data = hamster::HamsterData::open(hamster_db)?;
+    match hamster_data.stop_running(Local::now())? {
+        Some(fact_id) => println!("stopped fact {fact_id}"),
+        None => println!("nothing is running"),
+    }
+    Ok(())
+}
+
+fn add_fact(
+    hamster_db: Option<String>,
+    (activity, category): (String, String),
+    start: NaiveDateTime,
+    end: NaiveDateTime,
+    description: Option<String>,
+) -> Result<(), String> {
+    let hamster_data = hamster::HamsterData::open(hamster_db)?;
+    let fact_id = edit::add_fact(
+        &hamster_data,
+        (&activity, &category),
+        start,
+        end,
+        description.as_deref().unwrap_or_default(),
+    )?;
+    println!("added fact {fact_id} - {activity}@{category} {start} - {end}");
+    Ok(())
+}
+
+fn edit_fact(
+    hamster_db: Option<String>,
+    fact_id: i64,
+    changes: FactChanges,
+    editor: bool,
+    config: &Config,
+    force: bool,
+) -> Result<(), String> {
+    let hamster_data = hamster::HamsterData::open(hamster_db)?;
+    let mut fact = edit::changed_fact(&hamster_data, fact_id, changes)?;
+    if editor {
+        fact.description = edit_in_editor(fact_id, &fact.description)?;
+    }
+    for problem in edit::save_fact(&hamster_data, &fact, config, force)? {
+        eprintln!("{problem}");
+    }
+    println!(
+        "updated fact {fact_id} - {}@{} {} - {}",
+        fact.activity,
+        fact.category,
+        fact.start_time.format("%Y-%m-%d %H:%M"),
+        fact.end_time
+            .map(|end_time| end_time.format("%Y-%m-%d %H:%M").to_string())
+            .unwrap_or("...".to_string())
+    );
+    Ok(())
+}
 
+/// Opens the text in $VISUAL or $EDITOR (vi if neither is set), gives back what was saved
+fn edit_in_editor(fact_id: i64, text: &str) -> Result<String, String> {
+    let editor = std::env::var("VISUAL")
+        .or_else(|_| std::env::var("EDITOR"))
+        .unwrap_or("vi".to_string());
+    let (path, mut file) = utils::create_private_file(&format!("ham-cli-fact-{fact_id}"), "md")?;
+    let written = file
+        .write_all(text.as_bytes())
+        .map_err(|err| format!("couldn't write {}: {err}", path.display()));
+    drop(file);
+
+    // editor may come with arguments, e.g. "code --wait"
+    let edited = written.and_then(|_| {
+        let mut command_line = editor.split_whitespace();
+        let status = std::process::Command::new(command_line.next().unwrap_or("vi"))
+            .args(command_line)
+            .arg(&path)
+            .status()
+            .map_err(|err| format!("couldn't run {editor}: {err}"))?;
+        if !status.success() {
+            return Err(format!(
+                "{editor} exited with {status}, fact is not changed"
+            ));
+        }
+        std::fs::read_to_string(&path)
+            .map_err(|err| format!("couldn't read {}: {err}", path.display()))
+    });
+    let _ = std::fs::remove_file(&path);
+    Ok(edited?.trim_end().to_string())
+}
+
+/// Shows relinked facts with their old and new descriptions
+fn print_relinked_facts(relinked: &[relink::RelinkedFact], old_task_id: &str) {
+    if relinked.is_empty() {
+        println!("No links to task {old_task_id} found");
+        return;
+    }
     let mut table = Table::new();
-    table.set_header(["Task ID", "name", "duration", "comments"]);
-    for (task_id, task_data) in tasks.into_iter() {
-        total_duration += task_data.duration;
+    table.set_header(["ID", "start", "description", "new description"]);
+    for relinked in relinked {
         table.add_row([
-            task_id.unwrap_or("-".to_string()),
-            task_data.title.unwrap_or("-".to_string()),
-            task_data.duration.as_hhmm(),
-            task_data.comments.join("\n"),
+            relinked.fact.id.to_string(),
+            relinked
+                .fact
+                .start_time
+                .format("%Y-%m-%d %H:%M")
+                .to_string(),
+            relinked.old_description.clone(),
+            relinked.fact.description.clone(),
         ]);
     }
-    table.add_row(["", "", total_duration.as_hhmm().as_str()]);
     println!("{table}");
 }
 
-async fn sync_tasks_to_everhour(
+fn export_facts(
     hamster_db: Option<String>,
-    api_token: String,
-    from: NaiveDate,
-    to: NaiveDate,
+    range: DateRange,
     category: Option<String>,
+    config: &Config,
+    format: cli::ExportFormat,
+    email: &str,
+) -> Result<String, String> {
+    let hamster_data = hamster::HamsterData::open(hamster_db)?;
+    let facts = hamster_data.get_category_facts(range, category.as_deref());
+    let now = Local::now();
+    Ok(match format {
+        cli::ExportFormat::Csv => export::to_csv(&facts, now),
+        cli::ExportFormat::Jsonl => export::to_jsonl(&facts, now),
+        cli::ExportFormat::Ical => export::to_ical(&facts, now),
+        cli::ExportFormat::Toggl => export::to_toggl_csv(&facts, config, email, now),
+        cli::ExportFormat::Clockify => export::to_clockify_csv(&facts, config, email, now),
+    })
+}
+
+/// Adds facts from the file, skipping ones that overlap with existing facts
+fn import_facts(
+    hamster_db: Option<String>,
+    path: &str,
+    format: cli::ImportFormat,
+    defaults: &import::ImportDefaults,
     run_mode: RunMode,
-) {
-    let client = EverhourClient::new(api_token);
-    let me = client.get_current_user().await.unwrap();
-    let existing_time_records = client
-        .get_user_time_records(me.id, Some(from), Some(to))
-        .await
-        .unwrap();
-
-    // sort existing time records into map by day/id pair
-    let mut records_map: HashMap<(NaiveDate, String), TimeRecord> = HashMap::new();
-
-    for time_record in existing_time_records.into_iter() {
-        records_map
-            .entry((
-                time_record.date,
-                time_record.task.as_ref().unwrap().id.clone(),
-            ))
-            .or_insert(time_record);
+) -> Result<(), String> {
+    let text =
+        std::fs::read_to_string(path).map_err(|err| format!("couldn't read {path}: {err}"))?;
+    let (facts, unreadable) = match format {
+        cli::ImportFormat::Csv => (import::parse_ham_csv(&text, defaults)?, vec![]),
+        cli::ImportFormat::Ical => import::parse_ical(&text, defaults),
+        cli::ImportFormat::Toggl | cli::ImportFormat::Clockify => {
+            (import::parse_tool_csv(&text, defaults)?, vec![])
+        }
+    };
+    for problem in unreadable.iter() {
+        println!("skipping, {problem}");
+    }
+    if facts.is_empty() {
+        println!("Nothing to import in {path}");
+        return Ok(());
     }
 
-    let mut day = from;
-    while day <= to {
-        println!("Processing day {}", day);
-        let next_day = day.checked_add_days(Days::new(1)).unwrap();
-        let tasks = get_tasks_with_durations(hamster_db.clone(), day, next_day, category.clone());
-        let mut total_duration = Duration::new(0, 0);
-        for (task_id, task_data) in tasks.into_iter() {
-            let task_id_eh = match &task_id {
-                Some(task_id) => format!("as:{task_id}"),
-                None => match run_mode {
-                    RunMode::DryRun => "-".to_string(),
-                    RunMode::Normal => panic!(
-                        "Missing task id! ({}, '{}')",
-                        task_data.duration.as_hhmm(),
-                        task_data.title.unwrap_or("-".to_string())
-                    ),
-                },
-            };
-
-            total_duration += task_data.duration;
+    let (added, skipped) = import::import_facts(hamster_db, facts, run_mode)?;
+    let skipped = skipped + unreadable.len();
+    match run_mode {
+        RunMode::DryRun => println!("{added} fact(s) would be imported, {skipped} skipped"),
+        RunMode::Normal => println!("{added} fact(s) imported, {skipped} skipped"),
+    }
+    Ok(())
+}
 
-            if task_id.is_none() {
-                // no task id - not enough data to add anything
-                continue;
+fn manage_mappings(
+    config_path: Option<String>,
+    command: cli::MappingsCommands,
+) -> Result<(), String> {
+    let mut config = Config::load(config_path.clone())?;
+    match command {
+        cli::MappingsCommands::List {} => {
+            let mut table = Table::new();
+            table.set_header(["category", "activity", "Task ID"]);
+            for mapping in config.mappings.iter() {
+                table.add_row([
+                    mapping.category.clone().unwrap_or("*".to_string()),
+                    mapping.activity.clone().unwrap_or("*".to_string()),
+                    mapping.task_id.clone(),
+                ]);
             }
-
-            let data_msg = format!(
-                "{day}: {} seconds ({}) for user {} on task {} ({})",
-                task_data.duration.as_secs(),
-                task_data.duration.as_hhmm(),
-                me.id,
-                task_id_eh,
-                task_data.title.unwrap_or("-".to_string())
-            );
-            let existing_record = records_map.get(&(day, task_id_eh.clone()));
-
-            match (existing_record, &run_mode) {
-                (Some(existing_record), RunMode::DryRun) => {
-                    println!(
-                        "would sync to record {} - {data_msg}",
-                        existing_record.id.unwrap()
-                    )
-                }
-                (None, RunMode::DryRun) => println!("would add new  record - {data_msg}"),
-                (Some(existing_record), RunMode::Normal) => {
-                    println!(
-                        "syncing to record {} - {data_msg}",
-                        existing_record.id.unwrap()
-                    );
-                    client
-                        .update_task_time_record(
-                            task_id_eh,
-                            TimeRecord::for_adding(
-                                day,
-                                me.id,
-                                task_data.duration.as_secs() as i64,
-                                Some(task_data.comments.join("\n")),
-                            ),
-                        )
-                        .await
-                        .unwrap();
-                }
-                (None, RunMode::Normal) => {
-                    println!("adding - {data_msg}");
-                    client
-                        .add_task_time_record(
-                            task_id_eh,
-                            TimeRecord::for_adding(
-                                day,
-                                me.id,
-                                task_data.duration.as_secs() as i64,
-                                Some(task_data.comments.join("\n")),
-                            ),
-                        )
-                        .await
-                        .unwrap();
-                }
+            println!("{table}");
+        }
+        cli::MappingsCommands::Add {
+            task_id,
+            category,
+            activity,
+        } => {
+            if category.is_none() && activity.is_none() {
+                return Err(String::from(
+                    "At least one of --category and --activity is required",
+                ));
+            }
+            let mapping = config::TaskMapping {
+                category,
+                activity,
+                task_id,
             };
+            // re-adding the same pair just points it to the new task
+            config.mappings.retain(|existing| {
+                existing.category != mapping.category || existing.activity != mapping.activity
+            });
+            println!("{} -> {}", mapping.describe(), mapping.task_id);
+            config.mappings.push(mapping);
+            config.save(config_path)?;
         }
+        cli::MappingsCommands::Remove { category, activity } => {
+            let mappings_count = config.mappings.len();
+            config
+                .mappings
+                .retain(|existing| existing.category != category || existing.activity != activity);
+            if config.mappings.len() == mappings_count {
+                return Err(String::from("No such mapping"));
+            }
+            config.save(config_path)?;
+        }
+    }
+    Ok(())
+}
 
-        println!(
-            "Total seconds for day: {} ({})",
-            total_duration.as_secs(),
-            total_duration.as_hhmm()
-        );
-
-        day = next_day;
+/// Stores, checks and removes Everhour API token
+async fn manage_auth(
+    config_path: Option<String>,
+    everhour: &cli::EverhourArgs,
+    command: cli::AuthCommands,
+) -> Result<(), String> {
+    let config = Config::load(config_path.clone())?;
+    let path = auth::token_path(config_path)?;
+    match command {
+        cli::AuthCommands::Login { no_verify } => {
+            let stdin = std::io::stdin();
+            // typed token isn't echoed, piped one is read as is
+            let token = if stdin.is_terminal() {
+                rpassword::prompt_password("Everhour API token: ")
+                    .map_err(|err| format!("couldn't read token: {err}"))?
+            } else {
+                let mut token = String::new();
+                stdin
+                    .read_line(&mut token)
+                    .map_err(|err| format!("couldn't read token: {err}"))?;
+                token
+            };
+            let token = token.trim();
+            if token.is_empty() {
+                return Err(String::from("No token given"));
+            }
+            if !no_verify {
+                let user = everhour_client(token.to_string(), everhour)
+                    .current_user()
+                    .await
+                    .map_err(|err| format!("Token doesn't work: {err}"))?;
+                println!("Logged in as {} ({})", user.name, user.id);
+            }
+            auth::save_token(&path, token)?;
+            println!("Token saved to {}", path.display());
+            if let Some(command) = &config.token_command {
+                println!("Note: token_command `{command}` from the config takes precedence");
+            }
+        }
+        cli::AuthCommands::Status {} => {
+            let from_env = std::env::var("EVERHOUR_API_TOKEN").ok();
+            let Some((token, source)) = auth::find_token(from_env, &config, &path)? else {
+                return Err(String::from("Not logged in - run `ham-cli auth login`"));
+            };
+            println!("Token from {source}");
+            let user = everhour_client(token, everhour).current_user().await?;
+            println!("Everhour user: {} ({})", user.name, user.id);
+        }
+        cli::AuthCommands::Logout {} => {
+            if auth::remove_token(&path)? {
+                println!("Token removed from {}", path.display());
+            } else {
+                println!("No token stored at {}", path.display());
+            }
+            if let Some(command) = &config.token_command {
+                println!("Note: token_command `{command}` from the config is still used");
+            }
+        }
     }
-    println!("Everhour user id: {}", me.id);
+    Ok(())
 }
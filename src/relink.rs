@@ -0,0 +1,85 @@
+//! `ham-cli relink`: pointing links to a task at another one
+use chrono::NaiveDate;
+use std::io::Write;
+
+use crate::date_range::DateRange;
+use crate::enrichment::{task_id_from_url, HamsterEnrichedData};
+use crate::hamster::{self, HamsterData, HamsterFact};
+use crate::utils::create_private_file;
+
+/// Fact with its links rewritten, not saved yet
+pub struct RelinkedFact {
+    pub fact: HamsterFact,
+    /// description before relinking
+    pub old_description: String,
+}
+
+/// Facts of the range, only of the category if given, with links to the old
+/// task pointed to the new one
+pub fn find_relinked_facts(
+    hamster_db: Option<String>,
+    range: DateRange,
+    category: Option<&str>,
+    old_task_id: &str,
+    new_url: &str,
+) -> Result<Vec<RelinkedFact>, String> {
+    if task_id_from_url(new_url).is_none() {
+        return Err(format!("{new_url} doesn't look like a task link"));
+    }
+    let hamster_data = HamsterData::open(hamster_db)?;
+    Ok(hamster_data
+        .get_category_facts(range, category)
+        .into_iter()
+        .filter_map(|mut fact| {
+            let description = fact.relinked(old_task_id, new_url)?;
+            let old_description = std::mem::replace(&mut fact.description, description);
+            Some(RelinkedFact {
+                fact,
+                old_description,
+            })
+        })
+        .collect())
+}
+
+/// Days the relinked facts start on, in order
+pub fn relinked_days(relinked: &[RelinkedFact]) -> Vec<NaiveDate> {
+    let mut days: Vec<NaiveDate> = relinked
+        .iter()
+        .map(|relinked| relinked.fact.start_time.date_naive())
+        .collect();
+    days.sort();
+    days.dedup();
+    days
+}
+
+/// Writes relinked facts to the database
+pub fn save_relinked_facts(
+    hamster_db: Option<String>,
+    relinked: &[RelinkedFact],
+) -> Result<(), String> {
+    let hamster_data = HamsterData::open(hamster_db)?;
+    relinked
+        .iter()
+        .try_for_each(|relinked| hamster_data.update_fact(&relinked.fact))
+}
+
+/// Private copy of the database with relinked facts written to it, for previewing the sync
+pub fn relinked_copy(
+    hamster_db: Option<String>,
+    relinked: &[RelinkedFact],
+) -> Result<String, String> {
+    let db_path = hamster::db_path(hamster_db)?;
+    let content =
+        std::fs::read(&db_path).map_err(|err| format!("couldn't read {db_path}: {err}"))?;
+    let (path, mut file) = create_private_file("ham-cli-relink", "db")?;
+    let path = path.to_string_lossy().into_owned();
+    let copied = file
+        .write_all(&content)
+        .map_err(|err| format!("couldn't write {path}: {err}"))
+        .and_then(|_| save_relinked_facts(Some(path.clone()), relinked));
+    if let Err(err) = copied {
+        let _ = std::fs::remove_file(&path);
+        return Err(err);
+    }
+    Ok(path)
+}
@@ -0,0 +1,174 @@
+use chrono::NaiveDate;
+use comfy_table::Table;
+use std::time::Duration;
+
+use crate::utils::{csv_line, DurationFormatting};
+
+/// Tasks × days matrix with daily and overall totals
+pub struct Timesheet {
+    days: Vec<NaiveDate>,
+    rows: Vec<TimesheetRow>,
+}
+
+struct TimesheetRow {
+    task_id: String,
+    title: String,
+    durations: Vec<Duration>,
+}
+
+impl Timesheet {
+    pub fn new(days: Vec<NaiveDate>) -> Timesheet {
+        Timesheet { days, rows: vec![] }
+    }
+
+    pub fn add(&mut self, day: NaiveDate, task_id: &str, title: &str, duration: Duration) {
+        let Some(day_index) = self.days.iter().position(|report_day| *report_day == day) else {
+            return;
+        };
+        let row_index = match self.rows.iter().position(|row| row.task_id == task_id) {
+            Some(row_index) => row_index,
+            None => {
+                self.rows.push(TimesheetRow {
+                    task_id: task_id.to_string(),
+                    title: title.to_string(),
+                    durations: vec![Duration::new(0, 0); self.days.len()],
+                });
+                self.rows.sort_by(|a, b| a.task_id.cmp(&b.task_id));
+                self.rows
+                    .iter()
+                    .position(|row| row.task_id == task_id)
+                    .unwrap()
+            }
+        };
+        self.rows[row_index].durations[day_index] += duration;
+    }
+
+    pub fn day_totals(&self) -> Vec<Duration> {
+        (0..self.days.len())
+            .map(|day_index| self.rows.iter().map(|row| row.durations[day_index]).sum())
+            .collect()
+    }
+
+    pub fn total(&self) -> Duration {
+        self.day_totals().into_iter().sum()
+    }
+
+    fn header(&self) -> Vec<String> {
+        let mut header = vec![String::from("Task ID"), String::from("name")];
+        header.extend(
+            self.days
+                .iter()
+                .map(|day| day.format("%a %m-%d").to_string()),
+        );
+        header.push(String::from("total"));
+        header
+    }
+
+    /// Task rows followed by the totals row, durations formatted as hh:mm
+    fn lines(&self) -> Vec<Vec<String>> {
+        let cell = |duration: Duration| {
+            if duration.is_zero() {
+                String::new()
+            } else {
+                duration.as_hhmm()
+            }
+        };
+        let mut lines: Vec<Vec<String>> = self
+            .rows
+            .iter()
+            .map(|row| {
+                let mut line = vec![row.task_id.clone(), row.title.clone()];
+                line.extend(row.durations.iter().map(|duration| cell(*duration)));
+                line.push(cell(row.durations.iter().sum()));
+                line
+            })
+            .collect();
+
+        let mut totals = vec![String::from("total"), String::new()];
+        totals.extend(self.day_totals().into_iter().map(cell));
+        totals.push(self.total().as_hhmm());
+        lines.push(totals);
+        lines
+    }
+
+    pub fn to_table(&self) -> Table {
+        let mut table = Table::new();
+        table.set_header(self.header());
+        for line in self.lines() {
+            table.add_row(line);
+        }
+        table
+    }
+
+    pub fn to_csv(&self) -> String {
+        std::iter::once(self.header())
+            .chain(self.lines())
+            .map(|line| csv_line(&line) + "\n")
+            .collect()
+    }
+
+    pub fn to_markdown(&self) -> String {
+        let markdown_line = |line: Vec<String>| {
+            let cells: Vec<String> = line.iter().map(|cell| cell.replace('|', "\\|")).collect();
+            format!("| {} |\n", cells.join(" | "))
+        };
+        let header = self.header();
+        let separator = vec![String::from("---"); header.len()];
+        std::iter::once(header)
+            .chain(std::iter::once(separator))
+            .chain(self.lines())
+            .map(markdown_line)
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::NaiveDate;
+    use std::time::Duration;
+
+    use super::Timesheet;
+
+    fn timesheet() -> Timesheet {
+        let monday = NaiveDate::from_ymd_opt(2024, 5, 13).unwrap();
+        let tuesday = NaiveDate::from_ymd_opt(2024, 5, 14).unwrap();
+        let mut timesheet = Timesheet::new(vec![monday, tuesday]);
+        timesheet.add(monday, "222", "Review, misc", Duration::new(1800, 0));
+        timesheet.add(monday, "111", "Parser", Duration::new(3600, 0));
+        timesheet.add(tuesday, "111", "Parser", Duration::new(5400, 0));
+        timesheet
+    }
+
+    #[test]
+    fn totals_calculated_correctly() {
+        let timesheet = timesheet();
+        assert_eq!(
+            timesheet.day_totals(),
+            [Duration::new(5400, 0), Duration::new(5400, 0)]
+        );
+        assert_eq!(timesheet.total(), Duration::new(10800, 0));
+    }
+
+    #[test]
+    fn csv_rendered_correctly() {
+        assert_eq!(
+            timesheet().to_csv(),
+            "Task ID,name,Mon 05-13,Tue 05-14,total\n\
+            111,Parser,1:00,1:30,2:30\n\
+            222,\"Review, misc\",0:30,,0:30\n\
+            total,,1:30,1:30,3:00\n"
+        );
+    }
+
+    #[test]
+    fn markdown_rendered_correctly() {
+        assert_eq!(
+            timesheet().to_markdown(),
+            "| Task ID | name | Mon 05-13 | Tue 05-14 | total |\n\
+            | --- | --- | --- | --- | --- |\n\
+            | 111 | Parser | 1:00 | 1:30 | 2:30 |\n\
+            | 222 | Review, misc | 0:30 |  | 0:30 |\n\
+            | total |  | 1:30 | 1:30 | 3:00 |\n"
+        );
+    }
+}
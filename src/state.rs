@@ -0,0 +1,224 @@
+//! What ham-cli remembers between runs: the last day synced to Everhour, so
+//! `sync-eh --since-last` knows where to start, and how far `watch` has seen facts
+use chrono::{Days, NaiveDate};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::config;
+use crate::date_range::DateRange;
+use crate::watch::FactMark;
+
+#[derive(Serialize, Deserialize, Default, Debug, PartialEq)]
+pub struct SyncState {
+    /// Days up to and including this one are synced
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_synced_day: Option<NaiveDate>,
+    /// Closed facts up to this one are synced by `watch`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub watch_mark: Option<FactMark>,
+    /// Days `watch` failed to sync, retried on its next change or start
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub watch_failed_days: Vec<NaiveDate>,
+}
+
+/// State file, next to the config file
+pub fn state_path(config_path: Option<String>) -> Result<PathBuf, String> {
+    let config_path = config::resolve_path(config_path)?;
+    Ok(config_path
+        .parent()
+        .unwrap_or(Path::new("."))
+        .join("sync-state.toml"))
+}
+
+impl SyncState {
+    /// Loads state, empty if nothing was synced yet
+    pub fn load(path: &Path) -> Result<SyncState, String> {
+        if !path.exists() {
+            return Ok(SyncState::default());
+        }
+        let raw = fs::read_to_string(path)
+            .map_err(|err| format!("couldn't read {}: {}", path.display(), err))?;
+        toml::from_str(&raw).map_err(|err| format!("couldn't parse {}: {}", path.display(), err))
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), String> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)
+                .map_err(|err| format!("couldn't create {}: {}", parent.display(), err))?;
+        }
+        let raw =
+            toml::to_string(self).map_err(|err| format!("couldn't serialize state: {}", err))?;
+        fs::write(path, raw).map_err(|err| format!("couldn't write {}: {}", path.display(), err))
+    }
+
+    /// Saves how far `watch` got, reloading the file first so days synced
+    /// by `sync-eh` in the meantime aren't forgotten
+    pub fn save_watch_progress(
+        path: &Path,
+        mark: FactMark,
+        failed_days: Vec<NaiveDate>,
+    ) -> Result<(), String> {
+        let mut state = SyncState::load(path)?;
+        state.watch_mark = Some(mark);
+        state.watch_failed_days = failed_days;
+        state.save(path)
+    }
+
+    /// Days after the last synced one up to yesterday - just yesterday if nothing
+    /// was synced yet. None if there's nothing left to sync.
+    pub fn unsynced_range(&self, today: NaiveDate) -> Option<DateRange> {
+        let yesterday = today.pred_opt().unwrap();
+        let first = match self.last_synced_day {
+            Some(day) => day.succ_opt().unwrap(),
+            None => yesterday,
+        };
+        DateRange::new(first, yesterday).ok()
+    }
+
+    /// Records a successful sync of the range. Today isn't over yet, so it never
+    /// counts as synced, and a range leaving a gap after the last synced day doesn't
+    /// move it - the gap would never be synced otherwise. Neither does a sync of
+    /// a single category, facts of the others being left out.
+    pub fn record_sync(
+        &mut self,
+        range: DateRange,
+        today: NaiveDate,
+        category: Option<&str>,
+    ) -> bool {
+        if category.is_some() {
+            return false;
+        }
+        let Some(synced) = range.until(today.pred_opt().unwrap()) else {
+            return false;
+        };
+        let advances = match self.last_synced_day {
+            None => true,
+            Some(day) => synced.first() <= day.succ_opt().unwrap() && synced.last() > day,
+        };
+        if advances {
+            self.last_synced_day = Some(synced.last());
+        }
+        advances
+    }
+
+    /// Oldest unsynced day, if it's more than `days` days ago
+    pub fn overdue_since(&self, today: NaiveDate, days: u64) -> Option<NaiveDate> {
+        let first_unsynced = self.last_synced_day?.succ_opt().unwrap();
+        let limit = today.checked_sub_days(Days::new(days)).unwrap();
+        (first_unsynced < limit).then_some(first_unsynced)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{Local, NaiveDate, TimeZone};
+
+    use crate::date_range::DateRange;
+    use crate::watch::FactMark;
+
+    use super::SyncState;
+
+    fn day(day: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(2024, 5, day).unwrap()
+    }
+
+    fn synced_until(last: u32) -> SyncState {
+        SyncState {
+            last_synced_day: Some(day(last)),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn unsynced_range_ends_yesterday() {
+        assert_eq!(
+            synced_until(13).unsynced_range(day(17)),
+            DateRange::new(day(14), day(16)).ok()
+        );
+        assert_eq!(synced_until(16).unsynced_range(day(17)), None);
+        assert_eq!(
+            SyncState::default().unsynced_range(day(17)),
+            Some(DateRange::day(day(16)))
+        );
+    }
+
+    #[test]
+    fn sync_recorded_up_to_yesterday() {
+        let mut state = synced_until(13);
+
+        assert!(state.record_sync(DateRange::new(day(14), day(17)).unwrap(), day(17), None));
+        assert_eq!(state, synced_until(16));
+        // today only
+        assert!(!state.record_sync(DateRange::day(day(17)), day(17), None));
+        assert_eq!(state, synced_until(16));
+    }
+
+    #[test]
+    fn gap_not_skipped() {
+        let mut state = synced_until(13);
+
+        assert!(!state.record_sync(DateRange::day(day(15)), day(17), None));
+        assert_eq!(state, synced_until(13));
+        // re-syncing older days doesn't move it back either
+        assert!(!state.record_sync(DateRange::new(day(10), day(12)).unwrap(), day(17), None));
+        assert_eq!(state, synced_until(13));
+        assert!(state.record_sync(DateRange::new(day(10), day(15)).unwrap(), day(17), None));
+        assert_eq!(state, synced_until(15));
+    }
+
+    #[test]
+    fn category_sync_not_recorded() {
+        let mut state = synced_until(13);
+
+        assert!(!state.record_sync(DateRange::day(day(14)), day(17), Some("Work")));
+        assert_eq!(state, synced_until(13));
+        assert!(state.record_sync(DateRange::day(day(14)), day(17), None));
+        assert_eq!(state, synced_until(14));
+    }
+
+    #[test]
+    fn overdue_days_found() {
+        assert_eq!(synced_until(13).overdue_since(day(17), 7), None);
+        assert_eq!(synced_until(13).overdue_since(day(24), 7), Some(day(14)));
+        assert_eq!(SyncState::default().overdue_since(day(24), 7), None);
+    }
+
+    #[test]
+    fn state_roundtrips_through_toml() {
+        let state = synced_until(13);
+        let raw = toml::to_string(&state).unwrap();
+
+        assert_eq!(raw, "last_synced_day = \"2024-05-13\"\n");
+        assert_eq!(toml::from_str::<SyncState>(&raw).unwrap(), state);
+    }
+
+    #[test]
+    fn watch_progress_keeps_synced_day() {
+        let path = std::env::temp_dir().join(format!(
+            "ham-cli-{}-watch-progress/sync-state.toml",
+            std::process::id()
+        ));
+        let mark = FactMark {
+            fact_id: 4,
+            end_time: Local.with_ymd_and_hms(2024, 5, 14, 9, 15, 0).unwrap(),
+        };
+        SyncState::save_watch_progress(&path, mark, vec![day(13)]).unwrap();
+        // sync-eh --since-last running meanwhile
+        let mut state = SyncState::load(&path).unwrap();
+        state.last_synced_day = Some(day(15));
+        state.save(&path).unwrap();
+
+        SyncState::save_watch_progress(&path, mark, vec![day(14)]).unwrap();
+
+        assert_eq!(
+            SyncState::load(&path).unwrap(),
+            SyncState {
+                last_synced_day: Some(day(15)),
+                watch_mark: Some(mark),
+                watch_failed_days: vec![day(14)],
+            }
+        );
+        std::fs::remove_file(&path).unwrap();
+    }
+}
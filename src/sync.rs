@@ -0,0 +1,322 @@
+use chrono::NaiveDate;
+use std::collections::HashMap;
+use std::time::Duration;
+
+use crate::aggregation::{get_tasks_with_durations, AggregationOptions, TaskData};
+use crate::backend::{TimeEntry, TimeTrackerBackend};
+use crate::config::Config;
+use crate::date_range::DateRange;
+use crate::utils::{DurationFormatting, RunMode};
+
+/// What has to be done in the tracker for a task on a day
+#[derive(Debug, PartialEq)]
+pub enum SyncAction {
+    /// no record for the task on that day yet
+    Add,
+    /// overwrite the existing record
+    Update { record_id: i64 },
+}
+
+pub struct PlannedRecord {
+    pub day: NaiveDate,
+    /// id of the task (or project) in the tracker
+    pub target_id: String,
+    pub task: TaskData,
+    pub action: SyncAction,
+    /// other records of the task on that day - time in them is counted twice,
+    /// they're reported or, if asked to, removed
+    pub duplicates: Vec<i64>,
+}
+
+/// Everything to sync for a day
+pub struct DayPlan {
+    pub day: NaiveDate,
+    /// ordered by tracker task id
+    pub records: Vec<PlannedRecord>,
+    /// tasks that can't be synced, having no task id
+    pub unresolved: Vec<TaskData>,
+    /// tasks the tracker has no mapping for, by task id
+    pub unmapped: Vec<(String, TaskData)>,
+}
+
+impl DayPlan {
+    /// Time tracked on the day, including tasks that can't be synced
+    pub fn total_duration(&self) -> Duration {
+        self.records
+            .iter()
+            .map(|record| &record.task)
+            .chain(self.unresolved.iter())
+            .chain(self.unmapped.iter().map(|(_, task)| task))
+            .map(|task| task.duration)
+            .sum()
+    }
+}
+
+/// Decides how to sync aggregated tasks of a day, given ids of existing
+/// records by day and tracker task id, and the way tracker task ids are found
+pub fn plan_day(
+    day: NaiveDate,
+    tasks: HashMap<Option<String>, TaskData>,
+    existing: &HashMap<(NaiveDate, String), Vec<i64>>,
+    target_id: impl Fn(&str, &TaskData) -> Option<String>,
+) -> DayPlan {
+    let mut records = vec![];
+    let mut unresolved = vec![];
+    let mut unmapped = vec![];
+    for (task_id, task) in tasks.into_iter() {
+        let Some(task_id) = task_id else {
+            unresolved.push(task);
+            continue;
+        };
+        let Some(target_id) = target_id(&task_id, &task) else {
+            unmapped.push((task_id, task));
+            continue;
+        };
+        let record_ids = existing
+            .get(&(day, target_id.clone()))
+            .cloned()
+            .unwrap_or_default();
+        let action = match record_ids.first() {
+            Some(record_id) => SyncAction::Update {
+                record_id: *record_id,
+            },
+            None => SyncAction::Add,
+        };
+        records.push(PlannedRecord {
+            day,
+            target_id,
+            task,
+            action,
+            duplicates: record_ids.into_iter().skip(1).collect(),
+        });
+    }
+    records.sort_by(|a, b| a.target_id.cmp(&b.target_id));
+    unmapped.sort_by(|a, b| a.0.cmp(&b.0));
+    DayPlan {
+        day,
+        records,
+        unresolved,
+        unmapped,
+    }
+}
+
+/// Syncs tasks of days of the range to the tracker,
+/// telling what's done (or would be done) along the way.
+/// Extra records of a task on a day are only reported unless `delete_duplicates` is set.
+pub async fn sync_days<B: TimeTrackerBackend>(
+    backend: &B,
+    hamster_db: Option<String>,
+    range: DateRange,
+    config: &Config,
+    options: &AggregationOptions,
+    delete_duplicates: bool,
+    run_mode: RunMode,
+) -> Result<(), String> {
+    let user = backend.current_user().await?;
+
+    // ids of existing time records by day/task id pair
+    let mut records_map: HashMap<(NaiveDate, String), Vec<i64>> = HashMap::new();
+    for time_record in backend.time_records(&user, range).await?.into_iter() {
+        // project-level records are always overwritten, no need to track them
+        let Some(task_id) = time_record.task_id else {
+            continue;
+        };
+        records_map
+            .entry((time_record.date, task_id))
+            .or_default()
+            .push(time_record.id);
+    }
+
+    for day in range.days() {
+        println!("Processing day {}", day);
+        let tasks =
+            get_tasks_with_durations(hamster_db.clone(), DateRange::day(day), config, options);
+        let plan = plan_day(day, tasks, &records_map, |task_id, task| {
+            backend.task_id(task_id, task)
+        });
+
+        if let RunMode::Normal = run_mode {
+            if let Some(task_data) = plan.unresolved.first() {
+                return Err(format!(
+                    "Missing task id! ({}, '{}') - add a link to the description \
+                    or a mapping via `ham-cli mappings add`",
+                    task_data.duration.as_hhmm(),
+                    task_data.title.as_deref().unwrap_or("-")
+                ));
+            }
+            if let Some((task_id, task_data)) = plan.unmapped.first() {
+                return Err(format!(
+                    "No {} mapping for task {task_id} ('{}')",
+                    backend.name(),
+                    task_data.title.as_deref().unwrap_or("-")
+                ));
+            }
+        }
+        for (task_id, task_data) in plan.unmapped.iter() {
+            println!(
+                "would fail - no {} mapping for task {task_id} ({}, '{}')",
+                backend.name(),
+                task_data.duration.as_hhmm(),
+                task_data.title.as_deref().unwrap_or("-")
+            );
+        }
+
+        for record in plan.records.iter() {
+            let task_data = &record.task;
+            let target_id = record.target_id.as_str();
+            let data_msg = format!(
+                "{day}: {} seconds ({}) for user {user} on {} {target_id} ({}){}",
+                task_data.duration.as_secs(),
+                task_data.duration.as_hhmm(),
+                if task_data.project { "project" } else { "task" },
+                task_data.title.as_deref().unwrap_or("-"),
+                match task_data.billable {
+                    Some(true) => ", billable",
+                    Some(false) => ", non-billable",
+                    None => "",
+                }
+            );
+            let entry = TimeEntry::new(day, task_data);
+
+            for record_id in record.duplicates.iter() {
+                match (delete_duplicates, run_mode) {
+                    (false, _) => println!(
+                        "duplicate record {record_id} of {target_id} left alone - \
+                        its time is counted twice, use --delete-duplicates to remove it"
+                    ),
+                    (true, RunMode::DryRun) => {
+                        println!("would delete duplicate record {record_id} of {target_id}")
+                    }
+                    (true, RunMode::Normal) => {
+                        println!("deleting duplicate record {record_id} of {target_id}");
+                        backend.delete_time(target_id, *record_id).await?;
+                    }
+                }
+            }
+
+            match (&record.action, run_mode) {
+                (SyncAction::Update { record_id }, RunMode::DryRun) => {
+                    println!("would sync to record {record_id} - {data_msg}")
+                }
+                (SyncAction::Update { record_id }, RunMode::Normal) => {
+                    println!("syncing to record {record_id} - {data_msg}");
+                    backend
+                        .update_time(&user, target_id, *record_id, &entry)
+                        .await?;
+                }
+                (SyncAction::Add, RunMode::DryRun) => {
+                    println!("would add new  record - {data_msg}")
+                }
+                (SyncAction::Add, RunMode::Normal) => {
+                    println!("adding - {data_msg}");
+                    backend.add_time(&user, target_id, &entry).await?;
+                }
+            };
+        }
+
+        let total_duration = plan.total_duration();
+        println!(
+            "Total seconds for day: {} ({})",
+            total_duration.as_secs(),
+            total_duration.as_hhmm()
+        );
+    }
+    println!("{} user id: {user}", backend.name());
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::NaiveDate;
+    use std::collections::HashMap;
+    use std::time::Duration;
+
+    use crate::aggregation::TaskData;
+
+    use super::{plan_day, SyncAction};
+
+    fn task(minutes: u64, project: bool) -> TaskData {
+        TaskData {
+            title: Some(String::from("task")),
+            duration: Duration::new(minutes * 60, 0),
+            comments: vec![],
+            splits: vec![],
+            project,
+            billable: None,
+        }
+    }
+
+    fn everhour_id(task_id: &str, task: &TaskData) -> Option<String> {
+        Some(task.everhour_id(task_id))
+    }
+
+    #[test]
+    fn actions_planned() {
+        let day = NaiveDate::from_ymd_opt(2024, 5, 13).unwrap();
+        let tasks = HashMap::from([
+            (Some(String::from("1")), task(60, false)),
+            (Some(String::from("2")), task(30, false)),
+            (Some(String::from("ev:3")), task(10, true)),
+            (None, task(5, false)),
+        ]);
+        let existing = HashMap::from([((day, String::from("as:2")), vec![42])]);
+
+        let plan = plan_day(day, tasks, &existing, everhour_id);
+
+        assert_eq!(
+            plan.records
+                .iter()
+                .map(|record| (record.target_id.as_str(), &record.action))
+                .collect::<Vec<_>>(),
+            [
+                ("as:1", &SyncAction::Add),
+                ("as:2", &SyncAction::Update { record_id: 42 }),
+                ("ev:3", &SyncAction::Add),
+            ]
+        );
+        assert_eq!(plan.unresolved.len(), 1);
+        assert_eq!(plan.total_duration(), Duration::new(105 * 60, 0));
+    }
+
+    #[test]
+    fn records_of_other_days_ignored() {
+        let day = NaiveDate::from_ymd_opt(2024, 5, 13).unwrap();
+        let other_day = NaiveDate::from_ymd_opt(2024, 5, 14).unwrap();
+        let tasks = HashMap::from([(Some(String::from("1")), task(60, false))]);
+        let existing = HashMap::from([((other_day, String::from("as:1")), vec![42])]);
+
+        let plan = plan_day(day, tasks, &existing, everhour_id);
+
+        assert_eq!(plan.records[0].action, SyncAction::Add);
+    }
+
+    #[test]
+    fn duplicates_set_aside() {
+        let day = NaiveDate::from_ymd_opt(2024, 5, 13).unwrap();
+        let tasks = HashMap::from([(Some(String::from("1")), task(60, false))]);
+        let existing = HashMap::from([((day, String::from("as:1")), vec![42, 43, 44])]);
+
+        let plan = plan_day(day, tasks, &existing, everhour_id);
+
+        assert_eq!(plan.records[0].action, SyncAction::Update { record_id: 42 });
+        assert_eq!(plan.records[0].duplicates, [43, 44]);
+    }
+
+    #[test]
+    fn unmapped_tasks_set_aside() {
+        let day = NaiveDate::from_ymd_opt(2024, 5, 13).unwrap();
+        let tasks = HashMap::from([
+            (Some(String::from("1")), task(60, false)),
+            (Some(String::from("2")), task(30, false)),
+        ]);
+        let mapping = HashMap::from([("1", "PROJ-1")]);
+
+        let plan = plan_day(day, tasks, &HashMap::new(), |task_id, _| {
+            mapping.get(task_id).map(|key| key.to_string())
+        });
+
+        assert_eq!(plan.records.len(), 1);
+        assert_eq!(plan.records[0].target_id, "PROJ-1");
+        assert_eq!(plan.unmapped[0].0, "2");
+    }
+}
@@ -0,0 +1,42 @@
+//! Trackers besides Everhour that `sync --target` can book time to. Each has its
+//! own `[targets.<name>]` config section, mapping task ids to its own ids.
+use serde::de::DeserializeOwned;
+
+pub mod harvest;
+pub mod jira;
+pub mod toggl;
+pub mod webhook;
+
+/// Token from the config, or from the env variable if it's not there
+fn api_token(configured: &Option<String>, env_variable: &str) -> Result<String, String> {
+    match configured {
+        Some(api_token) => Ok(api_token.clone()),
+        None => std::env::var(env_variable)
+            .map_err(|_| format!("API token is neither in the config nor in {env_variable}")),
+    }
+}
+
+/// Sends the request, failing on anything but a success status
+async fn send(request: reqwest::RequestBuilder, what: &str) -> Result<reqwest::Response, String> {
+    let response = request
+        .send()
+        .await
+        .map_err(|err| format!("{what} failed: {err}"))?;
+    let status = response.status();
+    if status.is_success() {
+        return Ok(response);
+    }
+    let body = response.text().await.unwrap_or_default();
+    Err(format!("{what} failed with {status}: {body}"))
+}
+
+async fn send_json<T: DeserializeOwned>(
+    request: reqwest::RequestBuilder,
+    what: &str,
+) -> Result<T, String> {
+    send(request, what)
+        .await?
+        .json()
+        .await
+        .map_err(|err| format!("{what} gave unexpected response: {err}"))
+}
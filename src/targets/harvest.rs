@@ -0,0 +1,224 @@
+//! Harvest time entries, via API v2
+use chrono::NaiveDate;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+use super::{api_token, send, send_json};
+use crate::aggregation::TaskData;
+use crate::backend::{BookedTime, TimeEntry, TimeTrackerBackend};
+use crate::date_range::DateRange;
+
+/// `[targets.harvest]` config section
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct HarvestConfig {
+    #[serde(default = "default_url")]
+    pub url: String,
+    pub account_id: String,
+    /// Taken from HARVEST_API_TOKEN env variable if not set
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub api_token: Option<String>,
+    /// "project_id/task_id" pairs by task id
+    #[serde(default)]
+    pub tasks: BTreeMap<String, String>,
+}
+
+fn default_url() -> String {
+    String::from("https://api.harvestapp.com")
+}
+
+pub struct HarvestBackend {
+    config: HarvestConfig,
+    api_token: String,
+    http: reqwest::Client,
+}
+
+#[derive(Deserialize)]
+struct User {
+    id: i64,
+}
+
+#[derive(Deserialize)]
+struct Reference {
+    id: i64,
+}
+
+#[derive(Deserialize)]
+struct TimeEntries {
+    time_entries: Vec<HarvestEntry>,
+    next_page: Option<u32>,
+}
+
+#[derive(Deserialize)]
+struct HarvestEntry {
+    id: i64,
+    spent_date: NaiveDate,
+    hours: f64,
+    #[serde(default)]
+    notes: Option<String>,
+    project: Reference,
+    task: Reference,
+}
+
+#[derive(Serialize)]
+struct NewEntry {
+    user_id: i64,
+    project_id: i64,
+    task_id: i64,
+    spent_date: NaiveDate,
+    hours: f64,
+    notes: String,
+}
+
+#[derive(Serialize)]
+struct EntryUpdate {
+    hours: f64,
+    notes: String,
+}
+
+fn hours(time: Duration) -> f64 {
+    time.as_secs() as f64 / 3600.0
+}
+
+/// Project and task ids out of "project_id/task_id"
+fn split_task_id(task_id: &str) -> Result<(i64, i64), String> {
+    let invalid = || format!("Harvest task {task_id} should be \"project_id/task_id\"");
+    let (project_id, task_id) = task_id.split_once('/').ok_or_else(invalid)?;
+    Ok((
+        project_id.parse().map_err(|_| invalid())?,
+        task_id.parse().map_err(|_| invalid())?,
+    ))
+}
+
+impl HarvestBackend {
+    pub fn new(config: &HarvestConfig) -> Result<HarvestBackend, String> {
+        Ok(HarvestBackend {
+            config: config.clone(),
+            api_token: api_token(&config.api_token, "HARVEST_API_TOKEN")?,
+            http: reqwest::Client::new(),
+        })
+    }
+
+    fn request(&self, method: reqwest::Method, path: &str) -> reqwest::RequestBuilder {
+        self.http
+            .request(
+                method,
+                format!("{}/v2{path}", self.config.url.trim_end_matches('/')),
+            )
+            .bearer_auth(&self.api_token)
+            .header("Harvest-Account-Id", &self.config.account_id)
+            .header("User-Agent", "ham-cli")
+    }
+}
+
+impl TimeTrackerBackend for HarvestBackend {
+    type User = i64;
+
+    fn name(&self) -> &str {
+        "Harvest"
+    }
+
+    fn task_id(&self, task_id: &str, _task: &TaskData) -> Option<String> {
+        self.config.tasks.get(task_id).cloned()
+    }
+
+    async fn current_user(&self) -> Result<i64, String> {
+        let user: User = send_json(
+            self.request(reqwest::Method::GET, "/users/me"),
+            "Getting Harvest user",
+        )
+        .await?;
+        Ok(user.id)
+    }
+
+    async fn time_records(&self, user: &i64, range: DateRange) -> Result<Vec<BookedTime>, String> {
+        let mut records = vec![];
+        let mut page = Some(1);
+        while let Some(current_page) = page {
+            let entries: TimeEntries = send_json(
+                self.request(reqwest::Method::GET, "/time_entries").query(&[
+                    ("user_id", user.to_string()),
+                    ("from", range.first().to_string()),
+                    ("to", range.last().to_string()),
+                    ("page", current_page.to_string()),
+                ]),
+                "Getting Harvest time entries",
+            )
+            .await?;
+            records.extend(entries.time_entries.into_iter().map(|entry| BookedTime {
+                id: entry.id,
+                date: entry.spent_date,
+                task_id: Some(format!("{}/{}", entry.project.id, entry.task.id)),
+                task_name: None,
+                time: Duration::new((entry.hours * 3600.0).round() as u64, 0),
+                comment: entry.notes,
+            }));
+            page = entries.next_page;
+        }
+        Ok(records)
+    }
+
+    async fn add_time(&self, user: &i64, task_id: &str, entry: &TimeEntry) -> Result<(), String> {
+        let (project_id, harvest_task_id) = split_task_id(task_id)?;
+        send(
+            self.request(reqwest::Method::POST, "/time_entries")
+                .json(&NewEntry {
+                    user_id: *user,
+                    project_id,
+                    task_id: harvest_task_id,
+                    spent_date: entry.date,
+                    hours: hours(entry.time),
+                    notes: entry.comment.clone(),
+                }),
+            &format!("Adding time to {task_id}"),
+        )
+        .await?;
+        Ok(())
+    }
+
+    async fn update_time(
+        &self,
+        _user: &i64,
+        task_id: &str,
+        record_id: i64,
+        entry: &TimeEntry,
+    ) -> Result<(), String> {
+        send(
+            self.request(
+                reqwest::Method::PATCH,
+                &format!("/time_entries/{record_id}"),
+            )
+            .json(&EntryUpdate {
+                hours: hours(entry.time),
+                notes: entry.comment.clone(),
+            }),
+            &format!("Updating time entry {record_id} of {task_id}"),
+        )
+        .await?;
+        Ok(())
+    }
+
+    async fn delete_time(&self, task_id: &str, record_id: i64) -> Result<(), String> {
+        send(
+            self.request(
+                reqwest::Method::DELETE,
+                &format!("/time_entries/{record_id}"),
+            ),
+            &format!("Deleting time entry {record_id} of {task_id}"),
+        )
+        .await?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::split_task_id;
+
+    #[test]
+    fn task_ids_split() {
+        assert_eq!(split_task_id("10/20"), Ok((10, 20)));
+        assert!(split_task_id("10").is_err());
+        assert!(split_task_id("ten/20").is_err());
+    }
+}
@@ -0,0 +1,206 @@
+//! Jira worklogs, via REST API v2 (plain text comments)
+use chrono::{DateTime, Local, NaiveTime};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use std::time::Duration;
+
+use super::{api_token, send, send_json};
+use crate::aggregation::TaskData;
+use crate::backend::{BookedTime, TimeEntry, TimeTrackerBackend};
+use crate::date_range::DateRange;
+
+const STARTED_FORMAT: &str = "%Y-%m-%dT%H:%M:%S%.3f%z";
+
+/// `[targets.jira]` config section
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct JiraConfig {
+    /// e.g. "https://example.atlassian.net"
+    pub url: String,
+    /// Account email, used with the API token
+    pub email: String,
+    /// Taken from JIRA_API_TOKEN env variable if not set
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub api_token: Option<String>,
+    /// Issue keys by task id
+    #[serde(default)]
+    pub tasks: BTreeMap<String, String>,
+}
+
+pub struct JiraBackend {
+    config: JiraConfig,
+    api_token: String,
+    http: reqwest::Client,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Account {
+    account_id: String,
+}
+
+#[derive(Deserialize)]
+struct Worklogs {
+    worklogs: Vec<Worklog>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Worklog {
+    id: String,
+    author: Account,
+    started: String,
+    time_spent_seconds: u64,
+    #[serde(default)]
+    comment: Option<String>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct WorklogUpdate {
+    started: String,
+    time_spent_seconds: u64,
+    comment: String,
+}
+
+impl WorklogUpdate {
+    fn new(entry: &TimeEntry) -> WorklogUpdate {
+        // worklogs need a start time - days are booked from the start of working hours
+        let started = entry
+            .date
+            .and_time(NaiveTime::from_hms_opt(9, 0, 0).unwrap())
+            .and_local_timezone(Local)
+            .unwrap();
+        WorklogUpdate {
+            started: started.format(STARTED_FORMAT).to_string(),
+            time_spent_seconds: entry.time.as_secs(),
+            comment: entry.comment.clone(),
+        }
+    }
+}
+
+impl JiraBackend {
+    pub fn new(config: &JiraConfig) -> Result<JiraBackend, String> {
+        Ok(JiraBackend {
+            config: config.clone(),
+            api_token: api_token(&config.api_token, "JIRA_API_TOKEN")?,
+            http: reqwest::Client::new(),
+        })
+    }
+
+    fn request(&self, method: reqwest::Method, path: &str) -> reqwest::RequestBuilder {
+        self.http
+            .request(
+                method,
+                format!("{}/rest/api/2{path}", self.config.url.trim_end_matches('/')),
+            )
+            .basic_auth(&self.config.email, Some(&self.api_token))
+    }
+}
+
+impl TimeTrackerBackend for JiraBackend {
+    type User = String;
+
+    fn name(&self) -> &str {
+        "Jira"
+    }
+
+    fn task_id(&self, task_id: &str, _task: &TaskData) -> Option<String> {
+        self.config.tasks.get(task_id).cloned()
+    }
+
+    async fn current_user(&self) -> Result<String, String> {
+        let account: Account = send_json(
+            self.request(reqwest::Method::GET, "/myself"),
+            "Getting Jira user",
+        )
+        .await?;
+        Ok(account.account_id)
+    }
+
+    async fn time_records(
+        &self,
+        user: &String,
+        range: DateRange,
+    ) -> Result<Vec<BookedTime>, String> {
+        // worklogs can only be listed by issue - mapped issues are all we care about anyway
+        let issue_keys: BTreeSet<&String> = self.config.tasks.values().collect();
+        let mut records = vec![];
+        for issue_key in issue_keys {
+            let worklogs: Worklogs = send_json(
+                self.request(reqwest::Method::GET, &format!("/issue/{issue_key}/worklog")),
+                &format!("Getting worklogs of {issue_key}"),
+            )
+            .await?;
+            for worklog in worklogs.worklogs {
+                if &worklog.author.account_id != user {
+                    continue;
+                }
+                let date = DateTime::parse_from_str(&worklog.started, STARTED_FORMAT)
+                    .map_err(|err| format!("Bad worklog start {}: {err}", worklog.started))?
+                    .with_timezone(&Local)
+                    .date_naive();
+                if !range.contains(date) {
+                    continue;
+                }
+                records.push(BookedTime {
+                    id: worklog
+                        .id
+                        .parse()
+                        .map_err(|_| format!("Unexpected worklog id {}", worklog.id))?,
+                    date,
+                    task_id: Some(issue_key.clone()),
+                    task_name: None,
+                    time: Duration::new(worklog.time_spent_seconds, 0),
+                    comment: worklog.comment,
+                });
+            }
+        }
+        Ok(records)
+    }
+
+    async fn add_time(
+        &self,
+        _user: &String,
+        task_id: &str,
+        entry: &TimeEntry,
+    ) -> Result<(), String> {
+        send(
+            self.request(reqwest::Method::POST, &format!("/issue/{task_id}/worklog"))
+                .json(&WorklogUpdate::new(entry)),
+            &format!("Adding worklog to {task_id}"),
+        )
+        .await?;
+        Ok(())
+    }
+
+    async fn update_time(
+        &self,
+        _user: &String,
+        task_id: &str,
+        record_id: i64,
+        entry: &TimeEntry,
+    ) -> Result<(), String> {
+        send(
+            self.request(
+                reqwest::Method::PUT,
+                &format!("/issue/{task_id}/worklog/{record_id}"),
+            )
+            .json(&WorklogUpdate::new(entry)),
+            &format!("Updating worklog {record_id} of {task_id}"),
+        )
+        .await?;
+        Ok(())
+    }
+
+    async fn delete_time(&self, task_id: &str, record_id: i64) -> Result<(), String> {
+        send(
+            self.request(
+                reqwest::Method::DELETE,
+                &format!("/issue/{task_id}/worklog/{record_id}"),
+            ),
+            &format!("Deleting worklog {record_id} of {task_id}"),
+        )
+        .await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,195 @@
+//! Toggl Track time entries, via API v9
+use chrono::{DateTime, FixedOffset, Local, NaiveTime};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+use super::{api_token, send, send_json};
+use crate::aggregation::TaskData;
+use crate::backend::{BookedTime, TimeEntry, TimeTrackerBackend};
+use crate::date_range::DateRange;
+
+/// `[targets.toggl]` config section
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct TogglConfig {
+    #[serde(default = "default_url")]
+    pub url: String,
+    pub workspace_id: i64,
+    /// Taken from TOGGL_API_TOKEN env variable if not set
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub api_token: Option<String>,
+    /// Project ids by task id
+    #[serde(default)]
+    pub tasks: BTreeMap<String, String>,
+}
+
+fn default_url() -> String {
+    String::from("https://api.track.toggl.com")
+}
+
+pub struct TogglBackend {
+    config: TogglConfig,
+    api_token: String,
+    http: reqwest::Client,
+}
+
+#[derive(Deserialize)]
+struct User {
+    id: i64,
+}
+
+#[derive(Deserialize)]
+struct TogglEntry {
+    id: i64,
+    workspace_id: i64,
+    #[serde(default)]
+    project_id: Option<i64>,
+    start: DateTime<FixedOffset>,
+    /// negative for the running entry
+    duration: i64,
+    #[serde(default)]
+    description: Option<String>,
+}
+
+#[derive(Serialize)]
+struct EntryUpdate {
+    created_with: &'static str,
+    workspace_id: i64,
+    project_id: i64,
+    start: String,
+    duration: i64,
+    /// Toggl has a single line per entry - the task title goes there
+    description: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    billable: Option<bool>,
+}
+
+impl TogglBackend {
+    pub fn new(config: &TogglConfig) -> Result<TogglBackend, String> {
+        Ok(TogglBackend {
+            config: config.clone(),
+            api_token: api_token(&config.api_token, "TOGGL_API_TOKEN")?,
+            http: reqwest::Client::new(),
+        })
+    }
+
+    fn request(&self, method: reqwest::Method, path: &str) -> reqwest::RequestBuilder {
+        self.http
+            .request(
+                method,
+                format!("{}/api/v9{path}", self.config.url.trim_end_matches('/')),
+            )
+            .basic_auth(&self.api_token, Some("api_token"))
+    }
+
+    fn entry_update(&self, task_id: &str, entry: &TimeEntry) -> Result<EntryUpdate, String> {
+        // entries need a start time - days are booked from the start of working hours
+        let start = entry
+            .date
+            .and_time(NaiveTime::from_hms_opt(9, 0, 0).unwrap())
+            .and_local_timezone(Local)
+            .unwrap();
+        Ok(EntryUpdate {
+            created_with: "ham-cli",
+            workspace_id: self.config.workspace_id,
+            project_id: task_id
+                .parse()
+                .map_err(|_| format!("Toggl project id {task_id} should be a number"))?,
+            start: start.to_rfc3339(),
+            duration: entry.time.as_secs() as i64,
+            description: entry.title.clone(),
+            billable: entry.billable,
+        })
+    }
+
+    fn entries_path(&self) -> String {
+        format!("/workspaces/{}/time_entries", self.config.workspace_id)
+    }
+}
+
+impl TimeTrackerBackend for TogglBackend {
+    type User = i64;
+
+    fn name(&self) -> &str {
+        "Toggl"
+    }
+
+    fn task_id(&self, task_id: &str, _task: &TaskData) -> Option<String> {
+        self.config.tasks.get(task_id).cloned()
+    }
+
+    async fn current_user(&self) -> Result<i64, String> {
+        let user: User = send_json(
+            self.request(reqwest::Method::GET, "/me"),
+            "Getting Toggl user",
+        )
+        .await?;
+        Ok(user.id)
+    }
+
+    async fn time_records(&self, _user: &i64, range: DateRange) -> Result<Vec<BookedTime>, String> {
+        let entries: Vec<TogglEntry> = send_json(
+            self.request(reqwest::Method::GET, "/me/time_entries")
+                .query(&[
+                    ("start_date", range.first().to_string()),
+                    // end date is exclusive
+                    ("end_date", range.end().to_string()),
+                ]),
+            "Getting Toggl time entries",
+        )
+        .await?;
+        Ok(entries
+            .into_iter()
+            .filter(|entry| entry.workspace_id == self.config.workspace_id)
+            .map(|entry| BookedTime {
+                id: entry.id,
+                date: entry.start.with_timezone(&Local).date_naive(),
+                task_id: entry.project_id.map(|project_id| project_id.to_string()),
+                task_name: entry.description.clone(),
+                time: Duration::new(entry.duration.max(0) as u64, 0),
+                comment: entry.description,
+            })
+            .collect())
+    }
+
+    async fn add_time(&self, _user: &i64, task_id: &str, entry: &TimeEntry) -> Result<(), String> {
+        send(
+            self.request(reqwest::Method::POST, &self.entries_path())
+                .json(&self.entry_update(task_id, entry)?),
+            &format!("Adding time to {task_id}"),
+        )
+        .await?;
+        Ok(())
+    }
+
+    async fn update_time(
+        &self,
+        _user: &i64,
+        task_id: &str,
+        record_id: i64,
+        entry: &TimeEntry,
+    ) -> Result<(), String> {
+        send(
+            self.request(
+                reqwest::Method::PUT,
+                &format!("{}/{record_id}", self.entries_path()),
+            )
+            .json(&self.entry_update(task_id, entry)?),
+            &format!("Updating time entry {record_id} of {task_id}"),
+        )
+        .await?;
+        Ok(())
+    }
+
+    async fn delete_time(&self, task_id: &str, record_id: i64) -> Result<(), String> {
+        send(
+            self.request(
+                reqwest::Method::DELETE,
+                &format!("{}/{record_id}", self.entries_path()),
+            ),
+            &format!("Deleting time entry {record_id} of {task_id}"),
+        )
+        .await?;
+        Ok(())
+    }
+}
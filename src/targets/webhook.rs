@@ -0,0 +1,125 @@
+//! Generic target: every synced task of a day is POSTed as JSON to a URL.
+//! There's nothing to list or delete, so the receiver is expected to upsert
+//! by day and task id.
+use chrono::NaiveDate;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+use super::send;
+use crate::aggregation::TaskData;
+use crate::backend::{BookedTime, TimeEntry, TimeTrackerBackend};
+use crate::date_range::DateRange;
+
+/// `[targets.webhook]` config section
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct WebhookConfig {
+    pub url: String,
+    /// Sent along with every request, e.g. for authorization
+    #[serde(default)]
+    pub headers: BTreeMap<String, String>,
+    /// Passed to the receiver as is
+    #[serde(default = "default_user")]
+    pub user: String,
+    /// Ids to send by task id - task ids are sent as they are if not mapped
+    #[serde(default)]
+    pub tasks: BTreeMap<String, String>,
+}
+
+fn default_user() -> String {
+    String::from("-")
+}
+
+pub struct WebhookBackend {
+    config: WebhookConfig,
+    http: reqwest::Client,
+}
+
+#[derive(Serialize)]
+struct Payload<'a> {
+    user: &'a str,
+    date: NaiveDate,
+    task_id: &'a str,
+    title: &'a str,
+    /// seconds
+    time: u64,
+    comment: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    billable: Option<bool>,
+}
+
+impl WebhookBackend {
+    pub fn new(config: &WebhookConfig) -> WebhookBackend {
+        WebhookBackend {
+            config: config.clone(),
+            http: reqwest::Client::new(),
+        }
+    }
+}
+
+impl TimeTrackerBackend for WebhookBackend {
+    type User = String;
+
+    fn name(&self) -> &str {
+        "webhook"
+    }
+
+    fn task_id(&self, task_id: &str, _task: &TaskData) -> Option<String> {
+        Some(
+            self.config
+                .tasks
+                .get(task_id)
+                .cloned()
+                .unwrap_or(task_id.to_string()),
+        )
+    }
+
+    async fn current_user(&self) -> Result<String, String> {
+        Ok(self.config.user.clone())
+    }
+
+    async fn time_records(
+        &self,
+        _user: &String,
+        _range: DateRange,
+    ) -> Result<Vec<BookedTime>, String> {
+        Ok(vec![])
+    }
+
+    async fn add_time(
+        &self,
+        user: &String,
+        task_id: &str,
+        entry: &TimeEntry,
+    ) -> Result<(), String> {
+        let mut request = self.http.post(&self.config.url).json(&Payload {
+            user,
+            date: entry.date,
+            task_id,
+            title: &entry.title,
+            time: entry.time.as_secs(),
+            comment: &entry.comment,
+            billable: entry.billable,
+        });
+        for (name, value) in self.config.headers.iter() {
+            request = request.header(name, value);
+        }
+        send(request, &format!("Sending {task_id} to the webhook")).await?;
+        Ok(())
+    }
+
+    async fn update_time(
+        &self,
+        user: &String,
+        task_id: &str,
+        _record_id: i64,
+        entry: &TimeEntry,
+    ) -> Result<(), String> {
+        self.add_time(user, task_id, entry).await
+    }
+
+    async fn delete_time(&self, task_id: &str, record_id: i64) -> Result<(), String> {
+        Err(format!(
+            "Webhook can't delete record {record_id} of {task_id}"
+        ))
+    }
+}
@@ -1,12 +1,189 @@
-use chrono::{Datelike, Days, NaiveDate};
+use chrono::{
+    Datelike, Days, Local, Months, NaiveDate, NaiveDateTime, NaiveTime, TimeDelta, Weekday,
+};
 use markdown::mdast::{Link, Node, Text};
 use std::time::Duration;
 
+use crate::date_range::DateRange;
+
+/// Whether commands change anything or only tell what they would do
+#[derive(Default, Clone, Copy)]
+pub enum RunMode {
+    #[default]
+    Normal,
+    DryRun,
+}
+
 pub fn week_start(date: NaiveDate) -> NaiveDate {
-    date.checked_sub_days(Days::new(date.weekday().num_days_from_monday() as u64))
+    week_start_on(date, Weekday::Mon)
+}
+
+/// Start of the week, for weeks starting on `first_day`
+pub fn week_start_on(date: NaiveDate, first_day: Weekday) -> NaiveDate {
+    date.checked_sub_days(Days::new(date.weekday().days_since(first_day) as u64))
+        .unwrap()
+}
+
+pub fn month_start(date: NaiveDate) -> NaiveDate {
+    date.with_day(1).unwrap()
+}
+
+/// Last day of the month
+pub fn month_end(date: NaiveDate) -> NaiveDate {
+    month_start(date)
+        .checked_add_months(Months::new(1))
+        .unwrap()
+        .pred_opt()
         .unwrap()
 }
 
+/// Calendar period relative to today
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Period {
+    ThisWeek,
+    LastWeek,
+    ThisMonth,
+    LastMonth,
+}
+
+impl Period {
+    /// Days of the period - current periods end today
+    pub fn range(&self, today: NaiveDate, first_day: Weekday) -> DateRange {
+        let (first, last) = match self {
+            Period::ThisWeek => (week_start_on(today, first_day), today),
+            Period::LastWeek => {
+                let this_week = week_start_on(today, first_day);
+                (
+                    this_week.checked_sub_days(Days::new(7)).unwrap(),
+                    this_week.pred_opt().unwrap(),
+                )
+            }
+            Period::ThisMonth => (month_start(today), today),
+            Period::LastMonth => {
+                let last_day = month_start(today).pred_opt().unwrap();
+                (month_start(last_day), last_day)
+            }
+        };
+        DateRange::new(first, last).unwrap()
+    }
+}
+
+/// Time back from today, like "3d", "2w" or "1m"
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Since {
+    Days(u32),
+    Weeks(u32),
+    Months(u32),
+}
+
+impl Since {
+    pub fn start(&self, today: NaiveDate) -> NaiveDate {
+        match self {
+            Since::Days(days) => today.checked_sub_days(Days::new(*days as u64)),
+            Since::Weeks(weeks) => today.checked_sub_days(Days::new(*weeks as u64 * 7)),
+            Since::Months(months) => today.checked_sub_months(Months::new(*months)),
+        }
+        .unwrap()
+    }
+}
+
+/// Parses "3d", "2w" or "1m" - days, weeks or months back from today, up to
+/// a hundred years, so the start can always be found
+pub fn parse_since(since: &str) -> Result<Since, String> {
+    let invalid = || format!("'{since}' is not a period, expected e.g. 3d, 2w or 1m");
+    let (unit_at, _) = since.char_indices().last().ok_or_else(invalid)?;
+    let (count, unit) = since.split_at(unit_at);
+    let count: u32 = count.parse().map_err(|_| invalid())?;
+    let (period, limit) = match unit {
+        "d" => (Since::Days(count), 100 * 366),
+        "w" => (Since::Weeks(count), 100 * 53),
+        "m" => (Since::Months(count), 100 * 12),
+        _ => return Err(invalid()),
+    };
+    if count > limit {
+        return Err(format!("'{since}' goes back more than a hundred years"));
+    }
+    Ok(period)
+}
+
+/// Parses "YYYY-MM" into the first day of that month
+pub fn parse_month(month: &str) -> Result<NaiveDate, String> {
+    NaiveDate::parse_from_str(&format!("{month}-01"), "%Y-%m-%d")
+        .map_err(|_| format!("'{month}' is not a month, expected YYYY-MM"))
+}
+
+/// Parses a positive number of hours, like "10" or "1.5"
+pub fn parse_hours(hours: &str) -> Result<f64, String> {
+    // NaN and infinity fail the checks as well, and so do hours too large for a duration
+    match hours.parse::<f64>() {
+        Ok(value) if value > 0.0 && Duration::try_from_secs_f64(value * 3600.0).is_ok() => {
+            Ok(value)
+        }
+        _ => Err(format!("'{hours}' is not a positive number of hours")),
+    }
+}
+
+/// Parses "HH:MM" time of day
+pub fn parse_time(time: &str) -> Result<NaiveTime, String> {
+    NaiveTime::parse_from_str(time, "%H:%M")
+        .map_err(|_| format!("'{time}' is not a time, expected HH:MM"))
+}
+
+/// Parses "YYYY-MM-DD HH:MM", or just "HH:MM" for today
+pub fn parse_datetime(datetime: &str) -> Result<NaiveDateTime, String> {
+    NaiveDateTime::parse_from_str(datetime, "%Y-%m-%d %H:%M")
+        .or_else(|_| parse_time(datetime).map(|time| Local::now().date_naive().and_time(time)))
+        .map_err(|_| format!("'{datetime}' is not a time, expected YYYY-MM-DD HH:MM or HH:MM"))
+}
+
+/// Parses Hamster's "activity@category" into activity and category
+pub fn parse_activity(activity: &str) -> Result<(String, String), String> {
+    match activity.rsplit_once('@') {
+        Some((name, category)) if !name.trim().is_empty() && !category.trim().is_empty() => {
+            Ok((name.trim().to_string(), category.trim().to_string()))
+        }
+        _ => Err(format!(
+            "'{activity}' is not an activity, expected activity@category"
+        )),
+    }
+}
+
+/// Formats time difference as +h:mm or -h:mm
+pub fn signed_hhmm(delta: TimeDelta) -> String {
+    let sign = if delta < TimeDelta::zero() { "-" } else { "+" };
+    format!("{sign}{}", delta.abs().to_std().unwrap().as_hhmm())
+}
+
+/// New file in the temp dir, readable by the user only. The name is random, and an
+/// existing file is never opened, so nobody can plant a file or symlink there beforehand.
+pub fn create_private_file(
+    prefix: &str,
+    extension: &str,
+) -> Result<(std::path::PathBuf, std::fs::File), String> {
+    use std::hash::{BuildHasher, Hasher};
+
+    for _ in 0..16 {
+        // randomly keyed by the standard library, so it's different every time
+        let random = std::collections::hash_map::RandomState::new()
+            .build_hasher()
+            .finish();
+        let path = std::env::temp_dir().join(format!("{prefix}-{random:016x}.{extension}"));
+        let mut options = std::fs::OpenOptions::new();
+        options.write(true).create_new(true);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::OpenOptionsExt;
+            options.mode(0o600);
+        }
+        match options.open(&path) {
+            Ok(file) => return Ok((path, file)),
+            Err(err) if err.kind() == std::io::ErrorKind::AlreadyExists => continue,
+            Err(err) => return Err(format!("couldn't create {}: {err}", path.display())),
+        }
+    }
+    Err(String::from("couldn't create a temporary file"))
+}
+
 pub trait DurationFormatting {
     fn duration_minutes(&self) -> u64;
     fn as_hhmm(&self) -> String {
@@ -26,15 +203,6 @@ impl DurationFormatting for Duration {
 
 pub trait MarkdownProcessing {
     fn flatten_tree(&self) -> Vec<&Node>;
-    fn links(&self) -> Vec<&Link> {
-        self.flatten_tree()
-            .into_iter()
-            .filter_map(|node| match node {
-                Node::Link(link) => Some(link),
-                _ => None,
-            })
-            .collect()
-    }
     fn texts(&self) -> Vec<&Text> {
         self.flatten_tree()
             .into_iter()
@@ -63,13 +231,17 @@ pub trait LinkText {
 }
 
 impl LinkText for Link {
+    /// Text of the link, with emphasis, inline code etc. rendered as plain text
     fn text(&self) -> String {
         self.children
             .iter()
-            .fold(String::new(), |acc, node| match node {
-                Node::Text(text) => acc + &String::from(&text.value),
-                _ => String::new(),
+            .flat_map(|node| node.flatten_tree())
+            .filter_map(|node| match node {
+                Node::Text(text) => Some(text.value.as_str()),
+                Node::InlineCode(code) => Some(code.value.as_str()),
+                _ => None,
             })
+            .collect()
     }
 }
 
@@ -87,22 +259,80 @@ pub fn unique_lines(lines: Vec<String>) -> Vec<String> {
         last_line = line;
     }
 
-    unique.sort_by_key(|(index, _)| index.clone());
+    unique.sort_by_key(|(index, _)| *index);
     unique.into_iter().map(|(_, line)| line).collect()
 }
 
+/// Joins fields into a CSV line, quoting them where needed
+pub fn csv_line(fields: &[String]) -> String {
+    fields
+        .iter()
+        .map(|field| {
+            if field.contains([',', '"', '\n', '\r']) {
+                format!("\"{}\"", field.replace('"', "\"\""))
+            } else {
+                field.clone()
+            }
+        })
+        .collect::<Vec<String>>()
+        .join(",")
+}
+
+/// Splits CSV text into records of fields - quoted fields may contain commas,
+/// quotes (doubled) and line breaks
+pub fn parse_csv(text: &str) -> Vec<Vec<String>> {
+    let mut records = vec![];
+    let mut record: Vec<String> = vec![];
+    let mut field = String::new();
+    let mut quoted = false;
+    let mut characters = text.chars().peekable();
+    while let Some(character) = characters.next() {
+        match (quoted, character) {
+            (true, '"') if characters.peek() == Some(&'"') => {
+                field.push('"');
+                characters.next();
+            }
+            (true, '"') => quoted = false,
+            (true, _) => field.push(character),
+            (false, '"') => quoted = true,
+            (false, ',') => record.push(std::mem::take(&mut field)),
+            (false, '\r') => {}
+            (false, '\n') => {
+                record.push(std::mem::take(&mut field));
+                records.push(std::mem::take(&mut record));
+            }
+            (false, _) => field.push(character),
+        }
+    }
+    if !field.is_empty() || !record.is_empty() {
+        record.push(field);
+        records.push(record);
+    }
+    records
+}
+
 #[cfg(test)]
 mod tests {
     use std::time::Duration;
 
-    use chrono::NaiveDate;
+    use chrono::{NaiveDate, TimeDelta, Weekday};
     use markdown::ParseOptions;
 
-    use crate::utils::{unique_lines, DurationFormatting, MarkdownProcessing};
+    use markdown::mdast::Node;
+
+    use crate::utils::{
+        csv_line, parse_csv, unique_lines, DurationFormatting, LinkText, MarkdownProcessing,
+    };
+
+    use crate::date_range::DateRange;
 
-    use super::week_start;
+    use super::{
+        month_end, month_start, parse_activity, parse_datetime, parse_hours, parse_month,
+        parse_since, signed_hhmm, week_start, week_start_on, Period, Since,
+    };
 
     #[test]
+    #[allow(clippy::zero_prefixed_literal)]
     fn week_start_works() {
         assert_eq!(
             week_start(NaiveDate::from_ymd_opt(2024, 04, 26).unwrap()),
@@ -110,6 +340,153 @@ mod tests {
         )
     }
 
+    #[test]
+    fn month_bounds_work() {
+        let date = NaiveDate::from_ymd_opt(2024, 2, 14).unwrap();
+        assert_eq!(
+            month_start(date),
+            NaiveDate::from_ymd_opt(2024, 2, 1).unwrap()
+        );
+        assert_eq!(
+            month_end(date),
+            NaiveDate::from_ymd_opt(2024, 2, 29).unwrap()
+        );
+        assert_eq!(
+            month_end(NaiveDate::from_ymd_opt(2024, 12, 31).unwrap()),
+            NaiveDate::from_ymd_opt(2024, 12, 31).unwrap()
+        );
+    }
+
+    #[test]
+    fn week_start_configurable() {
+        // Friday
+        let date = NaiveDate::from_ymd_opt(2024, 4, 26).unwrap();
+        assert_eq!(
+            week_start_on(date, Weekday::Sun),
+            NaiveDate::from_ymd_opt(2024, 4, 21).unwrap()
+        );
+        assert_eq!(week_start_on(date, Weekday::Fri), date);
+        assert_eq!(
+            week_start_on(date, Weekday::Sat),
+            NaiveDate::from_ymd_opt(2024, 4, 20).unwrap()
+        );
+    }
+
+    #[test]
+    fn period_days() {
+        // Wednesday
+        let today = NaiveDate::from_ymd_opt(2024, 3, 6).unwrap();
+        let day = |month, day| NaiveDate::from_ymd_opt(2024, month, day).unwrap();
+        assert_eq!(
+            Period::ThisWeek.range(today, Weekday::Mon),
+            DateRange::new(day(3, 4), today).unwrap()
+        );
+        assert_eq!(
+            Period::LastWeek.range(today, Weekday::Mon),
+            DateRange::new(day(2, 26), day(3, 3)).unwrap()
+        );
+        assert_eq!(
+            Period::LastWeek.range(today, Weekday::Sun),
+            DateRange::new(day(2, 25), day(3, 2)).unwrap()
+        );
+        assert_eq!(
+            Period::ThisMonth.range(today, Weekday::Mon),
+            DateRange::new(day(3, 1), today).unwrap()
+        );
+        assert_eq!(
+            Period::LastMonth.range(today, Weekday::Mon),
+            DateRange::new(day(2, 1), day(2, 29)).unwrap()
+        );
+    }
+
+    #[test]
+    fn parse_since_works() {
+        let today = NaiveDate::from_ymd_opt(2024, 3, 31).unwrap();
+        assert_eq!(parse_since("3d"), Ok(Since::Days(3)));
+        assert_eq!(
+            parse_since("3d").unwrap().start(today),
+            NaiveDate::from_ymd_opt(2024, 3, 28).unwrap()
+        );
+        assert_eq!(
+            parse_since("2w").unwrap().start(today),
+            NaiveDate::from_ymd_opt(2024, 3, 17).unwrap()
+        );
+        assert_eq!(
+            parse_since("1m").unwrap().start(today),
+            NaiveDate::from_ymd_opt(2024, 2, 29).unwrap()
+        );
+        assert!(parse_since("").is_err());
+        assert!(parse_since("d").is_err());
+        assert!(parse_since("3y").is_err());
+        assert!(parse_since("-3d").is_err());
+        assert_eq!(
+            parse_since("100000000d"),
+            Err(String::from(
+                "'100000000d' goes back more than a hundred years"
+            ))
+        );
+        assert!(parse_since("1200m").unwrap().start(today) < today);
+    }
+
+    #[test]
+    fn parse_activity_works() {
+        assert_eq!(
+            parse_activity("code review@Work").unwrap(),
+            (String::from("code review"), String::from("Work"))
+        );
+        assert_eq!(
+            parse_activity("mail@home@Personal").unwrap(),
+            (String::from("mail@home"), String::from("Personal"))
+        );
+        assert!(parse_activity("coding").is_err());
+        assert!(parse_activity("coding@").is_err());
+    }
+
+    #[test]
+    fn parse_datetime_works() {
+        assert_eq!(
+            parse_datetime("2024-05-13 09:30").unwrap(),
+            NaiveDate::from_ymd_opt(2024, 5, 13)
+                .unwrap()
+                .and_hms_opt(9, 30, 0)
+                .unwrap()
+        );
+        assert_eq!(
+            parse_datetime("17:45").unwrap().format("%H:%M").to_string(),
+            "17:45"
+        );
+        assert!(parse_datetime("2024-05-13").is_err());
+    }
+
+    #[test]
+    fn parse_month_works() {
+        assert_eq!(
+            parse_month("2024-05"),
+            Ok(NaiveDate::from_ymd_opt(2024, 5, 1).unwrap())
+        );
+        assert!(parse_month("2024-13").is_err());
+        assert!(parse_month("May").is_err());
+    }
+
+    #[test]
+    fn parse_hours_works() {
+        assert_eq!(parse_hours("10"), Ok(10.0));
+        assert_eq!(parse_hours("1.5"), Ok(1.5));
+        assert!(parse_hours("0").is_err());
+        assert!(parse_hours("-2").is_err());
+        assert!(parse_hours("NaN").is_err());
+        assert!(parse_hours("inf").is_err());
+        assert!(parse_hours("1e300").is_err());
+        assert!(parse_hours("ten").is_err());
+    }
+
+    #[test]
+    fn signed_hhmm_works() {
+        assert_eq!(signed_hhmm(TimeDelta::minutes(90)), "+1:30");
+        assert_eq!(signed_hhmm(TimeDelta::minutes(-45)), "-0:45");
+        assert_eq!(signed_hhmm(TimeDelta::zero()), "+0:00");
+    }
+
     #[test]
     fn duration_as_hhmm_works() {
         assert_eq!(Duration::new(0, 0).as_hhmm(), String::from("0:00"));
@@ -161,4 +538,69 @@ mod tests {
             vec!["def", "abc", "xyz", "zyx"]
         )
     }
+
+    fn link_texts(markdown: &str) -> Vec<String> {
+        let root_node = markdown::to_mdast(markdown, &ParseOptions::default()).unwrap();
+        root_node
+            .flatten_tree()
+            .into_iter()
+            .filter_map(|node| match node {
+                Node::Link(link) => Some(link.text()),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn plain_link_text_extracted_correctly() {
+        assert_eq!(
+            link_texts("[Fix the bug](https://example.com/task/1/f)"),
+            ["Fix the bug"]
+        );
+    }
+
+    #[test]
+    fn formatted_link_text_extracted_correctly() {
+        assert_eq!(
+            link_texts("[**Fix** the bug](https://example.com/task/1/f)"),
+            ["Fix the bug"]
+        );
+        assert_eq!(
+            link_texts("[Fix the *flaky* bug](https://example.com/task/1/f)"),
+            ["Fix the flaky bug"]
+        );
+        assert_eq!(
+            link_texts("[Fix `parse_date` in ***all*** locales](https://example.com/task/1/f)"),
+            ["Fix parse_date in all locales"]
+        );
+        assert_eq!(
+            link_texts("[_Fix_ the **`bug`**](https://example.com/task/1/f)"),
+            ["Fix the bug"]
+        );
+    }
+
+    #[test]
+    fn csv_line_quotes_fields() {
+        assert_eq!(
+            csv_line(&[
+                String::from("plain"),
+                String::from("with, comma"),
+                String::from("with \"quotes\""),
+                String::from("multi\nline"),
+            ]),
+            "plain,\"with, comma\",\"with \"\"quotes\"\"\",\"multi\nline\""
+        );
+    }
+
+    #[test]
+    fn csv_parsed_back() {
+        let lines = [
+            vec![String::from("id"), String::from("description")],
+            vec![String::from("1"), String::from("[A, B](x)\n- \"quoted\"")],
+            vec![String::from("2"), String::new()],
+        ];
+        let text: String = lines.iter().map(|line| csv_line(line) + "\r\n").collect();
+
+        assert_eq!(parse_csv(&text), lines);
+    }
 }
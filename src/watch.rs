@@ -0,0 +1,361 @@
+//! `ham-cli watch`: syncing days of facts as they get closed, once the
+//! database has been quiet for a while
+use chrono::{DateTime, Local, NaiveDate};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+use crate::aggregation::AggregationOptions;
+use crate::backend::TimeTrackerBackend;
+use crate::checks::{find_fact_issues, CheckOptions};
+use crate::config::Config;
+use crate::date_range::DateRange;
+use crate::hamster::{self, HamsterData, HamsterFact};
+use crate::state::SyncState;
+use crate::sync::sync_days;
+use crate::utils::RunMode;
+
+pub struct WatchOptions {
+    pub aggregation: AggregationOptions,
+    pub checks: CheckOptions,
+    /// sync days with overlapping or bad length facts anyway
+    pub skip_checks: bool,
+    /// delete extra records of a task on a day instead of reporting them
+    pub delete_duplicates: bool,
+    pub run_mode: RunMode,
+    /// how often the database is looked at
+    pub interval: Duration,
+    /// how long no fact has to be closed before syncing
+    pub debounce: Duration,
+}
+
+/// How far closed facts have been seen: a fact added since has a higher id,
+/// a fact stopped since ends later
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub struct FactMark {
+    pub fact_id: i64,
+    pub end_time: DateTime<Local>,
+}
+
+impl FactMark {
+    /// Mark moved past the facts
+    pub fn after(&self, facts: &[HamsterFact]) -> FactMark {
+        facts.iter().fold(*self, |mark, fact| FactMark {
+            fact_id: mark.fact_id.max(fact.id),
+            end_time: fact
+                .end_time
+                .map_or(mark.end_time, |end| mark.end_time.max(end)),
+        })
+    }
+}
+
+/// Days waiting to be synced until facts stop changing for the quiet period
+pub struct PendingDays {
+    quiet: Duration,
+    days: BTreeSet<NaiveDate>,
+    /// failed to sync, tried again once the database changes
+    failed: BTreeSet<NaiveDate>,
+    last_change: Option<Instant>,
+}
+
+impl PendingDays {
+    pub fn new(quiet: Duration) -> PendingDays {
+        PendingDays {
+            quiet,
+            days: BTreeSet::new(),
+            failed: BTreeSet::new(),
+            last_change: None,
+        }
+    }
+
+    /// Takes in the database change: days of facts closed since the last look
+    /// and the days that failed before
+    pub fn changed(&mut self, closed: &[HamsterFact], now: Instant) {
+        if closed.is_empty() && self.failed.is_empty() {
+            return;
+        }
+        self.days.append(&mut self.failed);
+        self.days
+            .extend(closed.iter().map(|fact| fact.start_time.date_naive()));
+        self.last_change = Some(now);
+    }
+
+    /// Days to sync, once nothing has changed for the quiet period
+    pub fn take_ready(&mut self, now: Instant) -> Vec<NaiveDate> {
+        match self.last_change {
+            Some(last_change) if now.duration_since(last_change) >= self.quiet => {
+                self.last_change = None;
+                std::mem::take(&mut self.days).into_iter().collect()
+            }
+            _ => vec![],
+        }
+    }
+
+    pub fn retry_later(&mut self, days: impl IntoIterator<Item = NaiveDate>) {
+        self.failed.extend(days);
+    }
+
+    /// Days waiting for a retry
+    pub fn failed(&self) -> Vec<NaiveDate> {
+        self.failed.iter().copied().collect()
+    }
+}
+
+/// Timestamped lines on stdout and, if there's a file, appended to it
+pub struct WatchLog {
+    file: Option<File>,
+}
+
+impl WatchLog {
+    pub fn open(path: Option<&Path>) -> Result<WatchLog, String> {
+        let file = match path {
+            Some(path) => Some(
+                OpenOptions::new()
+                    .create(true)
+                    .append(true)
+                    .open(path)
+                    .map_err(|err| format!("couldn't open {}: {}", path.display(), err))?,
+            ),
+            None => None,
+        };
+        Ok(WatchLog { file })
+    }
+
+    pub fn write(&mut self, message: &str) {
+        let line = format!("{} {message}", Local::now().format("%Y-%m-%d %H:%M:%S"));
+        println!("{line}");
+        if let Some(file) = self.file.as_mut() {
+            if let Err(err) = writeln!(file, "{line}") {
+                eprintln!("couldn't write to the log file: {err}");
+            }
+        }
+    }
+}
+
+/// Syncs days of facts closed since the last look, waiting for the database to
+/// quiet down first - runs until stopped, returns only if it can't go on
+pub async fn watch_facts<B: TimeTrackerBackend>(
+    hamster_db: Option<String>,
+    backend: &B,
+    config: &Config,
+    options: &WatchOptions,
+    state_path: &Path,
+    log: &mut WatchLog,
+) -> Result<(), String> {
+    let db_path = hamster::db_path(hamster_db)?;
+    let sync_state = SyncState::load(state_path)?;
+    let mut mark = match sync_state.watch_mark {
+        Some(mark) => mark,
+        // facts closed before the first run are left to sync-eh
+        None => {
+            let (fact_id, end_time) = HamsterData::open(Some(db_path.clone()))?
+                .last_closed_fact()
+                .unwrap_or((0, DateTime::UNIX_EPOCH.with_timezone(&Local)));
+            FactMark { fact_id, end_time }
+        }
+    };
+    log.write(&format!(
+        "Watching {db_path} for facts closed after fact {} / {}",
+        mark.fact_id,
+        mark.end_time.format("%Y-%m-%d %H:%M:%S")
+    ));
+
+    let mut pending = PendingDays::new(options.debounce);
+    // days that failed before a restart are retried after the first look at the database
+    pending.retry_later(sync_state.watch_failed_days);
+    let mut modified = None;
+    loop {
+        let db_modified = std::fs::metadata(&db_path)
+            .and_then(|metadata| metadata.modified())
+            .ok();
+        if modified.is_none() || db_modified != modified {
+            modified = db_modified;
+            let closed = HamsterData::open(Some(db_path.clone())).and_then(|hamster_data| {
+                hamster_data.get_closed_facts_after(mark.fact_id, mark.end_time)
+            });
+            match closed {
+                Ok(closed) => {
+                    for fact in closed.iter() {
+                        log.write(&format!(
+                            "Fact {} closed ({}, '{}')",
+                            fact.id,
+                            fact.start_time.date_naive(),
+                            fact.description.lines().next().unwrap_or_default()
+                        ));
+                    }
+                    pending.changed(&closed, Instant::now());
+                    mark = mark.after(&closed);
+                }
+                // e.g. Hamster writing to it - the mark stays, the next look reads again
+                Err(err) => {
+                    log.write(&format!("Couldn't look at the database: {err}"));
+                    modified = None;
+                }
+            }
+        }
+
+        let days = pending.take_ready(Instant::now());
+        if !days.is_empty() {
+            let mut failed = vec![];
+            for day in days {
+                match sync_watched_day(db_path.clone(), backend, day, config, options, log).await {
+                    Ok(()) => log.write(&format!("Synced {day}")),
+                    Err(err) => {
+                        log.write(&format!(
+                            "Couldn't sync {day}, retrying after the next change: {err}"
+                        ));
+                        failed.push(day);
+                    }
+                }
+            }
+            pending.retry_later(failed);
+            if let RunMode::Normal = options.run_mode {
+                let saved = SyncState::save_watch_progress(state_path, mark, pending.failed());
+                if let Err(err) = saved {
+                    log.write(&format!("Couldn't remember synced facts: {err}"));
+                }
+            }
+        }
+        tokio::time::sleep(options.interval).await;
+    }
+}
+
+/// Checks facts of the day and syncs it, unless there are errors
+async fn sync_watched_day<B: TimeTrackerBackend>(
+    db_path: String,
+    backend: &B,
+    day: NaiveDate,
+    config: &Config,
+    options: &WatchOptions,
+    log: &mut WatchLog,
+) -> Result<(), String> {
+    let range = DateRange::day(day);
+    let issues = find_fact_issues(
+        Some(db_path.clone()),
+        range,
+        options.aggregation.category.as_deref(),
+        &options.checks,
+    )?;
+    for issue in issues.iter() {
+        let level = if issue.is_error() { "error" } else { "warning" };
+        log.write(&format!("{level}: {issue}"));
+    }
+    if !options.skip_checks {
+        if let Some(issue) = issues.iter().find(|issue| issue.is_error()) {
+            return Err(issue.to_string());
+        }
+    }
+    sync_days(
+        backend,
+        Some(db_path),
+        range,
+        config,
+        &options.aggregation,
+        options.delete_duplicates,
+        options.run_mode,
+    )
+    .await
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{DateTime, Local, NaiveDate, TimeZone};
+    use std::time::{Duration, Instant};
+
+    use crate::hamster::HamsterFact;
+
+    use super::{FactMark, PendingDays};
+
+    fn time(day: u32, hour: u32) -> DateTime<Local> {
+        Local.with_ymd_and_hms(2024, 5, day, hour, 0, 0).unwrap()
+    }
+
+    fn closed_fact(id: i64, day: u32, end_hour: u32) -> HamsterFact {
+        HamsterFact {
+            id,
+            start_time: time(day, end_hour - 1),
+            end_time: Some(time(day, end_hour)),
+            description: String::new(),
+            activity: String::from("coding"),
+            category: String::from("Work"),
+            tags: vec![],
+        }
+    }
+
+    #[test]
+    fn mark_moves_past_added_and_stopped_facts() {
+        let mark = FactMark {
+            fact_id: 5,
+            end_time: time(13, 12),
+        };
+
+        // fact 3 was running and got stopped, fact 6 is new
+        let moved = mark.after(&[closed_fact(3, 13, 15), closed_fact(6, 13, 14)]);
+
+        assert_eq!(
+            moved,
+            FactMark {
+                fact_id: 6,
+                end_time: time(13, 15)
+            }
+        );
+        assert_eq!(mark.after(&[]), mark);
+    }
+
+    #[test]
+    fn days_synced_once_quiet() {
+        let start = Instant::now();
+        let mut pending = PendingDays::new(Duration::from_secs(60));
+
+        pending.changed(&[closed_fact(1, 13, 10)], start);
+        assert!(pending
+            .take_ready(start + Duration::from_secs(30))
+            .is_empty());
+        // another fact closed - waiting starts over
+        pending.changed(&[closed_fact(2, 14, 10)], start + Duration::from_secs(50));
+        assert!(pending
+            .take_ready(start + Duration::from_secs(90))
+            .is_empty());
+
+        assert_eq!(
+            pending.take_ready(start + Duration::from_secs(110)),
+            [
+                NaiveDate::from_ymd_opt(2024, 5, 13).unwrap(),
+                NaiveDate::from_ymd_opt(2024, 5, 14).unwrap()
+            ]
+        );
+        assert!(pending
+            .take_ready(start + Duration::from_secs(200))
+            .is_empty());
+    }
+
+    #[test]
+    fn failed_days_retried_after_change() {
+        let start = Instant::now();
+        let day = NaiveDate::from_ymd_opt(2024, 5, 13).unwrap();
+        let mut pending = PendingDays::new(Duration::from_secs(60));
+
+        pending.retry_later([day]);
+        assert!(pending
+            .take_ready(start + Duration::from_secs(100))
+            .is_empty());
+
+        // e.g. the description got fixed, no fact closed
+        pending.changed(&[], start + Duration::from_secs(200));
+        assert_eq!(pending.take_ready(start + Duration::from_secs(260)), [day]);
+    }
+
+    #[test]
+    fn nothing_pending_without_closed_facts() {
+        let start = Instant::now();
+        let mut pending = PendingDays::new(Duration::from_secs(60));
+
+        pending.changed(&[], start);
+        assert!(pending
+            .take_ready(start + Duration::from_secs(100))
+            .is_empty());
+    }
+}
@@ -0,0 +1,139 @@
+//! Helpers shared by integration tests: fixture database and a mock HTTP server
+#![allow(dead_code)]
+
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::{Arc, Mutex};
+
+/// Fresh copy of the fixture database, path unique per test
+pub fn fixture_db(name: &str) -> String {
+    let path = std::env::temp_dir().join(format!("ham-cli-{}-{name}.db", std::process::id()));
+    let _ = std::fs::remove_file(&path);
+    let connection = sqlite::open(&path).unwrap();
+    connection
+        .execute(include_str!("../fixtures/hamster.sql"))
+        .unwrap();
+    path.to_string_lossy().into_owned()
+}
+
+#[derive(Clone, Debug)]
+pub struct MockRequest {
+    pub method: String,
+    /// without the query
+    pub path: String,
+    pub query: String,
+    /// names lowercased
+    pub headers: Vec<(String, String)>,
+    pub body: String,
+}
+
+impl MockRequest {
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(header, _)| header == name)
+            .map(|(_, value)| value.as_str())
+    }
+
+    pub fn json(&self) -> serde_json::Value {
+        serde_json::from_str(&self.body).unwrap()
+    }
+}
+
+/// HTTP server on a random local port, answering with canned JSON by
+/// "METHOD /path" (query left out) and with 404 to anything else
+pub struct MockServer {
+    pub url: String,
+    requests: Arc<Mutex<Vec<MockRequest>>>,
+}
+
+impl MockServer {
+    pub fn start(routes: &[(&str, &str)]) -> MockServer {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let routes: Vec<(String, String)> = routes
+            .iter()
+            .map(|(route, body)| (route.to_string(), body.to_string()))
+            .collect();
+        let requests = Arc::new(Mutex::new(vec![]));
+        let recorded = requests.clone();
+        std::thread::spawn(move || {
+            for stream in listener.incoming() {
+                let mut stream = stream.unwrap();
+                let request = read_request(&stream);
+                let route = format!("{} {}", request.method, request.path);
+                let response = match routes.iter().find(|(known, _)| *known == route) {
+                    Some((_, body)) => format!(
+                        "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\n\
+                        Content-Length: {}\r\nConnection: close\r\n\r\n{body}",
+                        body.len()
+                    ),
+                    None => String::from(
+                        "HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\nConnection: close\r\n\r\n",
+                    ),
+                };
+                recorded.lock().unwrap().push(request);
+                stream.write_all(response.as_bytes()).unwrap();
+            }
+        });
+        MockServer { url, requests }
+    }
+
+    pub fn requests(&self) -> Vec<MockRequest> {
+        self.requests.lock().unwrap().clone()
+    }
+
+    /// Requests other than GET, as "METHOD /path" and JSON body (null if empty)
+    pub fn changes(&self) -> Vec<(String, serde_json::Value)> {
+        self.requests()
+            .into_iter()
+            .filter(|request| request.method != "GET")
+            .map(|request| {
+                let body = if request.body.is_empty() {
+                    serde_json::Value::Null
+                } else {
+                    request.json()
+                };
+                (format!("{} {}", request.method, request.path), body)
+            })
+            .collect()
+    }
+}
+
+fn read_request(stream: &TcpStream) -> MockRequest {
+    let mut reader = BufReader::new(stream);
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line).unwrap();
+    let mut parts = request_line.split_whitespace();
+    let method = parts.next().unwrap_or_default().to_string();
+    let target = parts.next().unwrap_or_default();
+    let (path, query) = target.split_once('?').unwrap_or((target, ""));
+
+    let mut headers = vec![];
+    loop {
+        let mut line = String::new();
+        reader.read_line(&mut line).unwrap();
+        let line = line.trim_end();
+        if line.is_empty() {
+            break;
+        }
+        if let Some((name, value)) = line.split_once(':') {
+            headers.push((name.trim().to_lowercase(), value.trim().to_string()));
+        }
+    }
+    let length: usize = headers
+        .iter()
+        .find(|(name, _)| name == "content-length")
+        .map(|(_, value)| value.parse().unwrap())
+        .unwrap_or(0);
+    let mut body = vec![0; length];
+    reader.read_exact(&mut body).unwrap();
+
+    MockRequest {
+        method,
+        path: path.to_string(),
+        query: query.to_string(),
+        headers,
+        body: String::from_utf8(body).unwrap(),
+    }
+}
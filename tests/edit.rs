@@ -0,0 +1,140 @@
+//! Editing facts of the fixture database
+use chrono::{DateTime, Local, NaiveDate, NaiveDateTime, TimeZone};
+
+use ham_cli::config::Config;
+use ham_cli::edit::{changed_fact, save_fact, FactChanges};
+use ham_cli::hamster::HamsterData;
+
+mod common;
+use common::fixture_db;
+
+fn time(day: u32, hour: u32, minute: u32) -> DateTime<Local> {
+    Local
+        .with_ymd_and_hms(2024, 5, day, hour, minute, 0)
+        .unwrap()
+}
+
+fn naive_time(day: u32, hour: u32, minute: u32) -> NaiveDateTime {
+    NaiveDate::from_ymd_opt(2024, 5, day)
+        .unwrap()
+        .and_hms_opt(hour, minute, 0)
+        .unwrap()
+}
+
+#[test]
+fn unknown_fact_refused() {
+    let hamster_data = HamsterData::open(Some(fixture_db("edit-unknown"))).unwrap();
+
+    let result = changed_fact(&hamster_data, 99, FactChanges::default());
+
+    assert_eq!(result.err(), Some("there's no fact 99".to_string()));
+}
+
+#[test]
+fn changes_saved() {
+    let hamster_data = HamsterData::open(Some(fixture_db("edit-saved"))).unwrap();
+    let changes = FactChanges {
+        activity: Some(("review".to_string(), "Work".to_string())),
+        end: Some(naive_time(13, 10, 15)),
+        description: Some("[Review](https://app.asana.com/0/0/333/f)".to_string()),
+        ..Default::default()
+    };
+
+    let fact = changed_fact(&hamster_data, 2, changes).unwrap();
+    let problems = save_fact(&hamster_data, &fact, &Config::default(), false).unwrap();
+
+    assert!(problems.is_empty());
+    let saved = hamster_data.get_fact(2).unwrap();
+    assert_eq!(saved.activity, "review");
+    assert_eq!(saved.category, "Work");
+    assert_eq!(saved.start_time, time(13, 10, 0));
+    assert_eq!(saved.end_time, Some(time(13, 10, 15)));
+    assert_eq!(
+        saved.description,
+        "[Review](https://app.asana.com/0/0/333/f)"
+    );
+}
+
+#[test]
+fn end_before_start_refused_even_if_forced() {
+    let hamster_data = HamsterData::open(Some(fixture_db("edit-end"))).unwrap();
+    let changes = FactChanges {
+        end: Some(naive_time(13, 8, 0)),
+        ..Default::default()
+    };
+
+    let fact = changed_fact(&hamster_data, 1, changes).unwrap();
+    let result = save_fact(&hamster_data, &fact, &Config::default(), true);
+
+    assert!(result.unwrap_err().contains("before it starts"));
+    assert_eq!(
+        hamster_data.get_fact(1).unwrap().end_time,
+        Some(time(13, 10, 0))
+    );
+}
+
+#[test]
+fn overlap_refused_unless_forced() {
+    let hamster_data = HamsterData::open(Some(fixture_db("edit-overlap"))).unwrap();
+    let changes = FactChanges {
+        end: Some(naive_time(13, 10, 45)),
+        ..Default::default()
+    };
+    let fact = changed_fact(&hamster_data, 1, changes).unwrap();
+
+    let result = save_fact(&hamster_data, &fact, &Config::default(), false);
+
+    let err = result.unwrap_err();
+    assert!(err.contains("overlap with fact 2"));
+    assert!(err.contains("overlap with fact 3"));
+    assert_eq!(
+        hamster_data.get_fact(1).unwrap().end_time,
+        Some(time(13, 10, 0))
+    );
+
+    let problems = save_fact(&hamster_data, &fact, &Config::default(), true).unwrap();
+
+    assert_eq!(problems.len(), 2);
+    assert_eq!(
+        hamster_data.get_fact(1).unwrap().end_time,
+        Some(time(13, 10, 45))
+    );
+}
+
+#[test]
+fn touching_neighbours_not_an_overlap() {
+    let hamster_data = HamsterData::open(Some(fixture_db("edit-touching"))).unwrap();
+    let changes = FactChanges {
+        start: Some(naive_time(13, 9, 0)),
+        end: Some(naive_time(13, 10, 30)),
+        ..Default::default()
+    };
+    // fact 2 sits between facts 1 and 3 - moved over fact 1 it overlaps just that
+    let fact = changed_fact(&hamster_data, 2, changes).unwrap();
+
+    let err = save_fact(&hamster_data, &fact, &Config::default(), false).unwrap_err();
+
+    assert!(err.contains("overlap with fact 1"));
+    assert!(!err.contains("overlap with fact 3"));
+}
+
+#[test]
+fn unsyncable_fact_refused_unless_forced() {
+    let hamster_data = HamsterData::open(Some(fixture_db("edit-unsyncable"))).unwrap();
+    let changes = FactChanges {
+        description: Some("standing".to_string()),
+        ..Default::default()
+    };
+    // stand-up has neither a link nor a mapping
+    let fact = changed_fact(&hamster_data, 4, changes).unwrap();
+
+    let err = save_fact(&hamster_data, &fact, &Config::default(), false).unwrap_err();
+
+    assert!(err.contains("wouldn't sync"));
+    assert_eq!(hamster_data.get_fact(4).unwrap().description, "");
+
+    let problems = save_fact(&hamster_data, &fact, &Config::default(), true).unwrap();
+
+    assert_eq!(problems.len(), 1);
+    assert_eq!(hamster_data.get_fact(4).unwrap().description, "standing");
+}
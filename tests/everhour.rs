@@ -0,0 +1,185 @@
+//! Everhour client pointed at a mock server, recording and replaying, with facts
+//! from the fixture database: on 2024-05-13 task 111 has 1:30 and task 222 has 0:30
+use chrono::NaiveDate;
+use serde_json::json;
+use std::time::Duration;
+
+use ham_cli::aggregation::AggregationOptions;
+use ham_cli::backend::{EverhourBackend, TimeEntry, TimeTrackerBackend};
+use ham_cli::config::Config;
+use ham_cli::date_range::DateRange;
+use ham_cli::everhour::Client;
+use ham_cli::sync::sync_days;
+use ham_cli::utils::RunMode;
+
+mod common;
+use common::{fixture_db, MockServer};
+
+const RECORDED: &str = concat!(
+    env!("CARGO_MANIFEST_DIR"),
+    "/tests/fixtures/everhour-sync.json"
+);
+
+fn day() -> NaiveDate {
+    NaiveDate::from_ymd_opt(2024, 5, 13).unwrap()
+}
+
+async fn sync(backend: &EverhourBackend, name: &str) -> Result<(), String> {
+    sync_days(
+        backend,
+        Some(fixture_db(name)),
+        DateRange::day(day()),
+        &Config::default(),
+        &AggregationOptions::default(),
+        false,
+        RunMode::Normal,
+    )
+    .await
+}
+
+fn mock_server() -> MockServer {
+    MockServer::start(&[
+        ("GET /users/me", r#"{"id": 1, "name": "Me"}"#),
+        (
+            "GET /users/1/time",
+            r#"[{"id": 5, "date": "2024-05-13", "time": 1800, "comment": "fixed it",
+                "task": {"id": "as:111", "name": "Fix parser"}}]"#,
+        ),
+        ("PUT /tasks/as:111/time", r#"{"id": 5}"#),
+        ("POST /tasks/as:222/time", r#"{"id": 6}"#),
+    ])
+}
+
+#[tokio::test]
+async fn sync_goes_to_configured_url() {
+    let server = mock_server();
+    let backend = EverhourBackend::new(Client::new(String::from("secret"), server.url.clone()));
+
+    sync(&backend, "sync_goes_to_configured_url").await.unwrap();
+
+    let requests = server.requests();
+    assert_eq!(requests[1].query, "from=2024-05-13&to=2024-05-13");
+    assert!(requests
+        .iter()
+        .all(|request| request.header("x-api-key") == Some("secret")));
+    let changes = server.changes();
+    assert_eq!(
+        changes,
+        [
+            (
+                String::from("PUT /tasks/as:111/time"),
+                json!({
+                    "date": "2024-05-13",
+                    "user": 1,
+                    "time": 5400,
+                    "comment": "fixed it\ntested it",
+                })
+            ),
+            (
+                String::from("POST /tasks/as:222/time"),
+                json!({
+                    "date": "2024-05-13",
+                    "user": 1,
+                    "time": 1800,
+                    "comment": "coding",
+                })
+            ),
+        ]
+    );
+}
+
+#[tokio::test]
+async fn recorded_responses_replayed() {
+    let backend = EverhourBackend::new(Client::replaying(RECORDED).unwrap());
+
+    sync(&backend, "recorded_responses_replayed").await.unwrap();
+
+    assert!(backend.client().unused_exchanges().is_empty());
+}
+
+#[tokio::test]
+async fn recording_replays_the_same_sync() {
+    let server = mock_server();
+    let path = std::env::temp_dir().join(format!(
+        "ham-cli-test-{}-recording.json",
+        std::process::id()
+    ));
+    let path = path.to_str().unwrap().to_string();
+    let client = Client::new(String::from("secret"), server.url.clone()).recording(path.clone());
+
+    sync(&EverhourBackend::new(client), "recording_live")
+        .await
+        .unwrap();
+    let backend = EverhourBackend::new(Client::replaying(&path).unwrap());
+    sync(&backend, "recording_replayed").await.unwrap();
+
+    assert_eq!(server.requests().len(), 4);
+    assert!(backend.client().unused_exchanges().is_empty());
+    std::fs::remove_file(path).unwrap();
+}
+
+#[tokio::test]
+async fn missing_recorded_response_is_an_error() {
+    let client = Client::replaying(RECORDED).unwrap();
+
+    let result = client
+        .time_records(1, day(), NaiveDate::from_ymd_opt(2024, 5, 14).unwrap())
+        .await;
+
+    assert_eq!(
+        result.err().unwrap(),
+        "No recorded response to GET /users/1/time?from=2024-05-13&to=2024-05-14"
+    );
+}
+
+#[tokio::test]
+async fn project_and_billable_time_put() {
+    let server = MockServer::start(&[
+        ("PUT /projects/ev:3/time", r#"{"id": 7}"#),
+        ("PUT /tasks/as:222/time", r#"{"id": 8}"#),
+    ]);
+    let backend = EverhourBackend::new(Client::new(String::from("secret"), server.url.clone()));
+    let entry = |time: u64, billable: Option<bool>, project: bool| TimeEntry {
+        date: day(),
+        time: Duration::new(time, 0),
+        title: String::from("-"),
+        comment: String::from("coding"),
+        billable,
+        project,
+    };
+
+    backend
+        .add_time(&1, "ev:3", &entry(3600, None, true))
+        .await
+        .unwrap();
+    // billable flag only goes with time set directly, even for a new record
+    backend
+        .add_time(&1, "as:222", &entry(1800, Some(false), false))
+        .await
+        .unwrap();
+
+    assert_eq!(
+        server.changes(),
+        [
+            (
+                String::from("PUT /projects/ev:3/time"),
+                json!({
+                    "date": "2024-05-13",
+                    "user": 1,
+                    "time": 3600,
+                    "comment": "coding",
+                })
+            ),
+            (
+                String::from("PUT /tasks/as:222/time"),
+                json!({
+                    "date": "2024-05-13",
+                    "user": 1,
+                    "time": 1800,
+                    "comment": "coding",
+                    "billable": false,
+                })
+            ),
+        ]
+    );
+}
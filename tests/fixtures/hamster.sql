@@ -0,0 +1,22 @@
+-- Hamster database, schema version 9, with a couple of working days
+CREATE TABLE version (version integer);
+CREATE TABLE categories (id integer primary key, name varchar2(500), color_code varchar2(50), category_order integer, search_name varchar2(500));
+CREATE TABLE activities (id integer primary key, name varchar2(500), work integer, activity_order integer, deleted integer, category_id integer, search_name varchar2(500));
+CREATE TABLE facts (id integer primary key, activity_id integer, start_time timestamp, end_time timestamp, description varchar2, FOREIGN KEY(activity_id) REFERENCES activities(id));
+CREATE TABLE tags (id integer primary key, name varchar2(500) not null, autocomplete boolean default true);
+CREATE TABLE fact_tags(fact_id integer, tag_id integer);
+
+INSERT INTO version VALUES (9);
+INSERT INTO categories VALUES (1, 'Work', NULL, NULL, 'work');
+INSERT INTO activities VALUES (1, 'coding', NULL, NULL, 0, 1, 'coding');
+INSERT INTO activities VALUES (2, 'stand-up', NULL, NULL, 0, 1, 'stand-up');
+
+-- 2024-05-13: two tasks, one of them tracked twice
+INSERT INTO facts VALUES (1, 1, '2024-05-13 09:00:00', '2024-05-13 10:00:00', '[Fix parser](https://app.asana.com/0/0/111/f)
+- fixed it');
+INSERT INTO facts VALUES (2, 1, '2024-05-13 10:00:00', '2024-05-13 10:30:00', '[Write docs](https://app.asana.com/0/0/222/f)');
+INSERT INTO facts VALUES (3, 1, '2024-05-13 10:30:00', '2024-05-13 11:00:00', '[Fix parser](https://app.asana.com/0/0/111/f)
+- tested it');
+
+-- 2024-05-14: a fact without a task link
+INSERT INTO facts VALUES (4, 2, '2024-05-14 09:00:00', '2024-05-14 09:15:00', '');
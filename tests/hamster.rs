@@ -0,0 +1,133 @@
+//! Writing facts to the fixture database
+use chrono::{DateTime, Local, TimeZone};
+
+use ham_cli::date_range::DateRange;
+use ham_cli::hamster::HamsterData;
+
+mod common;
+use common::fixture_db;
+
+fn time(day: u32, hour: u32, minute: u32) -> DateTime<Local> {
+    Local
+        .with_ymd_and_hms(2024, 5, day, hour, minute, 0)
+        .unwrap()
+}
+
+/// Number of rows in the table
+fn count(db: &str, table: &str) -> i64 {
+    let connection = sqlite::open(db).unwrap();
+    let mut statement = connection
+        .prepare(format!("SELECT COUNT(*) FROM {table}"))
+        .unwrap();
+    statement.next().unwrap();
+    statement.read::<i64, _>(0).unwrap()
+}
+
+#[test]
+fn existing_activity_and_category_reused() {
+    let db = fixture_db("hamster-reused");
+    let hamster_data = HamsterData::open(Some(db.clone())).unwrap();
+
+    let fact_id = hamster_data
+        .add_fact(
+            "coding",
+            "Work",
+            time(15, 9, 0),
+            Some(time(15, 10, 0)),
+            "",
+            &[],
+        )
+        .unwrap();
+    hamster_data
+        .add_fact(
+            "review",
+            "Work",
+            time(15, 10, 0),
+            Some(time(15, 11, 0)),
+            "",
+            &[String::from("backend")],
+        )
+        .unwrap();
+
+    assert_eq!(fact_id, 5);
+    assert_eq!(count(&db, "categories"), 1);
+    assert_eq!(count(&db, "activities"), 3);
+    let facts = hamster_data.get_facts(DateRange::day(time(15, 0, 0).date_naive()));
+    assert_eq!(facts[0].activity, "coding");
+    assert_eq!(facts[1].activity, "review");
+    assert_eq!(facts[1].category, "Work");
+    assert_eq!(facts[1].tags, ["backend"]);
+}
+
+#[test]
+fn unknown_schema_version_refused() {
+    let db = fixture_db("hamster-schema");
+    sqlite::open(&db)
+        .unwrap()
+        .execute("UPDATE version SET version = 10")
+        .unwrap();
+    let hamster_data = HamsterData::open(Some(db.clone())).unwrap();
+
+    let added = hamster_data.add_fact("coding", "Work", time(15, 9, 0), None, "", &[]);
+
+    assert_eq!(
+        added,
+        Err(String::from(
+            "Hamster schema version is 10, only 9 is supported for writing - \
+            refusing to touch the database"
+        ))
+    );
+    assert!(hamster_data.stop_running(time(15, 10, 0)).is_err());
+    assert_eq!(count(&db, "facts"), 4);
+}
+
+#[test]
+fn failed_add_leaves_nothing_behind() {
+    let db = fixture_db("hamster-rollback");
+    // fact can't be inserted after its category and activity are
+    sqlite::open(&db)
+        .unwrap()
+        .execute("DROP TABLE facts")
+        .unwrap();
+    let hamster_data = HamsterData::open(Some(db.clone())).unwrap();
+
+    assert!(hamster_data
+        .add_fact("support", "Ops", time(15, 9, 0), None, "", &[])
+        .is_err());
+
+    assert_eq!(count(&db, "categories"), 1);
+    assert_eq!(count(&db, "activities"), 2);
+}
+
+#[test]
+fn only_running_fact_stopped() {
+    let db = fixture_db("hamster-stop");
+    let hamster_data = HamsterData::open(Some(db.clone())).unwrap();
+    assert_eq!(hamster_data.stop_running(time(15, 10, 0)), Ok(None));
+    let running = hamster_data
+        .add_fact("coding", "Work", time(15, 9, 0), None, "", &[])
+        .unwrap();
+
+    assert_eq!(
+        hamster_data.stop_running(time(15, 10, 0)),
+        Ok(Some(running))
+    );
+
+    let ends: Vec<Option<DateTime<Local>>> = hamster_data
+        .get_facts(
+            DateRange::new(time(13, 0, 0).date_naive(), time(15, 0, 0).date_naive()).unwrap(),
+        )
+        .iter()
+        .map(|fact| fact.end_time)
+        .collect();
+    assert_eq!(
+        ends,
+        [
+            Some(time(13, 10, 0)),
+            Some(time(13, 10, 30)),
+            Some(time(13, 11, 0)),
+            Some(time(14, 9, 15)),
+            Some(time(15, 10, 0)),
+        ]
+    );
+}
@@ -0,0 +1,91 @@
+//! Importing facts into the fixture database
+use chrono::{DateTime, Local, TimeZone};
+
+use ham_cli::date_range::DateRange;
+use ham_cli::hamster::HamsterData;
+use ham_cli::import::{import_facts, ImportedFact};
+use ham_cli::utils::RunMode;
+
+mod common;
+use common::fixture_db;
+
+fn time(day: u32, hour: u32, minute: u32) -> DateTime<Local> {
+    Local
+        .with_ymd_and_hms(2024, 5, day, hour, minute, 0)
+        .unwrap()
+}
+
+fn imported(start_time: DateTime<Local>, end_time: DateTime<Local>) -> ImportedFact {
+    ImportedFact {
+        start_time,
+        end_time,
+        activity: String::from("coding"),
+        category: String::from("Work"),
+        description: String::from("[Review](https://app.asana.com/0/0/333/f)"),
+        tags: vec![String::from("review")],
+    }
+}
+
+fn fact_count(hamster_db: &str, day: u32) -> usize {
+    let day = time(day, 0, 0).date_naive();
+    HamsterData::open(Some(hamster_db.to_string()))
+        .unwrap()
+        .get_facts(DateRange::day(day))
+        .len()
+}
+
+#[test]
+fn facts_added_with_tags() {
+    let hamster_db = fixture_db("import-added");
+
+    let counts = import_facts(
+        Some(hamster_db.clone()),
+        vec![imported(time(15, 9, 0), time(15, 10, 0))],
+        RunMode::Normal,
+    )
+    .unwrap();
+
+    assert_eq!(counts, (1, 0));
+    let hamster_data = HamsterData::open(Some(hamster_db)).unwrap();
+    let fact = hamster_data.get_fact(5).unwrap();
+    assert_eq!(fact.start_time, time(15, 9, 0));
+    assert_eq!(fact.end_time, Some(time(15, 10, 0)));
+    assert_eq!(fact.tags, ["review"]);
+}
+
+#[test]
+fn overlapping_and_backwards_facts_skipped() {
+    let hamster_db = fixture_db("import-skipped");
+
+    let counts = import_facts(
+        Some(hamster_db.clone()),
+        vec![
+            // over existing fact 3
+            imported(time(13, 10, 45), time(13, 11, 30)),
+            imported(time(13, 12, 0), time(13, 11, 0)),
+            imported(time(13, 13, 0), time(13, 14, 0)),
+            // over the one before
+            imported(time(13, 13, 30), time(13, 14, 30)),
+        ],
+        RunMode::Normal,
+    )
+    .unwrap();
+
+    assert_eq!(counts, (1, 3));
+    assert_eq!(fact_count(&hamster_db, 13), 4);
+}
+
+#[test]
+fn dry_run_adds_nothing() {
+    let hamster_db = fixture_db("import-dry-run");
+
+    let counts = import_facts(
+        Some(hamster_db.clone()),
+        vec![imported(time(15, 9, 0), time(15, 10, 0))],
+        RunMode::DryRun,
+    )
+    .unwrap();
+
+    assert_eq!(counts, (1, 0));
+    assert_eq!(fact_count(&hamster_db, 15), 0);
+}
@@ -0,0 +1,94 @@
+//! Relinking facts of the fixture database
+use chrono::NaiveDate;
+
+use ham_cli::date_range::DateRange;
+use ham_cli::hamster::HamsterData;
+use ham_cli::relink::{find_relinked_facts, relinked_copy, relinked_days, save_relinked_facts};
+
+mod common;
+use common::fixture_db;
+
+const NEW_URL: &str = "https://app.asana.com/0/0/999/f";
+
+fn range() -> DateRange {
+    DateRange::new(
+        NaiveDate::from_ymd_opt(2024, 5, 13).unwrap(),
+        NaiveDate::from_ymd_opt(2024, 5, 14).unwrap(),
+    )
+    .unwrap()
+}
+
+fn description(hamster_db: &str, fact_id: i64) -> String {
+    HamsterData::open(Some(hamster_db.to_string()))
+        .unwrap()
+        .get_fact(fact_id)
+        .unwrap()
+        .description
+}
+
+#[test]
+fn links_to_old_task_rewritten() {
+    let hamster_db = fixture_db("relink-saved");
+
+    let relinked =
+        find_relinked_facts(Some(hamster_db.clone()), range(), None, "111", NEW_URL).unwrap();
+
+    assert_eq!(
+        relinked
+            .iter()
+            .map(|relinked| relinked.fact.id)
+            .collect::<Vec<_>>(),
+        [1, 3]
+    );
+    assert_eq!(
+        relinked_days(&relinked),
+        [NaiveDate::from_ymd_opt(2024, 5, 13).unwrap()]
+    );
+    assert!(relinked[0].old_description.contains("/111/"));
+    assert!(description(&hamster_db, 1).contains("/111/"));
+
+    save_relinked_facts(Some(hamster_db.clone()), &relinked).unwrap();
+
+    assert!(
+        description(&hamster_db, 1).starts_with("[Fix parser](https://app.asana.com/0/0/999/f)")
+    );
+    assert!(description(&hamster_db, 3).contains("/999/"));
+    assert!(description(&hamster_db, 2).contains("/222/"));
+}
+
+#[test]
+fn copy_relinked_database_untouched() {
+    let hamster_db = fixture_db("relink-copy");
+    let relinked =
+        find_relinked_facts(Some(hamster_db.clone()), range(), None, "111", NEW_URL).unwrap();
+
+    let copy = relinked_copy(Some(hamster_db.clone()), &relinked).unwrap();
+
+    assert!(description(&copy, 1).contains("/999/"));
+    assert!(description(&hamster_db, 1).contains("/111/"));
+    std::fs::remove_file(copy).unwrap();
+}
+
+#[test]
+fn other_category_and_bad_link_left_alone() {
+    let hamster_db = fixture_db("relink-category");
+
+    let relinked = find_relinked_facts(
+        Some(hamster_db.clone()),
+        range(),
+        Some("Home"),
+        "111",
+        NEW_URL,
+    )
+    .unwrap();
+    assert!(relinked.is_empty());
+
+    let result = find_relinked_facts(
+        Some(hamster_db),
+        range(),
+        None,
+        "111",
+        "https://example.com",
+    );
+    assert!(result.is_err());
+}
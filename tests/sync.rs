@@ -0,0 +1,340 @@
+//! Sync scenarios against the in-memory tracker, with facts from the fixture database
+use chrono::NaiveDate;
+use std::time::Duration;
+
+use ham_cli::aggregation::AggregationOptions;
+use ham_cli::backend::{BookedTime, InMemoryBackend};
+use ham_cli::config::Config;
+use ham_cli::date_range::DateRange;
+use ham_cli::sync::sync_days;
+use ham_cli::utils::RunMode;
+
+mod common;
+use common::fixture_db;
+
+const USER: i64 = 1;
+
+fn day(day: u32) -> NaiveDate {
+    NaiveDate::from_ymd_opt(2024, 5, day).unwrap()
+}
+
+fn record(id: i64, date: NaiveDate, task_id: &str, minutes: u64) -> BookedTime {
+    BookedTime {
+        id,
+        date,
+        task_id: Some(String::from(task_id)),
+        task_name: None,
+        time: Duration::new(minutes * 60, 0),
+        comment: None,
+    }
+}
+
+/// Records reduced to what matters: id, task and minutes
+fn summary(backend: &InMemoryBackend) -> Vec<(i64, String, u64)> {
+    backend
+        .records()
+        .into_iter()
+        .map(|record| {
+            (
+                record.id,
+                record.task_id.unwrap_or_default(),
+                record.time.as_secs() / 60,
+            )
+        })
+        .collect()
+}
+
+async fn sync(
+    backend: &InMemoryBackend,
+    hamster_db: &str,
+    date: NaiveDate,
+    config: &Config,
+    run_mode: RunMode,
+) -> Result<(), String> {
+    sync_days(
+        backend,
+        Some(hamster_db.to_string()),
+        DateRange::day(date),
+        config,
+        &AggregationOptions::default(),
+        false,
+        run_mode,
+    )
+    .await
+}
+
+#[tokio::test]
+async fn new_records_added() {
+    let hamster_db = fixture_db("new_records_added");
+    let backend = InMemoryBackend::new(USER, vec![]);
+
+    sync(
+        &backend,
+        &hamster_db,
+        day(13),
+        &Config::default(),
+        RunMode::Normal,
+    )
+    .await
+    .unwrap();
+
+    assert_eq!(
+        summary(&backend),
+        [
+            (1, String::from("as:111"), 90),
+            (2, String::from("as:222"), 30),
+        ]
+    );
+    assert_eq!(
+        backend.records()[0].comment.as_deref(),
+        Some("fixed it\ntested it")
+    );
+}
+
+#[tokio::test]
+async fn existing_records_updated() {
+    let hamster_db = fixture_db("existing_records_updated");
+    let backend = InMemoryBackend::new(USER, vec![record(7, day(13), "as:111", 20)]);
+
+    sync(
+        &backend,
+        &hamster_db,
+        day(13),
+        &Config::default(),
+        RunMode::Normal,
+    )
+    .await
+    .unwrap();
+
+    assert_eq!(
+        summary(&backend),
+        [
+            (7, String::from("as:111"), 90),
+            (8, String::from("as:222"), 30),
+        ]
+    );
+}
+
+fn with_duplicates() -> InMemoryBackend {
+    InMemoryBackend::new(
+        USER,
+        vec![
+            record(7, day(13), "as:111", 90),
+            record(8, day(13), "as:111", 90),
+            record(9, day(13), "as:222", 30),
+        ],
+    )
+}
+
+#[tokio::test]
+async fn duplicates_kept_by_default() {
+    let hamster_db = fixture_db("duplicates_kept_by_default");
+    let backend = with_duplicates();
+
+    sync(
+        &backend,
+        &hamster_db,
+        day(13),
+        &Config::default(),
+        RunMode::Normal,
+    )
+    .await
+    .unwrap();
+
+    assert_eq!(
+        summary(&backend),
+        [
+            (7, String::from("as:111"), 90),
+            (8, String::from("as:111"), 90),
+            (9, String::from("as:222"), 30),
+        ]
+    );
+}
+
+#[tokio::test]
+async fn duplicates_removed_if_asked() {
+    let hamster_db = fixture_db("duplicates_removed_if_asked");
+    let backend = with_duplicates();
+
+    sync_days(
+        &backend,
+        Some(hamster_db),
+        DateRange::day(day(13)),
+        &Config::default(),
+        &AggregationOptions::default(),
+        true,
+        RunMode::Normal,
+    )
+    .await
+    .unwrap();
+
+    assert_eq!(
+        summary(&backend),
+        [
+            (7, String::from("as:111"), 90),
+            (9, String::from("as:222"), 30),
+        ]
+    );
+}
+
+#[tokio::test]
+async fn second_sync_changes_nothing() {
+    let hamster_db = fixture_db("second_sync_changes_nothing");
+    let backend = InMemoryBackend::new(USER, vec![]);
+
+    sync(
+        &backend,
+        &hamster_db,
+        day(13),
+        &Config::default(),
+        RunMode::Normal,
+    )
+    .await
+    .unwrap();
+    let first = backend.records();
+    sync(
+        &backend,
+        &hamster_db,
+        day(13),
+        &Config::default(),
+        RunMode::Normal,
+    )
+    .await
+    .unwrap();
+
+    assert_eq!(backend.records(), first);
+}
+
+#[tokio::test]
+async fn other_days_left_alone() {
+    let hamster_db = fixture_db("other_days_left_alone");
+    let backend = InMemoryBackend::new(USER, vec![record(7, day(12), "as:111", 20)]);
+
+    sync(
+        &backend,
+        &hamster_db,
+        day(13),
+        &Config::default(),
+        RunMode::Normal,
+    )
+    .await
+    .unwrap();
+
+    assert_eq!(backend.records()[0], record(7, day(12), "as:111", 20));
+    assert_eq!(backend.records().len(), 3);
+}
+
+#[tokio::test]
+async fn dry_run_changes_nothing() {
+    let hamster_db = fixture_db("dry_run_changes_nothing");
+    let backend = InMemoryBackend::new(
+        USER,
+        vec![
+            record(7, day(13), "as:111", 20),
+            record(8, day(13), "as:111", 20),
+        ],
+    );
+
+    sync(
+        &backend,
+        &hamster_db,
+        day(13),
+        &Config::default(),
+        RunMode::DryRun,
+    )
+    .await
+    .unwrap();
+
+    assert_eq!(
+        summary(&backend),
+        [
+            (7, String::from("as:111"), 20),
+            (8, String::from("as:111"), 20),
+        ]
+    );
+}
+
+#[tokio::test]
+async fn missing_task_id_stops_sync() {
+    let hamster_db = fixture_db("missing_task_id_stops_sync");
+    let backend = InMemoryBackend::new(USER, vec![]);
+
+    let result = sync(
+        &backend,
+        &hamster_db,
+        day(14),
+        &Config::default(),
+        RunMode::Normal,
+    )
+    .await;
+
+    assert!(result.unwrap_err().starts_with("Missing task id!"));
+    assert!(backend.records().is_empty());
+}
+
+#[tokio::test]
+async fn missing_task_id_allowed_in_dry_run() {
+    let hamster_db = fixture_db("missing_task_id_allowed_in_dry_run");
+    let backend = InMemoryBackend::new(USER, vec![]);
+
+    sync(
+        &backend,
+        &hamster_db,
+        day(14),
+        &Config::default(),
+        RunMode::DryRun,
+    )
+    .await
+    .unwrap();
+
+    assert!(backend.records().is_empty());
+}
+
+#[tokio::test]
+async fn mapped_facts_synced() {
+    let hamster_db = fixture_db("mapped_facts_synced");
+    let backend = InMemoryBackend::new(USER, vec![]);
+    let config: Config = toml::from_str(
+        "[[mappings]]\ncategory = \"Work\"\nactivity = \"stand-up\"\ntask_id = \"333\"\n",
+    )
+    .unwrap();
+
+    sync(&backend, &hamster_db, day(14), &config, RunMode::Normal)
+        .await
+        .unwrap();
+
+    assert_eq!(summary(&backend), [(1, String::from("as:333"), 15)]);
+}
+
+#[tokio::test]
+async fn both_ends_of_range_synced() {
+    let hamster_db = fixture_db("both_ends_of_range_synced");
+    // outside of the range - must stay as it is
+    let backend = InMemoryBackend::new(USER, vec![record(7, day(15), "as:111", 20)]);
+    let config: Config = toml::from_str(
+        "[[mappings]]\ncategory = \"Work\"\nactivity = \"stand-up\"\ntask_id = \"333\"\n",
+    )
+    .unwrap();
+
+    sync_days(
+        &backend,
+        Some(hamster_db),
+        DateRange::new(day(13), day(14)).unwrap(),
+        &config,
+        &AggregationOptions::default(),
+        false,
+        RunMode::Normal,
+    )
+    .await
+    .unwrap();
+
+    assert_eq!(
+        summary(&backend),
+        [
+            (7, String::from("as:111"), 20),
+            (8, String::from("as:111"), 90),
+            (9, String::from("as:222"), 30),
+            (10, String::from("as:333"), 15),
+        ]
+    );
+}
@@ -0,0 +1,287 @@
+//! Sync targets against a mock HTTP server, with facts from the fixture database:
+//! on 2024-05-13 task 111 has 1:30 and task 222 has 0:30
+use chrono::NaiveDate;
+use serde_json::json;
+
+use ham_cli::aggregation::AggregationOptions;
+use ham_cli::backend::TimeTrackerBackend;
+use ham_cli::config::Config;
+use ham_cli::date_range::DateRange;
+use ham_cli::sync::sync_days;
+use ham_cli::targets::harvest::HarvestBackend;
+use ham_cli::targets::jira::JiraBackend;
+use ham_cli::targets::toggl::TogglBackend;
+use ham_cli::targets::webhook::WebhookBackend;
+use ham_cli::utils::RunMode;
+
+mod common;
+use common::{fixture_db, MockServer};
+
+fn day() -> NaiveDate {
+    NaiveDate::from_ymd_opt(2024, 5, 13).unwrap()
+}
+
+fn config(toml: &str) -> Config {
+    toml::from_str(toml).unwrap()
+}
+
+async fn sync<B: TimeTrackerBackend>(
+    backend: &B,
+    name: &str,
+    config: &Config,
+    run_mode: RunMode,
+) -> Result<(), String> {
+    sync_days(
+        backend,
+        Some(fixture_db(name)),
+        DateRange::day(day()),
+        config,
+        &AggregationOptions::default(),
+        false,
+        run_mode,
+    )
+    .await
+}
+
+#[tokio::test]
+async fn jira_worklogs_synced() {
+    let server = MockServer::start(&[
+        ("GET /rest/api/2/myself", r#"{"accountId": "me"}"#),
+        (
+            "GET /rest/api/2/issue/PROJ-1/worklog",
+            r#"{"worklogs": [
+                {"id": "10001", "author": {"accountId": "me"},
+                 "started": "2024-05-13T12:00:00.000+0000", "timeSpentSeconds": 1200},
+                {"id": "10002", "author": {"accountId": "someone"},
+                 "started": "2024-05-13T12:00:00.000+0000", "timeSpentSeconds": 600},
+                {"id": "10003", "author": {"accountId": "me"},
+                 "started": "2024-05-10T12:00:00.000+0000", "timeSpentSeconds": 600}
+            ]}"#,
+        ),
+        (
+            "GET /rest/api/2/issue/PROJ-2/worklog",
+            r#"{"worklogs": []}"#,
+        ),
+        ("PUT /rest/api/2/issue/PROJ-1/worklog/10001", "{}"),
+        ("POST /rest/api/2/issue/PROJ-2/worklog", "{}"),
+    ]);
+    let config = config(&format!(
+        "[targets.jira]\n\
+        url = \"{}\"\n\
+        email = \"me@example.com\"\n\
+        api_token = \"secret\"\n\
+        tasks = {{ \"111\" = \"PROJ-1\", \"222\" = \"PROJ-2\" }}\n",
+        server.url
+    ));
+    let backend = JiraBackend::new(config.targets.jira.as_ref().unwrap()).unwrap();
+
+    sync(&backend, "jira_worklogs_synced", &config, RunMode::Normal)
+        .await
+        .unwrap();
+
+    let changes = server.changes();
+    assert_eq!(
+        changes
+            .iter()
+            .map(|(route, body)| (route.as_str(), body["timeSpentSeconds"].clone()))
+            .collect::<Vec<_>>(),
+        [
+            ("PUT /rest/api/2/issue/PROJ-1/worklog/10001", json!(5400)),
+            ("POST /rest/api/2/issue/PROJ-2/worklog", json!(1800)),
+        ]
+    );
+    assert_eq!(changes[0].1["comment"], "fixed it\ntested it");
+    assert!(server.requests()[0]
+        .header("authorization")
+        .unwrap()
+        .starts_with("Basic "));
+}
+
+#[tokio::test]
+async fn unmapped_jira_task_stops_sync() {
+    let server = MockServer::start(&[
+        ("GET /rest/api/2/myself", r#"{"accountId": "me"}"#),
+        (
+            "GET /rest/api/2/issue/PROJ-1/worklog",
+            r#"{"worklogs": []}"#,
+        ),
+    ]);
+    let config = config(&format!(
+        "[targets.jira]\n\
+        url = \"{}\"\n\
+        email = \"me@example.com\"\n\
+        api_token = \"secret\"\n\
+        tasks = {{ \"111\" = \"PROJ-1\" }}\n",
+        server.url
+    ));
+    let backend = JiraBackend::new(config.targets.jira.as_ref().unwrap()).unwrap();
+
+    let result = sync(
+        &backend,
+        "unmapped_jira_task_stops_sync",
+        &config,
+        RunMode::Normal,
+    )
+    .await;
+
+    assert_eq!(
+        result.unwrap_err(),
+        "No Jira mapping for task 222 ('Write docs')"
+    );
+    assert!(server.changes().is_empty());
+}
+
+#[tokio::test]
+async fn harvest_entries_synced() {
+    let server = MockServer::start(&[
+        ("GET /v2/users/me", r#"{"id": 5}"#),
+        (
+            "GET /v2/time_entries",
+            r#"{"time_entries": [
+                {"id": 99, "spent_date": "2024-05-13", "hours": 0.5, "notes": null,
+                 "project": {"id": 10}, "task": {"id": 20}}
+            ], "next_page": null}"#,
+        ),
+        ("PATCH /v2/time_entries/99", "{}"),
+        ("POST /v2/time_entries", "{}"),
+    ]);
+    let config = config(&format!(
+        "[targets.harvest]\n\
+        url = \"{}\"\n\
+        account_id = \"42\"\n\
+        api_token = \"secret\"\n\
+        tasks = {{ \"111\" = \"10/20\", \"222\" = \"10/21\" }}\n",
+        server.url
+    ));
+    let backend = HarvestBackend::new(config.targets.harvest.as_ref().unwrap()).unwrap();
+
+    sync(&backend, "harvest_entries_synced", &config, RunMode::Normal)
+        .await
+        .unwrap();
+
+    let changes = server.changes();
+    assert_eq!(changes[0].0, "PATCH /v2/time_entries/99");
+    assert_eq!(changes[0].1["hours"], 1.5);
+    assert_eq!(changes[1].0, "POST /v2/time_entries");
+    let added = &changes[1].1;
+    assert_eq!(added["user_id"], 5);
+    assert_eq!(added["project_id"], 10);
+    assert_eq!(added["task_id"], 21);
+    assert_eq!(added["spent_date"], "2024-05-13");
+    assert_eq!(added["hours"], 0.5);
+    let listing = &server.requests()[1];
+    assert!(listing.query.contains("user_id=5"));
+    assert_eq!(listing.header("harvest-account-id"), Some("42"));
+}
+
+#[tokio::test]
+async fn toggl_entries_synced() {
+    let server = MockServer::start(&[
+        ("GET /api/v9/me", r#"{"id": 7}"#),
+        (
+            "GET /api/v9/me/time_entries",
+            r#"[
+                {"id": 1, "workspace_id": 1, "project_id": 300,
+                 "start": "2024-05-13T12:00:00+00:00", "duration": 600, "description": "Fix parser"},
+                {"id": 2, "workspace_id": 99, "project_id": 301,
+                 "start": "2024-05-13T12:00:00+00:00", "duration": 600, "description": "elsewhere"}
+            ]"#,
+        ),
+        ("PUT /api/v9/workspaces/1/time_entries/1", "{}"),
+        ("POST /api/v9/workspaces/1/time_entries", "{}"),
+    ]);
+    let config = config(&format!(
+        "[targets.toggl]\n\
+        url = \"{}\"\n\
+        workspace_id = 1\n\
+        api_token = \"secret\"\n\
+        tasks = {{ \"111\" = \"300\", \"222\" = \"301\" }}\n",
+        server.url
+    ));
+    let backend = TogglBackend::new(config.targets.toggl.as_ref().unwrap()).unwrap();
+
+    sync(&backend, "toggl_entries_synced", &config, RunMode::Normal)
+        .await
+        .unwrap();
+
+    let changes = server.changes();
+    assert_eq!(
+        changes
+            .iter()
+            .map(|(route, body)| (
+                route.as_str(),
+                body["project_id"].clone(),
+                body["duration"].clone(),
+                body["description"].clone()
+            ))
+            .collect::<Vec<_>>(),
+        [
+            (
+                "PUT /api/v9/workspaces/1/time_entries/1",
+                json!(300),
+                json!(5400),
+                json!("Fix parser")
+            ),
+            (
+                "POST /api/v9/workspaces/1/time_entries",
+                json!(301),
+                json!(1800),
+                json!("Write docs")
+            ),
+        ]
+    );
+}
+
+#[tokio::test]
+async fn webhook_called_per_task() {
+    let server = MockServer::start(&[("POST /hook", "{}")]);
+    let config = config(&format!(
+        "[targets.webhook]\n\
+        url = \"{}/hook\"\n\
+        user = \"me\"\n\
+        headers = {{ \"X-Token\" = \"secret\" }}\n\
+        tasks = {{ \"222\" = \"DOCS\" }}\n",
+        server.url
+    ));
+    let backend = WebhookBackend::new(config.targets.webhook.as_ref().unwrap());
+
+    sync(
+        &backend,
+        "webhook_called_per_task",
+        &config,
+        RunMode::Normal,
+    )
+    .await
+    .unwrap();
+
+    let changes = server.changes();
+    assert_eq!(
+        changes[0].1,
+        json!({
+            "user": "me",
+            "date": "2024-05-13",
+            "task_id": "111",
+            "title": "Fix parser",
+            "time": 5400,
+            "comment": "fixed it\ntested it",
+        })
+    );
+    assert_eq!(changes[1].1["task_id"], "DOCS");
+    assert_eq!(server.requests()[0].header("x-token"), Some("secret"));
+}
+
+#[tokio::test]
+async fn dry_run_sends_nothing() {
+    let server = MockServer::start(&[("POST /hook", "{}")]);
+    let config = config(&format!(
+        "[targets.webhook]\nurl = \"{}/hook\"\n",
+        server.url
+    ));
+    let backend = WebhookBackend::new(config.targets.webhook.as_ref().unwrap());
+
+    sync(&backend, "dry_run_sends_nothing", &config, RunMode::DryRun)
+        .await
+        .unwrap();
+
+    assert!(server.requests().is_empty());
+}
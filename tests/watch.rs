@@ -0,0 +1,76 @@
+//! Finding facts closed since the last look, in the fixture database
+use chrono::{DateTime, Local, TimeZone};
+
+use ham_cli::hamster::HamsterData;
+use ham_cli::watch::FactMark;
+
+mod common;
+use common::fixture_db;
+
+fn time(day: u32, hour: u32, minute: u32) -> DateTime<Local> {
+    Local
+        .with_ymd_and_hms(2024, 5, day, hour, minute, 0)
+        .unwrap()
+}
+
+fn ids(hamster_data: &HamsterData, mark: FactMark) -> Vec<i64> {
+    hamster_data
+        .get_closed_facts_after(mark.fact_id, mark.end_time)
+        .unwrap()
+        .iter()
+        .map(|fact| fact.id)
+        .collect()
+}
+
+#[test]
+fn last_closed_fact_found() {
+    let hamster_data = HamsterData::open(Some(fixture_db("watch-last"))).unwrap();
+
+    assert_eq!(hamster_data.last_closed_fact(), Some((4, time(14, 9, 15))));
+}
+
+#[test]
+fn added_and_stopped_facts_found() {
+    let hamster_data = HamsterData::open(Some(fixture_db("watch-closed"))).unwrap();
+    let (fact_id, end_time) = hamster_data.last_closed_fact().unwrap();
+    let mark = FactMark { fact_id, end_time };
+    assert!(ids(&hamster_data, mark).is_empty());
+
+    let running = hamster_data
+        .add_fact("coding", "Work", time(14, 10, 0), None, "", &[])
+        .unwrap();
+    let added = hamster_data
+        .add_fact(
+            "coding",
+            "Work",
+            time(14, 9, 30),
+            Some(time(14, 9, 45)),
+            "",
+            &[],
+        )
+        .unwrap();
+    // running facts aren't picked up until they're stopped
+    assert_eq!(ids(&hamster_data, mark), [added]);
+
+    let closed = hamster_data
+        .get_closed_facts_after(mark.fact_id, mark.end_time)
+        .unwrap();
+    let mark = mark.after(&closed);
+    assert!(ids(&hamster_data, mark).is_empty());
+
+    hamster_data.stop_running(time(14, 11, 0)).unwrap();
+    assert_eq!(ids(&hamster_data, mark), [running]);
+}
+
+#[test]
+fn locked_database_is_an_error() {
+    let db = fixture_db("watch-locked");
+    let hamster_data = HamsterData::open(Some(db.clone())).unwrap();
+    // Hamster in the middle of writing
+    let writer = sqlite::open(&db).unwrap();
+    writer.execute("BEGIN EXCLUSIVE").unwrap();
+
+    assert!(hamster_data
+        .get_closed_facts_after(0, time(13, 0, 0))
+        .is_err());
+}